@@ -0,0 +1,212 @@
+use anyhow::anyhow;
+use clap::{ArgEnum, Parser};
+use many::server::module::events::{ListArgs, ListReturns};
+use many::types::events::{EventFilter, EventId, EventLog};
+use many::types::identity::CoseKeyIdentity;
+use many::types::{CborRange, SortOrder};
+use many::Identity;
+use many_client::ManyClient;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Parser)]
+pub struct EventsOpt {
+    #[clap(subcommand)]
+    subcommand: EventsSubCommand,
+}
+
+#[derive(Parser)]
+enum EventsSubCommand {
+    /// Export the event log of a server to JSON Lines or CSV. When appending to
+    /// an existing file, the export resumes after the last event in it.
+    Export(ExportOpt),
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+#[derive(Parser)]
+struct ExportOpt {
+    /// The server to call. It MUST implement the events attribute (4).
+    server: url::Url,
+
+    /// A pem file to sign the messages. If this is omitted, the messages will be anonymous.
+    #[clap(long)]
+    pem: Option<PathBuf>,
+
+    /// The output format.
+    #[clap(long, arg_enum, default_value = "jsonl")]
+    format: ExportFormat,
+
+    /// The file to write to. Events are appended if it exists. Defaults to stdout.
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+
+    /// Only export events after this event ID (in hexadecimal). Defaults to the
+    /// last event of the output file, if any.
+    #[clap(long)]
+    after: Option<String>,
+
+    /// The number of events to request per call.
+    #[clap(long, default_value = "100")]
+    page_size: u64,
+}
+
+pub fn events(opts: EventsOpt) -> Result<(), anyhow::Error> {
+    match opts.subcommand {
+        EventsSubCommand::Export(o) => export(o),
+    }
+}
+
+fn export(o: ExportOpt) -> Result<(), anyhow::Error> {
+    let key = o.pem.map_or_else(
+        || Ok(CoseKeyIdentity::anonymous()),
+        |p| CoseKeyIdentity::from_pem(&std::fs::read_to_string(&p)?).map_err(|e| anyhow!(e)),
+    )?;
+    let client = ManyClient::new(o.server, Identity::anonymous(), key).map_err(|e| anyhow!(e))?;
+
+    let after = match (&o.after, &o.output) {
+        (Some(after), _) => Some(EventId::from(hex::decode(after)?)),
+        (None, Some(path)) => last_event_id(path, o.format)?,
+        (None, None) => None,
+    };
+    if let Some(id) = &after {
+        info!("Resuming after event {}", hex::encode(id));
+    }
+
+    let (mut output, is_new): (Box<dyn Write>, bool) = match &o.output {
+        Some(path) => {
+            let is_new = std::fs::metadata(path).map_or(true, |m| m.len() == 0);
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            (Box::new(file), is_new)
+        }
+        None => (Box::new(std::io::stdout()), true),
+    };
+
+    if o.format == ExportFormat::Csv && is_new {
+        writeln!(
+            output,
+            "id,time,kind,{},content",
+            CSV_CONTENT_COLUMNS.join(",")
+        )?;
+    }
+
+    let filter = after.map(|id| EventFilter {
+        id_range: Some(CborRange {
+            start: Bound::Excluded(id),
+            end: Bound::Unbounded,
+        }),
+        ..Default::default()
+    });
+    let mut cursor = None;
+    let mut total = 0;
+    loop {
+        let response = client.call_(
+            "events.list",
+            ListArgs {
+                count: Some(o.page_size),
+                order: Some(SortOrder::Ascending),
+                filter: filter.clone(),
+                cursor,
+            },
+        )?;
+        let ListReturns {
+            events,
+            cursor: next,
+            ..
+        } = minicbor::decode(&response)?;
+
+        for log in &events {
+            write_event(&mut output, log, o.format)?;
+        }
+        total += events.len();
+
+        cursor = next;
+        if cursor.is_none() || events.is_empty() {
+            break;
+        }
+    }
+
+    output.flush()?;
+    info!("Exported {} events", total);
+    Ok(())
+}
+
+/// The fields of the events that have their own CSV column, when they have
+/// them. The whole content of the event is in the last column.
+const CSV_CONTENT_COLUMNS: [&str; 5] = ["account", "from", "to", "symbol", "amount"];
+
+fn write_event(
+    output: &mut dyn Write,
+    log: &EventLog,
+    format: ExportFormat,
+) -> std::io::Result<()> {
+    match format {
+        ExportFormat::Jsonl => writeln!(output, "{}", serde_json::to_string(log)?),
+        ExportFormat::Csv => {
+            let content = serde_json::to_value(&log.content)?;
+            let columns: Vec<String> = CSV_CONTENT_COLUMNS
+                .iter()
+                .map(|name| match &content[name] {
+                    serde_json::Value::Null => String::new(),
+                    serde_json::Value::String(s) => csv_escape(s),
+                    value => csv_escape(&value.to_string()),
+                })
+                .collect();
+            writeln!(
+                output,
+                "{},{},{},{},{}",
+                hex::encode(&log.id),
+                serde_json::to_string(&log.time)?,
+                log.kind(),
+                columns.join(","),
+                csv_escape(&content.to_string()),
+            )
+        }
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// The ID of the last event of a previous export, if any.
+fn last_event_id(path: &Path, format: ExportFormat) -> Result<Option<EventId>, anyhow::Error> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+
+    let id = match (last, format) {
+        (None, _) => return Ok(None),
+        (Some(line), ExportFormat::Jsonl) => {
+            let value: serde_json::Value = serde_json::from_str(&line)?;
+            value["id"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Last line of the output has no event ID"))?
+                .to_string()
+        }
+        (Some(line), ExportFormat::Csv) => line.split(',').next().unwrap_or_default().to_string(),
+    };
+
+    // Only the CSV header was written.
+    if id == "id" {
+        return Ok(None);
+    }
+    Ok(Some(EventId::from(hex::decode(id)?)))
+}
@@ -21,6 +21,8 @@ use std::time::Duration;
 use tracing::{error, info, level_filters::LevelFilter, trace};
 use url::Url;
 
+mod events;
+
 #[derive(Parser)]
 struct Opts {
     /// Increase output logging verbosity to DEBUG level.
@@ -54,6 +56,9 @@ enum SubCommand {
 
     /// Get the token ID per string of a ledger's token.
     GetTokenId(GetTokenIdOpt),
+
+    /// Work with the event log of a server.
+    Events(events::EventsOpt),
 }
 
 #[derive(Parser)]
@@ -486,5 +491,11 @@ fn main() {
 
             println!("{}", id);
         }
+        SubCommand::Events(o) => {
+            if let Err(err) = events::events(o) {
+                error!("{}", err);
+                std::process::exit(1);
+            }
+        }
     }
 }
@@ -3,12 +3,24 @@ use many::message::{
     decode_response_from_cose_sign1, encode_cose_sign1_from_request, RequestMessage,
     RequestMessageBuilder, ResponseMessage,
 };
+use many::protocol::AttributeSet;
 use many::server::module::base::Status;
+use many::server::module::blockchain::attributes::{ProofAttribute, PROOF};
+use many::server::module::blockchain::invalid_proof;
+use many::server::module::kvstore::{
+    key_not_found, BeginUploadArgs, BeginUploadReturns, CommitUploadArgs, GetArgs, GetReturns,
+    KeyAcl, PutChunkArgs, ValueHasher, KVSTORE_CHUNK_MAX_SIZE, KVSTORE_READ_MAX_SIZE,
+};
 use many::types::identity::CoseKeyIdentity;
+use many::types::proof::Proof;
+use many::types::CborRange;
 use many::{Identity, ManyError};
+use minicbor::bytes::ByteVec;
 use minicbor::Encode;
 use reqwest::{IntoUrl, Url};
 use std::fmt::Formatter;
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Bound;
 
 #[derive(Clone)]
 pub struct ManyClient {
@@ -61,23 +73,32 @@ impl ManyClient {
         decode_response_from_cose_sign1(cose_sign1, None).map_err(ManyError::deserialization_error)
     }
 
-    pub fn call_raw<M>(&self, method: M, argument: &[u8]) -> Result<ResponseMessage, ManyError>
-    where
-        M: Into<String>,
-    {
+    fn message(
+        &self,
+        method: String,
+        argument: &[u8],
+        attributes: AttributeSet,
+    ) -> Result<RequestMessage, ManyError> {
         let mut nonce = [0u8; 16];
         rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce);
 
-        let message: RequestMessage = RequestMessageBuilder::default()
+        RequestMessageBuilder::default()
             .version(1)
             .from(self.id.identity)
             .to(self.to)
-            .method(method.into())
+            .method(method)
             .data(argument.to_vec())
             .nonce(nonce.to_vec())
+            .attributes(attributes)
             .build()
-            .map_err(|_| ManyError::internal_server_error())?;
+            .map_err(|_| ManyError::internal_server_error())
+    }
 
+    pub fn call_raw<M>(&self, method: M, argument: &[u8]) -> Result<ResponseMessage, ManyError>
+    where
+        M: Into<String>,
+    {
+        let message = self.message(method.into(), argument, AttributeSet::new())?;
         self.send_message(message)
     }
 
@@ -100,6 +121,30 @@ impl ManyClient {
         self.call(method, argument)?.data
     }
 
+    /// Call a method reading the state `key`, asking the server for a proof of
+    /// its result. The proof is verified with [Proof::verify] against a trusted
+    /// root, e.g. the app hash of a committed block. Returns the value of the
+    /// key, or None if the proof shows it has no value.
+    pub fn call_with_proof<M, I>(
+        &self,
+        method: M,
+        argument: I,
+        key: &[u8],
+        root: &[u8],
+    ) -> Result<(Option<Vec<u8>>, Proof), ManyError>
+    where
+        M: Into<String>,
+        I: Encode<()>,
+    {
+        let bytes: Vec<u8> = minicbor::to_vec(argument)
+            .map_err(|e| ManyError::serialization_error(e.to_string()))?;
+        let attributes = AttributeSet::from_iter([PROOF]);
+        let message = self.message(method.into(), bytes.as_slice(), attributes)?;
+
+        let response = self.send_message(message)?;
+        verify_response(response, key, root)
+    }
+
     pub fn status(&self) -> Result<Status, ManyError> {
         let response = self.call_("status", ())?;
 
@@ -107,4 +152,216 @@ impl ManyClient {
             .map_err(|e| ManyError::deserialization_error(e.to_string()))?;
         Ok(status)
     }
+
+    /// Store a value of any size in a key-value store, uploading it in chunks.
+    pub fn kvstore_put_stream<R: Read>(
+        &self,
+        key: &[u8],
+        mut reader: R,
+        acl: Option<KeyAcl>,
+    ) -> Result<(), ManyError> {
+        let BeginUploadReturns { upload } = self.decode_call(
+            "kvstore.beginUpload",
+            BeginUploadArgs {
+                key: ByteVec::from(key.to_vec()),
+                acl,
+            },
+        )?;
+
+        let mut buffer = vec![0u8; KVSTORE_CHUNK_MAX_SIZE];
+        let mut hasher = ValueHasher::new();
+        let mut size = 0u64;
+        let mut index = 0u64;
+        loop {
+            let len = read_full(&mut reader, &mut buffer)
+                .map_err(|e| ManyError::unknown(e.to_string()))?;
+            // An empty value is uploaded as a single empty chunk.
+            if len == 0 && index > 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..len]);
+            self.call_(
+                "kvstore.putChunk",
+                PutChunkArgs {
+                    upload: upload.clone(),
+                    index,
+                    data: ByteVec::from(buffer[..len].to_vec()),
+                },
+            )?;
+            size += len as u64;
+            index += 1;
+
+            if len < buffer.len() {
+                break;
+            }
+        }
+
+        self.call_(
+            "kvstore.commitUpload",
+            CommitUploadArgs {
+                upload,
+                size,
+                hash: hasher.finalize(),
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Read a value of any size from a key-value store, in ranges, writing it to
+    /// `writer`. Returns the number of bytes written.
+    pub fn kvstore_get_stream<W: Write>(
+        &self,
+        key: &[u8],
+        mut writer: W,
+    ) -> Result<u64, ManyError> {
+        let mut offset = 0u64;
+        loop {
+            let GetReturns { value, size } = self.decode_call(
+                "kvstore.get",
+                GetArgs {
+                    key: ByteVec::from(key.to_vec()),
+                    range: Some(CborRange {
+                        start: Bound::Included(offset),
+                        end: Bound::Excluded(offset + KVSTORE_READ_MAX_SIZE),
+                    }),
+                },
+            )?;
+            let value = value
+                .ok_or_else(|| many::server::module::kvstore::key_not_found(hex::encode(key)))?;
+
+            writer
+                .write_all(&value)
+                .map_err(|e| ManyError::unknown(e.to_string()))?;
+            offset += value.len() as u64;
+
+            if value.is_empty() || size.map_or(true, |size| offset >= size) {
+                return Ok(offset);
+            }
+        }
+    }
+
+    fn decode_call<M, I, O>(&self, method: M, argument: I) -> Result<O, ManyError>
+    where
+        M: Into<String>,
+        I: Encode<()>,
+        O: for<'b> minicbor::Decode<'b, ()>,
+    {
+        let response = self.call_(method, argument)?;
+        minicbor::decode(response.as_slice())
+            .map_err(|e| ManyError::deserialization_error(e.to_string()))
+    }
+}
+
+/// Verify the proof of a response reading the state `key`. An empty result, or
+/// a key not found error, means the key has no value.
+fn verify_response(
+    response: ResponseMessage,
+    key: &[u8],
+    root: &[u8],
+) -> Result<(Option<Vec<u8>>, Proof), ManyError> {
+    let proof = response.attributes.get::<ProofAttribute>()?.proof;
+    if proof.key.as_slice() != key {
+        return Err(invalid_proof());
+    }
+
+    let value = match response.data {
+        Ok(data) if data.is_empty() => None,
+        Ok(data) => Some(data),
+        Err(e) if e.code() == key_not_found("").code() => None,
+        Err(e) => return Err(e),
+    };
+    proof.verify(root, value.as_deref())?;
+    Ok((value, proof))
+}
+
+/// Read from `reader` until `buffer` is full or the end of the stream is reached.
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use many::types::proof::MerkleTree;
+
+    fn response(data: Result<Vec<u8>, ManyError>, proof: Proof) -> ResponseMessage {
+        ResponseMessage {
+            data,
+            ..Default::default()
+        }
+        .with_attribute(ProofAttribute::new(proof).into())
+    }
+
+    #[test]
+    fn inclusion_proof() {
+        let tree = MerkleTree::new([("k1", "v1"), ("k2", "v2")]);
+        let root = tree.root();
+
+        let (value, _) = verify_response(
+            response(Ok(b"v1".to_vec()), tree.prove(1, b"k1")),
+            b"k1",
+            &root,
+        )
+        .unwrap();
+        assert_eq!(value.as_deref(), Some(&b"v1"[..]));
+
+        // Another value for the key.
+        let result = verify_response(
+            response(Ok(b"v2".to_vec()), tree.prove(1, b"k1")),
+            b"k1",
+            &root,
+        );
+        assert_eq!(result.unwrap_err(), invalid_proof());
+    }
+
+    #[test]
+    fn wrong_key() {
+        let tree = MerkleTree::new([("k1", "v1"), ("k2", "v2")]);
+        let root = tree.root();
+
+        // The value of k2, with a valid proof for k2, as the value of k1.
+        let result = verify_response(
+            response(Ok(b"v2".to_vec()), tree.prove(1, b"k2")),
+            b"k1",
+            &root,
+        );
+        assert_eq!(result.unwrap_err(), invalid_proof());
+    }
+
+    #[test]
+    fn exclusion_proof() {
+        let tree = MerkleTree::new([("k1", "v1"), ("k3", "v3")]);
+        let root = tree.root();
+
+        let (value, _) =
+            verify_response(response(Ok(vec![]), tree.prove(1, b"k2")), b"k2", &root).unwrap();
+        assert_eq!(value, None);
+
+        let not_found = Err(key_not_found(hex::encode(b"k2")));
+        let (value, _) =
+            verify_response(response(not_found, tree.prove(1, b"k2")), b"k2", &root).unwrap();
+        assert_eq!(value, None);
+
+        // A key with a value is not absent.
+        let result = verify_response(response(Ok(vec![]), tree.prove(1, b"k1")), b"k1", &root);
+        assert_eq!(result.unwrap_err(), invalid_proof());
+
+        // Other errors are returned as is.
+        let result = verify_response(
+            response(Err(ManyError::unknown("error")), tree.prove(1, b"k2")),
+            b"k2",
+            &root,
+        );
+        assert_eq!(result.unwrap_err(), ManyError::unknown("error"));
+    }
 }
@@ -168,6 +168,28 @@ impl ManyServer {
             .find(|x| x.info().endpoints.contains(&message.method))
             .cloned()
     }
+
+    /// Execute a message with the module implementing its method, without an
+    /// envelope to verify. The sender of the message is trusted, so this must
+    /// only be used for messages built by the server itself, e.g. to execute an
+    /// approved multisig transaction as the account.
+    ///
+    /// The message is still validated by the module, with an empty envelope,
+    /// so endpoints that require a specific envelope (e.g. WebAuthn requests)
+    /// refuse it.
+    pub async fn execute_message(
+        this: &Arc<Mutex<Self>>,
+        message: RequestMessage,
+    ) -> Result<ResponseMessage, ManyError> {
+        let module = this.lock().unwrap().find_module(&message);
+        match module {
+            Some(m) => {
+                m.validate(&message, &CoseSign1::default())?;
+                m.execute(message).await
+            }
+            None => Err(ManyError::invalid_method_name(message.method)),
+        }
+    }
 }
 
 impl base::BaseModuleBackend for ManyServer {
@@ -385,6 +407,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn execute_message_validates() {
+        use crate::server::module::idstore::{IdStoreModule, MockIdStoreModuleBackend};
+
+        let id = generate_random_eddsa_identity();
+        let server = ManyServer::simple("test", id.clone(), None, None);
+        server
+            .lock()
+            .unwrap()
+            .add_module(IdStoreModule::new(Arc::new(Mutex::new(
+                MockIdStoreModuleBackend::new(),
+            ))));
+
+        let message = RequestMessage {
+            version: Some(1),
+            from: Some(id.identity),
+            method: "idstore.store".to_string(),
+            data: minicbor::to_vec(()).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            smol::block_on(ManyServer::execute_message(&server, message)).unwrap_err(),
+            ManyError::non_webauthn_request_denied("idstore.store")
+        );
+    }
+
     #[test]
     fn validate_time() {
         let timestamp = SystemTime::now();
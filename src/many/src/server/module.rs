@@ -28,6 +28,7 @@ reexport_module!(
     kvstore: _3_kvstore + _7_kvstore_commands;
     r#async: _8_async;
     account: _9_account;
+    escrow: _10_escrow;
     abci_backend: _1000_abci_backend;
     abci_frontend: _1001_abci_frontend;
     idstore: _1002_idstore;
@@ -4,13 +4,19 @@ use many_macros::many_module;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
+mod backend;
+mod credentials;
 pub mod errors;
 mod get;
+mod recall_phrase;
 mod store;
 pub mod types;
 
+pub use backend::*;
+pub use credentials::*;
 pub use errors::*;
 pub use get::*;
+pub use recall_phrase::*;
 pub use store::*;
 pub use types::*;
 
@@ -24,12 +30,31 @@ pub trait IdStoreModuleBackend: Send {
         args: GetFromRecallPhraseArgs,
     ) -> Result<GetReturns, ManyError>;
     fn get_from_address(&self, args: GetFromAddressArgs) -> Result<GetReturns, ManyError>;
+
+    /// Add a credential to an address, e.g. a new or backup authenticator. The
+    /// recall phrase of the address is unchanged.
+    #[many(check_webauthn, deny_anonymous)]
+    fn add_credential(
+        &mut self,
+        sender: &Identity,
+        args: AddCredentialArgs,
+    ) -> Result<AddCredentialReturns, ManyError>;
+
+    /// Revoke a credential of an address. The last credential of an address
+    /// cannot be revoked.
+    #[many(check_webauthn, deny_anonymous)]
+    fn revoke(&mut self, sender: &Identity, args: RevokeArgs) -> Result<RevokeReturns, ManyError>;
+
+    /// List the credentials of an address.
+    #[many(check_webauthn, deny_anonymous)]
+    fn list(&self, sender: &Identity, args: ListArgs) -> Result<ListReturns, ManyError>;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::server::module::testutils::call_module_envelope;
+    use crate::server::module::EmptyReturn;
     use crate::{
         server::module::testutils::call_module_cbor,
         types::identity::{cose::testsutils::generate_random_eddsa_identity, testing::identity},
@@ -83,6 +108,124 @@ mod tests {
         assert_eq!(store_returns.0, ret.0);
     }
 
+    fn webauthn_envelope() -> coset::CoseSign1 {
+        let mut envelope = coset::CoseSign1::default();
+        envelope
+            .protected
+            .header
+            .rest
+            .push((coset::Label::Text("webauthn".to_string()), true.into()));
+        envelope
+    }
+
+    #[test]
+    fn add_credential() {
+        let id = generate_random_eddsa_identity();
+        let data = AddCredentialArgs {
+            address: tests::identity(1),
+            cred_id: CredentialId(ByteVec::from(Vec::from([2u8; 16]))),
+            public_key: PublicKey(ByteVec::from(id.key.unwrap().to_vec().unwrap())),
+        };
+        let mut mock: MockIdStoreModuleBackend = MockIdStoreModuleBackend::new();
+        mock.expect_add_credential()
+            .with(
+                predicate::eq(tests::identity(1)),
+                predicate::eq(data.clone()),
+            )
+            .times(1)
+            .returning(|_, _| Ok(EmptyReturn));
+
+        let module = super::IdStoreModule::new(Arc::new(Mutex::new(mock)));
+        let payload = minicbor::to_vec(data).unwrap();
+        assert!(call_module_envelope(
+            1,
+            &module,
+            "idstore.addCredential",
+            payload.clone(),
+            &coset::CoseSign1::default(),
+        )
+        .is_err());
+        call_module_envelope(
+            1,
+            &module,
+            "idstore.addCredential",
+            payload,
+            &webauthn_envelope(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn revoke() {
+        let data = RevokeArgs {
+            address: tests::identity(1),
+            cred_id: CredentialId(ByteVec::from(Vec::from([2u8; 16]))),
+        };
+        let mut mock: MockIdStoreModuleBackend = MockIdStoreModuleBackend::new();
+        mock.expect_revoke()
+            .with(
+                predicate::eq(tests::identity(1)),
+                predicate::eq(data.clone()),
+            )
+            .times(1)
+            .returning(|_, _| Ok(EmptyReturn));
+
+        let module = super::IdStoreModule::new(Arc::new(Mutex::new(mock)));
+        call_module_envelope(
+            1,
+            &module,
+            "idstore.revoke",
+            minicbor::to_vec(data).unwrap(),
+            &webauthn_envelope(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn list() {
+        let id = generate_random_eddsa_identity();
+        let data = ListArgs {
+            address: tests::identity(1),
+        };
+        let ret = ListReturns {
+            credentials: vec![Credential {
+                cred_id: CredentialId(ByteVec::from(Vec::from([1u8; 16]))),
+                public_key: PublicKey(ByteVec::from(id.key.unwrap().to_vec().unwrap())),
+            }],
+        };
+        let mut mock: MockIdStoreModuleBackend = MockIdStoreModuleBackend::new();
+        mock.expect_list()
+            .with(
+                predicate::eq(tests::identity(1)),
+                predicate::eq(data.clone()),
+            )
+            .times(1)
+            .return_const(Ok(ret.clone()));
+
+        let module = super::IdStoreModule::new(Arc::new(Mutex::new(mock)));
+        assert!(call_module_envelope(
+            0,
+            &module,
+            "idstore.list",
+            minicbor::to_vec(data.clone()).unwrap(),
+            &webauthn_envelope(),
+        )
+        .is_err());
+        let list_returns: ListReturns = minicbor::decode(
+            &call_module_envelope(
+                1,
+                &module,
+                "idstore.list",
+                minicbor::to_vec(data).unwrap(),
+                &webauthn_envelope(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(list_returns, ret);
+    }
+
     #[test]
     fn get_from_recall_phrase() {
         let id = generate_random_eddsa_identity();
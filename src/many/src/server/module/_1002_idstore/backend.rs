@@ -0,0 +1,395 @@
+use super::*;
+use crate::server::module::EmptyReturn;
+use minicbor::{Decode, Encode};
+use std::collections::BTreeMap;
+
+/// Length of the recall phrases generated by default.
+pub const RECALL_PHRASE_DEFAULT_LENGTH: usize = 4;
+
+/// Number of seeds tried before giving up on generating a recall phrase that is
+/// not already used.
+pub const RECALL_PHRASE_MAX_ATTEMPTS: u64 = 32;
+
+/// Credential IDs are at least 16 bytes and at most 1023 bytes in WebAuthn.
+const CREDENTIAL_ID_MIN_LEN: usize = 16;
+const CREDENTIAL_ID_MAX_LEN: usize = 1023;
+
+/// What an [IdStore] keeps for an address.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct IdStoreEntry {
+    #[n(0)]
+    pub recall_phrase: RecallPhrase,
+
+    /// The credentials of the address, from the oldest to the most recent.
+    #[n(1)]
+    pub credentials: Vec<Credential>,
+}
+
+/// Persistence of the entries of an [IdStore].
+pub trait IdStoreStorage: Send {
+    fn get(&self, address: &Identity) -> Result<Option<IdStoreEntry>, ManyError>;
+
+    /// The address a recall phrase was generated for.
+    fn get_address(&self, recall_phrase: &RecallPhrase) -> Result<Option<Identity>, ManyError>;
+
+    /// Persist an entry, created or modified. Its recall phrase never changes.
+    fn put(&mut self, address: &Identity, entry: &IdStoreEntry) -> Result<(), ManyError>;
+
+    /// The seed of the next recall phrase, persisted so phrases are
+    /// deterministic across restarts.
+    fn next_seed(&self) -> Result<u64, ManyError>;
+
+    fn set_next_seed(&mut self, next: u64) -> Result<(), ManyError>;
+}
+
+/// An [IdStoreStorage] that keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryIdStoreStorage {
+    entries: BTreeMap<Identity, IdStoreEntry>,
+    recall_phrases: BTreeMap<RecallPhrase, Identity>,
+    next_seed: u64,
+}
+
+impl IdStoreStorage for InMemoryIdStoreStorage {
+    fn get(&self, address: &Identity) -> Result<Option<IdStoreEntry>, ManyError> {
+        Ok(self.entries.get(address).cloned())
+    }
+
+    fn get_address(&self, recall_phrase: &RecallPhrase) -> Result<Option<Identity>, ManyError> {
+        Ok(self.recall_phrases.get(recall_phrase).copied())
+    }
+
+    fn put(&mut self, address: &Identity, entry: &IdStoreEntry) -> Result<(), ManyError> {
+        self.recall_phrases
+            .insert(entry.recall_phrase.clone(), *address);
+        self.entries.insert(*address, entry.clone());
+        Ok(())
+    }
+
+    fn next_seed(&self) -> Result<u64, ManyError> {
+        Ok(self.next_seed)
+    }
+
+    fn set_next_seed(&mut self, next: u64) -> Result<(), ManyError> {
+        self.next_seed = next;
+        Ok(())
+    }
+}
+
+/// A reusable IdStore backend. Recall phrases are generated from the BIP-39
+/// English wordlist with [generate_recall_phrase], seeded by a persisted counter
+/// and the address, so every replica generates the same phrases. A phrase
+/// already in use is never reused. It implements [IdStoreModuleBackend]
+/// directly.
+///
+/// Only an address can add or revoke its own credentials. The recall phrase
+/// and the address resolve to the most recent credential.
+pub struct IdStore<S: IdStoreStorage> {
+    storage: S,
+    phrase_length: usize,
+}
+
+impl<S: IdStoreStorage> IdStore<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            phrase_length: RECALL_PHRASE_DEFAULT_LENGTH,
+        }
+    }
+
+    /// Set the number of words of the recall phrases generated.
+    pub fn with_phrase_length(mut self, length: usize) -> Self {
+        self.phrase_length = length;
+        self
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    fn get(&self, address: &Identity) -> Result<IdStoreEntry, ManyError> {
+        self.storage
+            .get(address)?
+            .ok_or_else(|| errors::entry_not_found(address))
+    }
+
+    /// Generate a recall phrase for an address that is not used yet.
+    fn generate(&mut self, address: &Identity) -> Result<RecallPhrase, ManyError> {
+        let first = self.storage.next_seed()?;
+        for seed in first..first.saturating_add(RECALL_PHRASE_MAX_ATTEMPTS) {
+            let recall_phrase = generate_recall_phrase(
+                &[&seed.to_be_bytes()[..], &address.to_vec()].concat(),
+                self.phrase_length,
+            )?;
+            if self.storage.get_address(&recall_phrase)?.is_none() {
+                self.storage.set_next_seed(seed + 1)?;
+                return Ok(recall_phrase);
+            }
+        }
+        Err(errors::recall_phrase_generation_failed())
+    }
+}
+
+fn check_owner(sender: &Identity, address: &Identity) -> Result<(), ManyError> {
+    if sender == address {
+        Ok(())
+    } else {
+        Err(errors::sender_is_not_owner(address))
+    }
+}
+
+fn check_credential(address: &Identity, cred_id: &CredentialId) -> Result<(), ManyError> {
+    if address.is_anonymous() {
+        return Err(errors::invalid_address(address));
+    }
+    if !(CREDENTIAL_ID_MIN_LEN..=CREDENTIAL_ID_MAX_LEN).contains(&cred_id.0.len()) {
+        return Err(errors::invalid_credential_id(hex::encode(
+            cred_id.0.as_slice(),
+        )));
+    }
+    Ok(())
+}
+
+fn latest(entry: IdStoreEntry) -> Result<GetReturns, ManyError> {
+    entry
+        .credentials
+        .into_iter()
+        .last()
+        .map(|c| GetReturns {
+            cred_id: c.cred_id,
+            public_key: c.public_key,
+        })
+        .ok_or_else(errors::last_credential)
+}
+
+impl<S: IdStoreStorage> IdStoreModuleBackend for IdStore<S> {
+    fn store(&mut self, sender: &Identity, args: StoreArgs) -> Result<StoreReturns, ManyError> {
+        check_owner(sender, &args.address)?;
+        check_credential(&args.address, &args.cred_id)?;
+        if self.storage.get(&args.address)?.is_some() {
+            return Err(errors::existing_entry());
+        }
+
+        let recall_phrase = self.generate(&args.address)?;
+        self.storage.put(
+            &args.address,
+            &IdStoreEntry {
+                recall_phrase: recall_phrase.clone(),
+                credentials: vec![Credential {
+                    cred_id: args.cred_id,
+                    public_key: args.public_key,
+                }],
+            },
+        )?;
+        Ok(StoreReturns(recall_phrase))
+    }
+
+    fn get_from_recall_phrase(
+        &self,
+        args: GetFromRecallPhraseArgs,
+    ) -> Result<GetReturns, ManyError> {
+        let address = self
+            .storage
+            .get_address(&args.0)?
+            .ok_or_else(|| errors::entry_not_found(args.0.join(" ")))?;
+        latest(self.get(&address)?)
+    }
+
+    fn get_from_address(&self, args: GetFromAddressArgs) -> Result<GetReturns, ManyError> {
+        latest(self.get(&args.0)?)
+    }
+
+    fn add_credential(
+        &mut self,
+        sender: &Identity,
+        args: AddCredentialArgs,
+    ) -> Result<AddCredentialReturns, ManyError> {
+        check_owner(sender, &args.address)?;
+        check_credential(&args.address, &args.cred_id)?;
+        let mut entry = self.get(&args.address)?;
+        if entry.credentials.iter().any(|c| c.cred_id == args.cred_id) {
+            return Err(errors::existing_entry());
+        }
+
+        entry.credentials.push(Credential {
+            cred_id: args.cred_id,
+            public_key: args.public_key,
+        });
+        self.storage.put(&args.address, &entry)?;
+        Ok(EmptyReturn)
+    }
+
+    fn revoke(&mut self, sender: &Identity, args: RevokeArgs) -> Result<RevokeReturns, ManyError> {
+        check_owner(sender, &args.address)?;
+        let mut entry = self.get(&args.address)?;
+        let index = entry
+            .credentials
+            .iter()
+            .position(|c| c.cred_id == args.cred_id)
+            .ok_or_else(|| errors::entry_not_found(hex::encode(args.cred_id.0.as_slice())))?;
+        if entry.credentials.len() == 1 {
+            return Err(errors::last_credential());
+        }
+
+        entry.credentials.remove(index);
+        self.storage.put(&args.address, &entry)?;
+        Ok(EmptyReturn)
+    }
+
+    fn list(&self, _sender: &Identity, args: ListArgs) -> Result<ListReturns, ManyError> {
+        Ok(ListReturns {
+            credentials: self.get(&args.address)?.credentials,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::identity::testing::identity;
+    use minicbor::bytes::ByteVec;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    fn credential(seed: u8) -> Credential {
+        Credential {
+            cred_id: CredentialId(ByteVec::from(vec![seed; 16])),
+            public_key: PublicKey(ByteVec::from(vec![seed; 32])),
+        }
+    }
+
+    fn store_credential(
+        id_store: &mut IdStore<InMemoryIdStoreStorage>,
+        sender: &Identity,
+        address: &Identity,
+        seed: u8,
+    ) -> Result<RecallPhrase, ManyError> {
+        let Credential {
+            cred_id,
+            public_key,
+        } = credential(seed);
+        id_store
+            .store(
+                sender,
+                StoreArgs {
+                    address: *address,
+                    cred_id,
+                    public_key,
+                },
+            )
+            .map(|r| r.0)
+    }
+
+    #[test]
+    fn credentials() {
+        let mut store = IdStore::new(InMemoryIdStoreStorage::default());
+        let phrase = store_credential(&mut store, &identity(1), &identity(1), 1).unwrap();
+        assert_eq!(phrase.len(), RECALL_PHRASE_DEFAULT_LENGTH);
+        assert_eq!(
+            store_credential(&mut store, &identity(1), &identity(1), 2).unwrap_err(),
+            errors::existing_entry()
+        );
+        assert_eq!(
+            store_credential(&mut store, &identity(1), &identity(2), 2).unwrap_err(),
+            errors::sender_is_not_owner(identity(2))
+        );
+        assert_eq!(
+            store_credential(&mut store, &identity(2), &identity(2), 0)
+                .and_then(|_| store.add_credential(
+                    &identity(2),
+                    AddCredentialArgs {
+                        address: identity(2),
+                        cred_id: CredentialId(ByteVec::from(vec![0; 8])),
+                        public_key: credential(0).public_key,
+                    }
+                ))
+                .unwrap_err(),
+            errors::invalid_credential_id(hex::encode([0u8; 8]))
+        );
+
+        let add = |c: Credential| AddCredentialArgs {
+            address: identity(1),
+            cred_id: c.cred_id,
+            public_key: c.public_key,
+        };
+        assert_eq!(
+            store
+                .add_credential(&identity(2), add(credential(2)))
+                .unwrap_err(),
+            errors::sender_is_not_owner(identity(1))
+        );
+        assert_eq!(
+            store
+                .add_credential(&identity(1), add(credential(1)))
+                .unwrap_err(),
+            errors::existing_entry()
+        );
+        store
+            .add_credential(&identity(1), add(credential(2)))
+            .unwrap();
+
+        let latest = store
+            .get_from_recall_phrase(GetFromRecallPhraseArgs(phrase.clone()))
+            .unwrap();
+        assert_eq!(latest.cred_id, credential(2).cred_id);
+        assert_eq!(
+            store
+                .list(
+                    &identity(1),
+                    ListArgs {
+                        address: identity(1)
+                    }
+                )
+                .unwrap()
+                .credentials,
+            vec![credential(1), credential(2)]
+        );
+
+        let revoke = |c: Credential| RevokeArgs {
+            address: identity(1),
+            cred_id: c.cred_id,
+        };
+        store.revoke(&identity(1), revoke(credential(2))).unwrap();
+        assert!(store.revoke(&identity(1), revoke(credential(2))).is_err());
+        assert_eq!(
+            store
+                .revoke(&identity(1), revoke(credential(1)))
+                .unwrap_err(),
+            errors::last_credential()
+        );
+        let latest = store
+            .get_from_address(GetFromAddressArgs(identity(1)))
+            .unwrap();
+        assert_eq!(latest.cred_id, credential(1).cred_id);
+    }
+
+    proptest! {
+        #[test]
+        fn unique_recall_phrases(addresses in prop::collection::btree_set(any::<[u8; 28]>(), 1..300)) {
+            // One word phrases make collisions frequent.
+            let mut store = IdStore::new(InMemoryIdStoreStorage::default()).with_phrase_length(1);
+            let mut phrases = BTreeSet::new();
+            for address in addresses.iter().map(|a| Identity::public_key_raw(*a)) {
+                let phrase = store_credential(&mut store, &address, &address, 1).unwrap();
+                prop_assert!(phrases.insert(phrase.clone()));
+                prop_assert_eq!(store.storage().get_address(&phrase).unwrap(), Some(address));
+
+                let get = store.get_from_recall_phrase(GetFromRecallPhraseArgs(phrase)).unwrap();
+                prop_assert_eq!(get.cred_id, credential(1).cred_id);
+            }
+        }
+
+        #[test]
+        fn deterministic_recall_phrases(addresses in prop::collection::vec(any::<[u8; 28]>(), 1..20)) {
+            let mut a = IdStore::new(InMemoryIdStoreStorage::default());
+            let mut b = IdStore::new(InMemoryIdStoreStorage::default());
+            for address in addresses.iter().map(|bytes| Identity::public_key_raw(*bytes)) {
+                prop_assert_eq!(
+                    store_credential(&mut a, &address, &address, 1).ok(),
+                    store_credential(&mut b, &address, &address, 1).ok()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::types::{CredentialId, PublicKey};
+use crate::server::module::EmptyReturn;
+use crate::Identity;
+use minicbor::{Decode, Encode};
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct AddCredentialArgs {
+    #[n(0)]
+    pub address: Identity,
+
+    #[n(1)]
+    pub cred_id: CredentialId,
+
+    #[n(2)]
+    pub public_key: PublicKey,
+}
+
+pub type AddCredentialReturns = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RevokeArgs {
+    #[n(0)]
+    pub address: Identity,
+
+    #[n(1)]
+    pub cred_id: CredentialId,
+}
+
+pub type RevokeReturns = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListArgs {
+    #[n(0)]
+    pub address: Identity,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Credential {
+    #[n(0)]
+    pub cred_id: CredentialId,
+
+    #[n(1)]
+    pub public_key: PublicKey,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListReturns {
+    #[n(0)]
+    pub credentials: Vec<Credential>,
+}
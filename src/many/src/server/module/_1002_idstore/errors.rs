@@ -7,5 +7,7 @@ define_attribute_many_error!(
         3: pub fn invalid_address(addr) => "The identity '{addr}' is invalid.",
         4: pub fn invalid_credential_id(cred_id) => "The credential ID '{cred_id}' is invalid.",
         5: pub fn recall_phrase_generation_failed() => "The recall phrase generation failed.",
+        6: pub fn last_credential() => "The last credential of an address cannot be revoked.",
+        7: pub fn sender_is_not_owner(addr) => "The sender is not the owner of '{addr}'.",
     }
 );
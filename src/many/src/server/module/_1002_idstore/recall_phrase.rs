@@ -0,0 +1,65 @@
+use super::errors;
+use super::types::RecallPhrase;
+use crate::ManyError;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+/// The BIP-39 English wordlist, 2048 words.
+pub static WORDLIST: Lazy<Vec<&'static str>> =
+    Lazy::new(|| include_str!("english.txt").lines().collect());
+
+/// Bits of entropy per word of the wordlist.
+const BITS_PER_WORD: usize = 11;
+
+/// The longest recall phrase that can be generated from a seed, using all the
+/// bits of its hash.
+pub const RECALL_PHRASE_MAX_LENGTH: usize = 256 / BITS_PER_WORD;
+
+/// Generate a recall phrase of `length` words from a seed. Like BIP-39, each
+/// word is picked by 11 bits, here of the SHA-256 hash of the seed. The same
+/// seed always generates the same phrase.
+pub fn generate_recall_phrase(seed: &[u8], length: usize) -> Result<RecallPhrase, ManyError> {
+    if length == 0 || length > RECALL_PHRASE_MAX_LENGTH {
+        return Err(errors::recall_phrase_generation_failed());
+    }
+
+    let hash = Sha256::digest(seed);
+    let bit = |i: usize| (hash[i / 8] >> (7 - i % 8)) & 1;
+    Ok((0..length)
+        .map(|w| {
+            let index = (0..BITS_PER_WORD).fold(0usize, |acc, b| {
+                (acc << 1) | bit(w * BITS_PER_WORD + b) as usize
+            });
+            WORDLIST[index].to_string()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn wordlist() {
+        assert_eq!(WORDLIST.len(), 1 << BITS_PER_WORD);
+        assert_eq!(WORDLIST.first(), Some(&"abandon"));
+        assert_eq!(WORDLIST.last(), Some(&"zoo"));
+    }
+
+    #[test]
+    fn invalid_length() {
+        assert!(generate_recall_phrase(b"seed", 0).is_err());
+        assert!(generate_recall_phrase(b"seed", RECALL_PHRASE_MAX_LENGTH + 1).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn deterministic(seed: Vec<u8>, length in 1..=RECALL_PHRASE_MAX_LENGTH) {
+            let phrase = generate_recall_phrase(&seed, length).unwrap();
+            prop_assert_eq!(phrase.len(), length);
+            prop_assert!(phrase.iter().all(|w| WORDLIST.contains(&w.as_str())));
+            prop_assert_eq!(phrase, generate_recall_phrase(&seed, length).unwrap());
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::server::module::account::{Account, Role};
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::Timestamp;
+use crate::{define_attribute_many_error, Identity, ManyError};
+use many_macros::many_module;
+use minicbor::{Decode, Encode};
+
+#[cfg(test)]
+use mockall::{automock, predicate::*};
+
+mod info;
+mod lock;
+mod release;
+
+pub use info::*;
+pub use lock::*;
+pub use release::*;
+
+define_attribute_many_error!(
+    attribute 10 => {
+        1: pub fn unknown_escrow(token) => "Escrow '{token}' unknown.",
+        2: pub fn escrow_not_pending() => "Escrow was already released, refunded or expired.",
+        3: pub fn escrow_expired() => "Escrow expired and its funds were refunded.",
+        4: pub fn invalid_expiration() => "Escrow expiration must be in the future.",
+        5: pub fn sender_is_not_arbiter() => "Only the arbiter can release or refund an escrow.",
+    }
+);
+
+/// A generic Escrow type. This is useful as utility for managing escrows in your backend.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Escrow {
+    #[n(0)]
+    pub from: Identity,
+
+    #[n(1)]
+    pub to: Identity,
+
+    #[n(2)]
+    pub arbiter: Identity,
+
+    #[n(3)]
+    pub symbol: Symbol,
+
+    #[n(4)]
+    pub amount: TokenAmount,
+
+    #[n(5)]
+    pub expiration: Timestamp,
+
+    #[n(6)]
+    pub memo: Option<String>,
+
+    #[n(7)]
+    pub state: EscrowState,
+}
+
+impl Escrow {
+    pub fn create(sender: &Identity, args: LockArgs, now: Timestamp) -> Result<Self, ManyError> {
+        if args.expiration <= now {
+            return Err(invalid_expiration());
+        }
+
+        Ok(Self {
+            from: args.from.unwrap_or(*sender),
+            to: args.to,
+            arbiter: args.arbiter,
+            symbol: args.symbol,
+            amount: args.amount,
+            expiration: args.expiration,
+            memo: args.memo,
+            state: EscrowState::Pending,
+        })
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == EscrowState::Pending
+    }
+
+    /// Whether the escrow is pending but its expiration passed. Backends should call
+    /// [Escrow::expire] and refund the funds to `from` when this is true.
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.is_pending() && self.expiration <= now
+    }
+
+    /// Check if an identity can act as the arbiter. If the arbiter is an account,
+    /// `arbiter_account` should be set and any identity with the `Owner` or
+    /// `CanEscrowArbitrate` roles on it that did not expire at `now` is accepted.
+    pub fn can_arbitrate(
+        &self,
+        id: &Identity,
+        arbiter_account: Option<&Account>,
+        now: Timestamp,
+    ) -> bool {
+        &self.arbiter == id
+            || arbiter_account.map_or(false, |account| {
+                account
+                    .needs_role_at(id, [Role::Owner, Role::CanEscrowArbitrate], now)
+                    .is_ok()
+            })
+    }
+
+    fn transition(
+        &mut self,
+        sender: &Identity,
+        arbiter_account: Option<&Account>,
+        now: Timestamp,
+        state: EscrowState,
+    ) -> Result<(), ManyError> {
+        if !self.can_arbitrate(sender, arbiter_account, now) {
+            return Err(sender_is_not_arbiter());
+        }
+        if self.is_expired(now) {
+            return Err(escrow_expired());
+        }
+        if !self.is_pending() {
+            return Err(escrow_not_pending());
+        }
+        self.state = state;
+        Ok(())
+    }
+
+    /// Release the funds to `to`. The backend is responsible for moving funds.
+    pub fn release(
+        &mut self,
+        sender: &Identity,
+        arbiter_account: Option<&Account>,
+        now: Timestamp,
+    ) -> Result<(), ManyError> {
+        self.transition(sender, arbiter_account, now, EscrowState::Released)
+    }
+
+    /// Refund the funds to `from`. The backend is responsible for moving funds.
+    pub fn refund(
+        &mut self,
+        sender: &Identity,
+        arbiter_account: Option<&Account>,
+        now: Timestamp,
+    ) -> Result<(), ManyError> {
+        self.transition(sender, arbiter_account, now, EscrowState::Refunded)
+    }
+
+    /// Mark the escrow as expired if its timeout passed. Returns true if the state
+    /// changed, in which case the funds should be refunded to `from`.
+    pub fn expire(&mut self, now: Timestamp) -> bool {
+        if self.is_expired(now) {
+            self.state = EscrowState::Expired;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl From<Escrow> for InfoReturns {
+    fn from(escrow: Escrow) -> Self {
+        Self {
+            from: escrow.from,
+            to: escrow.to,
+            arbiter: escrow.arbiter,
+            symbol: escrow.symbol,
+            amount: escrow.amount,
+            expiration: escrow.expiration,
+            memo: escrow.memo,
+            state: escrow.state,
+        }
+    }
+}
+
+#[many_module(name = EscrowModule, id = 10, namespace = escrow, many_crate = crate)]
+#[cfg_attr(test, automock)]
+pub trait EscrowModuleBackend: Send {
+    /// Lock funds until the arbiter releases or refunds them, or they expire.
+    #[many(deny_anonymous)]
+    fn lock(&mut self, sender: &Identity, args: LockArgs) -> Result<LockReturns, ManyError>;
+
+    /// Release locked funds to their recipient.
+    fn release(&mut self, sender: &Identity, args: ReleaseArgs)
+        -> Result<ReleaseReturns, ManyError>;
+
+    /// Refund locked funds to their owner.
+    fn refund(&mut self, sender: &Identity, args: RefundArgs) -> Result<RefundReturns, ManyError>;
+
+    /// Returns the information related to an escrow.
+    fn info(&self, sender: &Identity, args: InfoArgs) -> Result<InfoReturns, ManyError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::account::CreateArgs;
+    use crate::server::module::testutils::call_module_cbor;
+    use crate::types::identity::testing::identity;
+    use mockall::predicate;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{Arc, Mutex};
+
+    fn lock_args() -> LockArgs {
+        LockArgs {
+            from: None,
+            to: identity(2),
+            arbiter: identity(3),
+            symbol: identity(100),
+            amount: TokenAmount::from(1000u16),
+            expiration: Timestamp::new(2000).unwrap(),
+            memo: Some("Invoice 42".to_string()),
+        }
+    }
+
+    #[test]
+    fn lock() {
+        let data = lock_args();
+        let mut mock = MockEscrowModuleBackend::new();
+        mock.expect_lock()
+            .with(predicate::eq(identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_sender, _args| {
+                Ok(LockReturns {
+                    token: vec![1u8].into(),
+                })
+            });
+        let module = super::EscrowModule::new(Arc::new(Mutex::new(mock)));
+
+        let result: LockReturns = minicbor::decode(
+            &call_module_cbor(1, &module, "escrow.lock", minicbor::to_vec(data).unwrap()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result.token.to_vec(), vec![1u8]);
+    }
+
+    #[test]
+    fn lock_anonymous() {
+        let module = super::EscrowModule::new(Arc::new(Mutex::new(MockEscrowModuleBackend::new())));
+        assert!(call_module_cbor(
+            0,
+            &module,
+            "escrow.lock",
+            minicbor::to_vec(lock_args()).unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn release() {
+        let mut escrow =
+            Escrow::create(&identity(1), lock_args(), Timestamp::new(1000).unwrap()).unwrap();
+        let now = Timestamp::new(1500).unwrap();
+
+        assert_eq!(escrow.from, identity(1));
+        assert_eq!(
+            escrow.release(&identity(2), None, now),
+            Err(sender_is_not_arbiter())
+        );
+        assert!(escrow.release(&identity(3), None, now).is_ok());
+        assert_eq!(escrow.state, EscrowState::Released);
+        assert_eq!(
+            escrow.refund(&identity(3), None, now),
+            Err(escrow_not_pending())
+        );
+    }
+
+    #[test]
+    fn refund_by_account_role() {
+        let arbiter_account = Account::create(
+            &identity(4),
+            CreateArgs {
+                description: None,
+                roles: Some(BTreeMap::from([(
+                    identity(5),
+                    BTreeSet::from([Role::CanEscrowArbitrate]),
+                )])),
+                features: Default::default(),
+            },
+        );
+        let mut escrow =
+            Escrow::create(&identity(1), lock_args(), Timestamp::new(1000).unwrap()).unwrap();
+        let now = Timestamp::new(1500).unwrap();
+
+        assert!(escrow
+            .refund(&identity(6), Some(&arbiter_account), now)
+            .is_err());
+        assert!(escrow
+            .refund(&identity(5), Some(&arbiter_account), now)
+            .is_ok());
+        assert_eq!(escrow.state, EscrowState::Refunded);
+    }
+
+    #[test]
+    fn expire() {
+        assert_eq!(
+            Escrow::create(&identity(1), lock_args(), Timestamp::new(2000).unwrap()),
+            Err(invalid_expiration())
+        );
+
+        let mut escrow =
+            Escrow::create(&identity(1), lock_args(), Timestamp::new(1000).unwrap()).unwrap();
+        let later = Timestamp::new(2000).unwrap();
+
+        assert!(!escrow.expire(Timestamp::new(1999).unwrap()));
+        assert_eq!(
+            escrow.release(&identity(3), None, later),
+            Err(escrow_expired())
+        );
+        assert!(escrow.expire(later));
+        assert_eq!(escrow.state, EscrowState::Expired);
+        assert!(!escrow.expire(later));
+    }
+}
@@ -0,0 +1,72 @@
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::Timestamp;
+use crate::Identity;
+use minicbor::bytes::ByteVec;
+use minicbor::{encode, Decode, Decoder, Encode, Encoder};
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct InfoArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum EscrowState {
+    Pending = 0,
+    Released = 1,
+    Refunded = 2,
+    Expired = 3,
+}
+
+impl<C> Encode<C> for EscrowState {
+    fn encode<W: encode::Write>(
+        &self,
+        e: &mut Encoder<W>,
+        _: &mut C,
+    ) -> Result<(), encode::Error<W::Error>> {
+        e.u8(*self as u8)?;
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for EscrowState {
+    fn decode(d: &mut Decoder<'b>, _: &mut C) -> Result<Self, minicbor::decode::Error> {
+        match d.u32()? {
+            0 => Ok(Self::Pending),
+            1 => Ok(Self::Released),
+            2 => Ok(Self::Refunded),
+            3 => Ok(Self::Expired),
+            x => Err(minicbor::decode::Error::unknown_variant(x)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct InfoReturns {
+    #[n(0)]
+    pub from: Identity,
+
+    #[n(1)]
+    pub to: Identity,
+
+    #[n(2)]
+    pub arbiter: Identity,
+
+    #[n(3)]
+    pub symbol: Symbol,
+
+    #[n(4)]
+    pub amount: TokenAmount,
+
+    #[n(5)]
+    pub expiration: Timestamp,
+
+    #[n(6)]
+    pub memo: Option<String>,
+
+    #[n(7)]
+    pub state: EscrowState,
+}
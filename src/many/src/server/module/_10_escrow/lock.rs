@@ -0,0 +1,39 @@
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::Timestamp;
+use crate::Identity;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct LockArgs {
+    /// The identity funds are locked from. Defaults to the sender.
+    #[n(0)]
+    pub from: Option<Identity>,
+
+    #[n(1)]
+    pub to: Identity,
+
+    #[n(2)]
+    pub arbiter: Identity,
+
+    #[n(3)]
+    pub symbol: Symbol,
+
+    #[n(4)]
+    pub amount: TokenAmount,
+
+    /// After this time, the funds are refunded to `from` automatically.
+    #[n(5)]
+    pub expiration: Timestamp,
+
+    #[n(6)]
+    pub memo: Option<String>,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct LockReturns {
+    #[n(0)]
+    pub token: ByteVec,
+}
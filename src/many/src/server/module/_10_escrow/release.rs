@@ -0,0 +1,21 @@
+use crate::server::module::EmptyReturn;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ReleaseArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type ReleaseReturns = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RefundArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type RefundReturns = EmptyReturn;
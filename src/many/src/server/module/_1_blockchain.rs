@@ -8,6 +8,15 @@ use minicbor::{Decode, Encode};
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
+pub mod attributes;
+mod list;
+mod proof;
+mod store;
+
+pub use list::*;
+pub use proof::*;
+pub use store::*;
+
 define_attribute_many_error!(
     attribute 1 => {
         1: pub fn height_out_of_bound(height, min, max)
@@ -16,6 +25,8 @@ define_attribute_many_error!(
         3: pub fn unknown_block() => "Requested block query does not match any block.",
         4: pub fn unknown_transaction()
             => "Requested transaction query does not match any transaction.",
+        5: pub fn invalid_cursor() => "The cursor is not a valid cursor for this query.",
+        6: pub fn invalid_proof() => "The proof does not match the state root.",
     }
 );
 
@@ -66,6 +77,8 @@ pub trait BlockchainModuleBackend: Send {
     fn info(&self) -> Result<InfoReturns, ManyError>;
     fn block(&self, args: BlockArgs) -> Result<BlockReturns, ManyError>;
     fn transaction(&self, args: TransactionArgs) -> Result<TransactionReturns, ManyError>;
+    fn list(&self, args: ListArgs) -> Result<ListReturns, ManyError>;
+    fn transactions(&self, args: TransactionsArgs) -> Result<TransactionsReturns, ManyError>;
 }
 
 #[cfg(test)]
@@ -73,7 +86,11 @@ mod tests {
     use super::*;
     use crate::{
         server::module::testutils::{call_module, call_module_cbor},
-        types::{blockchain::TransactionIdentifier, Timestamp},
+        types::{
+            blockchain::{BlockFilter, TransactionFilter, TransactionIdentifier},
+            CborRange, Timestamp, VecOrSingle,
+        },
+        Identity,
     };
     use mockall::predicate;
     use std::sync::{Arc, Mutex};
@@ -225,4 +242,97 @@ mod tests {
         assert_eq!(transaction_returns.txn.id.hash, vec![6u8; 8]);
         assert_eq!(transaction_returns.txn.content, None);
     }
+
+    #[test]
+    fn list() {
+        let data = ListArgs {
+            count: Some(1),
+            order: None,
+            filter: Some(BlockFilter {
+                height_range: Some(CborRange {
+                    start: std::ops::Bound::Included(2),
+                    end: std::ops::Bound::Unbounded,
+                }),
+                date_range: None,
+            }),
+            cursor: None,
+        };
+        let mut mock = MockBlockchainModuleBackend::new();
+        mock.expect_list()
+            .with(predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_args| {
+                Ok(ListReturns {
+                    nb_blocks: 2,
+                    blocks: vec![Block {
+                        id: BlockIdentifier::new(vec![2u8; 8], 2),
+                        parent: BlockIdentifier::new(vec![1u8; 8], 1),
+                        app_hash: None,
+                        timestamp: Timestamp::now(),
+                        txs_count: 0,
+                        txs: vec![],
+                    }],
+                    cursor: Some(vec![2u8].into()),
+                })
+            });
+        let module = super::BlockchainModule::new(Arc::new(Mutex::new(mock)));
+
+        let list_returns: ListReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "blockchain.list",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(list_returns.nb_blocks, 2);
+        assert_eq!(list_returns.blocks.len(), 1);
+        assert_eq!(list_returns.blocks[0].id.height, 2);
+        assert!(list_returns.cursor.is_some());
+    }
+
+    #[test]
+    fn transactions() {
+        let data = TransactionsArgs {
+            filter: Some(TransactionFilter {
+                sender: Some(VecOrSingle(vec![Identity::anonymous()])),
+                method: Some(VecOrSingle(vec!["ledger.send".to_string()])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut mock = MockBlockchainModuleBackend::new();
+        mock.expect_transactions()
+            .with(predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_args| {
+                Ok(TransactionsReturns {
+                    nb_transactions: 1,
+                    transactions: vec![Transaction {
+                        id: TransactionIdentifier { hash: vec![7u8; 8] },
+                        content: None,
+                    }],
+                    cursor: None,
+                })
+            });
+        let module = super::BlockchainModule::new(Arc::new(Mutex::new(mock)));
+
+        let transactions_returns: TransactionsReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "blockchain.transactions",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(transactions_returns.nb_transactions, 1);
+        assert_eq!(transactions_returns.transactions[0].id.hash, vec![7u8; 8]);
+        assert!(transactions_returns.cursor.is_none());
+    }
 }
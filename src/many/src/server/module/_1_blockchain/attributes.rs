@@ -0,0 +1,58 @@
+use crate::cbor::CborAny;
+use crate::protocol::attributes::TryFromAttributeSet;
+use crate::protocol::{Attribute, AttributeSet};
+use crate::types::proof::Proof;
+use crate::ManyError;
+
+/// Requests with this attribute ask for a proof of the state they read. The
+/// response carries the proof in a [ProofAttribute].
+pub const PROOF: Attribute = Attribute::id(2);
+
+pub struct ProofAttribute {
+    pub proof: Proof,
+}
+
+impl ProofAttribute {
+    pub fn new(proof: Proof) -> Self {
+        Self { proof }
+    }
+}
+
+impl From<ProofAttribute> for Attribute {
+    fn from(a: ProofAttribute) -> Attribute {
+        let bytes = minicbor::to_vec(a.proof).expect("Encoding a proof cannot fail.");
+        PROOF.with_argument(CborAny::Bytes(bytes))
+    }
+}
+
+impl TryFrom<Attribute> for ProofAttribute {
+    type Error = ManyError;
+
+    fn try_from(value: Attribute) -> Result<Self, Self::Error> {
+        if value.id != PROOF.id {
+            return Err(ManyError::invalid_attribute_id(value.id));
+        }
+
+        let arguments = value.into_arguments();
+        if arguments.len() != 1 {
+            Err(ManyError::invalid_attribute_arguments())
+        } else {
+            match arguments.into_iter().next() {
+                Some(CborAny::Bytes(bytes)) => Ok(Self {
+                    proof: minicbor::decode(&bytes)
+                        .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                }),
+                _ => Err(ManyError::invalid_attribute_arguments()),
+            }
+        }
+    }
+}
+
+impl TryFromAttributeSet for ProofAttribute {
+    fn try_from_set(set: &AttributeSet) -> Result<Self, ManyError> {
+        match set.get_attribute(PROOF.id) {
+            Some(attr) => ProofAttribute::try_from(attr.clone()),
+            None => Err(ManyError::attribute_not_found(PROOF.id.to_string())),
+        }
+    }
+}
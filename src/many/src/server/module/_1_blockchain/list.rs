@@ -0,0 +1,72 @@
+use crate::types::blockchain::{Block, BlockFilter, Transaction, TransactionFilter};
+use crate::types::SortOrder;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+
+/// Maximum number of blocks or transactions returned by a single
+/// `blockchain.list` or `blockchain.transactions` call.
+pub const BLOCKCHAIN_LIST_MAX_COUNT: u64 = 100;
+
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListArgs {
+    #[n(0)]
+    pub count: Option<u64>,
+
+    #[n(1)]
+    pub order: Option<SortOrder>,
+
+    #[n(2)]
+    pub filter: Option<BlockFilter>,
+
+    /// A cursor returned by a previous call, to continue listing from there.
+    #[n(3)]
+    pub cursor: Option<ByteVec>,
+}
+
+#[derive(Clone, Encode, Decode)]
+#[cbor(map)]
+pub struct ListReturns {
+    #[n(0)]
+    pub nb_blocks: u64,
+
+    #[n(1)]
+    pub blocks: Vec<Block>,
+
+    /// Opaque cursor to pass to the next call if there are more blocks. None if
+    /// this is the last page.
+    #[n(2)]
+    pub cursor: Option<ByteVec>,
+}
+
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct TransactionsArgs {
+    #[n(0)]
+    pub count: Option<u64>,
+
+    #[n(1)]
+    pub order: Option<SortOrder>,
+
+    #[n(2)]
+    pub filter: Option<TransactionFilter>,
+
+    /// A cursor returned by a previous call, to continue listing from there.
+    #[n(3)]
+    pub cursor: Option<ByteVec>,
+}
+
+#[derive(Clone, Encode, Decode)]
+#[cbor(map)]
+pub struct TransactionsReturns {
+    #[n(0)]
+    pub nb_transactions: u64,
+
+    #[n(1)]
+    pub transactions: Vec<Transaction>,
+
+    /// Opaque cursor to pass to the next call if there are more transactions.
+    /// None if this is the last page.
+    #[n(2)]
+    pub cursor: Option<ByteVec>,
+}
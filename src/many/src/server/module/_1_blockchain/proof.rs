@@ -0,0 +1,127 @@
+use super::attributes::{ProofAttribute, PROOF};
+use crate::message::{RequestMessage, ResponseMessage};
+use crate::server::module::{ManyModule, ManyModuleInfo};
+use crate::types::proof::Proof;
+use crate::ManyError;
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// Proves the state read by queries, for a [ProofModule].
+pub trait ProofProvider: Debug + Send + Sync {
+    /// The result of a query and the proof of the state it read, both from the
+    /// same state, e.g. the latest committed block whose height and app hash
+    /// are in the proof. The result is empty if the key has no value.
+    fn query(&self, message: &RequestMessage) -> Result<(Vec<u8>, Proof), ManyError>;
+}
+
+/// A module wrapping another one to answer the requests with the [PROOF]
+/// attribute with a proof of their result, e.g. the `ledger` or `kvstore`
+/// modules of an application whose app hash is the root of a
+/// [MerkleTree](crate::types::proof::MerkleTree) of its state.
+///
+/// The requests with the [PROOF] attribute are answered by the provider, so
+/// their result and proof come from the same state.
+#[derive(Debug)]
+pub struct ProofModule<M, P> {
+    module: M,
+    provider: P,
+}
+
+impl<M: ManyModule, P: ProofProvider> ProofModule<M, P> {
+    pub fn new(module: M, provider: P) -> Self {
+        Self { module, provider }
+    }
+}
+
+#[async_trait]
+impl<M: ManyModule, P: ProofProvider> ManyModule for ProofModule<M, P> {
+    fn info(&self) -> &ManyModuleInfo {
+        self.module.info()
+    }
+
+    fn validate(
+        &self,
+        message: &RequestMessage,
+        envelope: &coset::CoseSign1,
+    ) -> Result<(), ManyError> {
+        self.module.validate(message, envelope)
+    }
+
+    async fn execute(&self, message: RequestMessage) -> Result<ResponseMessage, ManyError> {
+        if !message.attributes.has_id(PROOF.id) {
+            return self.module.execute(message).await;
+        }
+
+        let (data, proof) = self.provider.query(&message)?;
+        Ok(
+            ResponseMessage::from_request(&message, &message.to, Ok(data))
+                .with_attribute(ProofAttribute::new(proof).into()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::blockchain::{
+        BlockchainModule, InfoReturns, MockBlockchainModuleBackend,
+    };
+    use crate::types::blockchain::BlockIdentifier;
+    use crate::types::proof::MerkleTree;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct TreeProvider {
+        state: BTreeMap<String, Vec<u8>>,
+        tree: MerkleTree,
+        height: u64,
+    }
+
+    impl ProofProvider for TreeProvider {
+        fn query(&self, message: &RequestMessage) -> Result<(Vec<u8>, Proof), ManyError> {
+            let data = self.state.get(&message.method).cloned().unwrap_or_default();
+            let proof = self.tree.prove(self.height, message.method.as_bytes());
+            Ok((data, proof))
+        }
+    }
+
+    #[test]
+    fn proof_attribute() {
+        let state = BTreeMap::from([("blockchain.info".to_string(), b"value".to_vec())]);
+        let tree = MerkleTree::new(&state);
+        let root = tree.root();
+
+        let mut mock = MockBlockchainModuleBackend::new();
+        mock.expect_info().times(1).returning(|| {
+            Ok(InfoReturns {
+                latest_block: BlockIdentifier::genesis(),
+                app_hash: None,
+                retained_height: None,
+            })
+        });
+        let module = ProofModule::new(
+            BlockchainModule::new(Arc::new(Mutex::new(mock))),
+            TreeProvider {
+                state,
+                tree,
+                height: 3,
+            },
+        );
+
+        let message = RequestMessage::default()
+            .with_method("blockchain.info".to_string())
+            .with_data(minicbor::to_vec(()).unwrap());
+        let response = smol::block_on(module.execute(message.clone())).unwrap();
+        assert!(response.data.is_ok());
+        assert!(response.attributes.get::<ProofAttribute>().is_err());
+
+        // The provider answers proof requests, from the state it proves.
+        let response = smol::block_on(module.execute(message.with_attribute(PROOF))).unwrap();
+        let data = response.data.unwrap();
+        assert_eq!(data, b"value");
+        let proof = response.attributes.get::<ProofAttribute>().unwrap().proof;
+        assert_eq!(proof.height, 3);
+        assert_eq!(proof.verify(&root, Some(&data)), Ok(()));
+    }
+}
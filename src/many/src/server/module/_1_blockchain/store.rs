@@ -0,0 +1,863 @@
+use super::*;
+use crate::server::module::abci_backend::{AbciBlock, AbciCommitInfo};
+use crate::types::blockchain::TransactionIdentifier;
+use crate::types::{CborRange, SortOrder, Timestamp};
+use minicbor::bytes::ByteVec;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Bound, RangeInclusive};
+
+/// Persistence of the blocks of a [BlockStore].
+pub trait BlockStorage: Send {
+    fn get(&self, height: u64) -> Result<Option<Block>, ManyError>;
+
+    /// Persist a committed block.
+    fn put(&mut self, block: &Block) -> Result<(), ManyError>;
+
+    /// Remove a block below the retained height.
+    fn remove(&mut self, height: u64) -> Result<(), ManyError>;
+
+    /// All the blocks stored, sorted by height, used to load the store.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, ManyError>> + '_>;
+}
+
+/// A [BlockStorage] that keeps everything in memory.
+#[derive(Clone, Default)]
+pub struct InMemoryBlockStorage {
+    blocks: BTreeMap<u64, Block>,
+}
+
+impl BlockStorage for InMemoryBlockStorage {
+    fn get(&self, height: u64) -> Result<Option<Block>, ManyError> {
+        Ok(self.blocks.get(&height).cloned())
+    }
+
+    fn put(&mut self, block: &Block) -> Result<(), ManyError> {
+        self.blocks.insert(block.id.height, block.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, height: u64) -> Result<(), ManyError> {
+        self.blocks.remove(&height);
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, ManyError>> + '_> {
+        Box::new(self.blocks.values().map(|b| Ok(b.clone())))
+    }
+}
+
+/// The block being built between `begin_block` and `commit`.
+struct PendingBlock {
+    timestamp: Timestamp,
+    txs: Vec<Transaction>,
+}
+
+/// A page of items sorted by key, selected like the events of `events.list`.
+/// The cursor is the key of the last item of the previous page.
+struct Page<K, T> {
+    descending: bool,
+    count: usize,
+    cursor: Option<K>,
+    items: Vec<(K, T)>,
+}
+
+impl<K: Copy + Ord, T> Page<K, T> {
+    fn new(order: Option<&SortOrder>, count: Option<u64>, cursor: Option<K>) -> Self {
+        Self {
+            descending: order == Some(&SortOrder::Descending),
+            count: count.map_or(BLOCKCHAIN_LIST_MAX_COUNT, |c| {
+                c.min(BLOCKCHAIN_LIST_MAX_COUNT)
+            }) as usize,
+            cursor,
+            items: Vec::new(),
+        }
+    }
+
+    /// Iterate over items sorted by ascending key in the order of the page.
+    fn order<I>(&self, items: I) -> Box<dyn Iterator<Item = I::Item>>
+    where
+        I: DoubleEndedIterator + 'static,
+    {
+        if self.descending {
+            Box::new(items.rev())
+        } else {
+            Box::new(items)
+        }
+    }
+
+    /// Restrict a range of heights to the heights from the cursor height, in
+    /// the order of the page. The height of the cursor is included.
+    fn from_height(
+        &self,
+        heights: RangeInclusive<u64>,
+        cursor: Option<u64>,
+    ) -> RangeInclusive<u64> {
+        let (start, end) = heights.into_inner();
+        match cursor {
+            Some(height) if self.descending => start..=end.min(height),
+            Some(height) => start.max(height)..=end,
+            None => start..=end,
+        }
+    }
+
+    /// Add an item matching the query. Returns true once the page is full and
+    /// whether there is a next page is known, so the caller can stop.
+    fn push(&mut self, key: K, item: T) -> bool {
+        let after_cursor =
+            self.cursor
+                .map_or(true, |c| if self.descending { key < c } else { key > c });
+        if after_cursor {
+            self.items.push((key, item));
+        }
+        self.items.len() > self.count
+    }
+
+    /// The items of the page and the cursor of the next page, if any.
+    fn finish(mut self) -> (Vec<T>, Option<K>) {
+        let cursor = if self.items.len() > self.count {
+            self.items.truncate(self.count);
+            self.items.last().map(|(key, _)| *key)
+        } else {
+            None
+        };
+        (
+            self.items.into_iter().map(|(_, item)| item).collect(),
+            cursor,
+        )
+    }
+}
+
+/// The number of heights in a range.
+fn nb_heights(heights: &RangeInclusive<u64>) -> u64 {
+    if heights.is_empty() {
+        0
+    } else {
+        heights.end() - heights.start() + 1
+    }
+}
+
+fn block_cursor(cursor: &ByteVec) -> Result<u64, ManyError> {
+    let height = <[u8; 8]>::try_from(cursor.as_slice()).map_err(|_| invalid_cursor())?;
+    Ok(u64::from_be_bytes(height))
+}
+
+/// Transaction cursors are the height of their block followed by their index in
+/// the block.
+fn transaction_cursor(cursor: &ByteVec) -> Result<(u64, u64), ManyError> {
+    if cursor.len() != 16 {
+        return Err(invalid_cursor());
+    }
+    let (height, index) = cursor.split_at(8);
+    Ok((
+        block_cursor(&height.to_vec().into())?,
+        block_cursor(&index.to_vec().into())?,
+    ))
+}
+
+/// A reusable blockchain backend that records the blocks and transactions of
+/// an ABCI application. The application calls [BlockStore::begin_block],
+/// [BlockStore::add_transaction], [BlockStore::end_block] and
+/// [BlockStore::commit] from the matching [ManyAbciModuleBackend] hooks and
+/// commands. It implements [BlockchainModuleBackend] directly.
+///
+/// Transactions are identified by the SHA-256 of their content, like in
+/// Tendermint. The same transaction can be in multiple blocks, in which case
+/// `blockchain.transaction` returns it from the latest one. Blocks are
+/// identified by the SHA-256 of their parent hash, height, app hash and
+/// transaction hashes.
+///
+/// Lists only load the blocks needed for their page. The number of blocks or
+/// transactions they return is the number within the height range of their
+/// filter, without applying the rest of the filter.
+///
+/// [ManyAbciModuleBackend]: crate::server::module::abci_backend::ManyAbciModuleBackend
+pub struct BlockStore<S: BlockStorage> {
+    storage: S,
+    latest: BlockIdentifier,
+    app_hash: Option<Vec<u8>>,
+    retained_height: Option<u64>,
+    heights: BTreeMap<Vec<u8>, u64>,
+    /// The heights of the blocks containing each transaction.
+    transactions: BTreeMap<Vec<u8>, BTreeSet<u64>>,
+    /// The number of transactions of each block.
+    txs_counts: BTreeMap<u64, u64>,
+    pending: Option<PendingBlock>,
+}
+
+impl<S: BlockStorage> BlockStore<S> {
+    /// Create a store from a storage, with the blocks already in it.
+    pub fn load(storage: S) -> Result<Self, ManyError> {
+        let mut store = Self {
+            storage,
+            latest: BlockIdentifier::genesis(),
+            app_hash: None,
+            retained_height: None,
+            heights: BTreeMap::new(),
+            transactions: BTreeMap::new(),
+            txs_counts: BTreeMap::new(),
+            pending: None,
+        };
+
+        let mut blocks = Vec::new();
+        for block in store.storage.iter() {
+            blocks.push(block?);
+        }
+        if let Some(first) = blocks.first() {
+            if first.id.height > 1 {
+                store.retained_height = Some(first.id.height);
+            }
+        }
+        for block in blocks {
+            store.index(&block);
+        }
+        Ok(store)
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    fn index(&mut self, block: &Block) {
+        self.heights.insert(block.id.hash.clone(), block.id.height);
+        self.txs_counts.insert(block.id.height, block.txs_count);
+        for tx in &block.txs {
+            self.transactions
+                .entry(tx.id.hash.clone())
+                .or_default()
+                .insert(block.id.height);
+        }
+        self.latest = block.id.clone();
+        self.app_hash = block.app_hash.clone();
+    }
+
+    /// The lowest height stored.
+    fn first_height(&self) -> u64 {
+        self.retained_height.unwrap_or(1)
+    }
+
+    /// Start a block, from the `begin_block` hook. The block must have a time,
+    /// so that all the nodes record the same block.
+    pub fn begin_block(&mut self, info: &AbciBlock) -> Result<(), ManyError> {
+        let timestamp = match info.time {
+            Some(secs) => Timestamp::new(secs)?,
+            None => return Err(ManyError::unknown("The block has no time.".to_string())),
+        };
+        self.pending = Some(PendingBlock {
+            timestamp,
+            txs: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Record a transaction of the current block, e.g. the envelope of a
+    /// command, returning its identifier.
+    pub fn add_transaction(
+        &mut self,
+        content: Vec<u8>,
+    ) -> Result<TransactionIdentifier, ManyError> {
+        let pending = self
+            .pending
+            .as_mut()
+            .ok_or_else(|| ManyError::unknown("No block was started.".to_string()))?;
+        let id = TransactionIdentifier {
+            hash: Sha256::digest(&content).to_vec(),
+        };
+        pending.txs.push(Transaction {
+            id: id.clone(),
+            content: Some(content),
+        });
+        Ok(id)
+    }
+
+    /// End the current block, from the `end_block` hook. Transactions can no
+    /// longer be added to it.
+    pub fn end_block(&mut self) -> Result<(), ManyError> {
+        if self.pending.is_none() {
+            return Err(ManyError::unknown("No block was started.".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Commit the current block, from the `commit` hook, with the information
+    /// returned by the application. Blocks below the retain height are
+    /// removed.
+    pub fn commit(&mut self, info: &AbciCommitInfo) -> Result<BlockIdentifier, ManyError> {
+        let PendingBlock { timestamp, txs } = self
+            .pending
+            .take()
+            .ok_or_else(|| ManyError::unknown("No block was started.".to_string()))?;
+
+        let parent = self.latest.clone();
+        let height = parent.height + 1;
+        let app_hash = info.hash.to_vec();
+
+        let mut hasher = Sha256::new();
+        hasher.update(&parent.hash);
+        hasher.update(height.to_be_bytes());
+        hasher.update(&app_hash);
+        for tx in &txs {
+            hasher.update(&tx.id.hash);
+        }
+        let id = BlockIdentifier::new(hasher.finalize().to_vec(), height);
+
+        let block = Block {
+            id: id.clone(),
+            parent,
+            app_hash: Some(app_hash),
+            timestamp,
+            txs_count: txs.len() as u64,
+            txs,
+        };
+        self.storage.put(&block)?;
+        self.index(&block);
+
+        // A retain height of 0 means all blocks are retained.
+        if info.retain_height > self.first_height() {
+            for pruned in self.first_height()..info.retain_height.min(height) {
+                if let Some(block) = self.storage.get(pruned)? {
+                    self.heights.remove(&block.id.hash);
+                    self.txs_counts.remove(&pruned);
+                    for tx in &block.txs {
+                        if let Some(heights) = self.transactions.get_mut(&tx.id.hash) {
+                            heights.remove(&pruned);
+                            if heights.is_empty() {
+                                self.transactions.remove(&tx.id.hash);
+                            }
+                        }
+                    }
+                    self.storage.remove(pruned)?;
+                }
+            }
+            self.retained_height = Some(info.retain_height.min(height));
+        }
+        Ok(id)
+    }
+
+    fn get(&self, height: u64) -> Result<Block, ManyError> {
+        self.storage.get(height)?.ok_or_else(unknown_block)
+    }
+
+    /// The stored heights within a range. A range starting after the latest
+    /// block or ending before the retained height is out of bound.
+    fn heights(&self, range: Option<&CborRange<u64>>) -> Result<RangeInclusive<u64>, ManyError> {
+        let first = self.first_height();
+        let latest = self.latest.height;
+        let range = match range {
+            Some(range) => range,
+            None => return Ok(first..=latest),
+        };
+
+        let start = match range.start {
+            Bound::Included(height) => Some(height),
+            Bound::Excluded(height) => Some(height.saturating_add(1)),
+            Bound::Unbounded => None,
+        };
+        let end = match range.end {
+            Bound::Included(height) => Some(height),
+            Bound::Excluded(height) => Some(height.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        if let Some(start) = start.filter(|start| *start > latest) {
+            return Err(height_out_of_bound(start, first, latest));
+        }
+        if let Some(end) = end.filter(|end| *end < first) {
+            return Err(height_out_of_bound(end, first, latest));
+        }
+        Ok(start.map_or(first, |s| s.max(first))..=end.map_or(latest, |e| e.min(latest)))
+    }
+}
+
+impl<S: BlockStorage> BlockchainModuleBackend for BlockStore<S> {
+    fn info(&self) -> Result<InfoReturns, ManyError> {
+        Ok(InfoReturns {
+            latest_block: self.latest.clone(),
+            app_hash: self.app_hash.clone(),
+            retained_height: self.retained_height,
+        })
+    }
+
+    fn block(&self, args: BlockArgs) -> Result<BlockReturns, ManyError> {
+        let height = match args.query {
+            SingleBlockQuery::Hash(hash) => *self.heights.get(&hash).ok_or_else(unknown_block)?,
+            SingleBlockQuery::Height(height) => {
+                if height < self.first_height() || height > self.latest.height {
+                    return Err(height_out_of_bound(
+                        height,
+                        self.first_height(),
+                        self.latest.height,
+                    ));
+                }
+                height
+            }
+        };
+        Ok(BlockReturns {
+            block: self.get(height)?,
+        })
+    }
+
+    fn transaction(&self, args: TransactionArgs) -> Result<TransactionReturns, ManyError> {
+        let SingleTransactionQuery::Hash(hash) = args.query;
+        let height = self
+            .transactions
+            .get(&hash)
+            .and_then(|heights| heights.iter().next_back())
+            .ok_or_else(unknown_transaction)?;
+        self.get(*height)?
+            .txs
+            .into_iter()
+            .find(|tx| tx.id.hash == hash)
+            .map(|txn| TransactionReturns { txn })
+            .ok_or_else(unknown_transaction)
+    }
+
+    fn list(&self, args: ListArgs) -> Result<ListReturns, ManyError> {
+        let filter = args.filter.unwrap_or_default();
+        let cursor = args.cursor.as_ref().map(block_cursor).transpose()?;
+        let heights = self.heights(filter.height_range.as_ref())?;
+        let nb_blocks = nb_heights(&heights);
+        let mut page = Page::new(args.order.as_ref(), args.count, cursor);
+
+        // The block of the cursor was on the previous page.
+        let from = cursor.map(|height| {
+            if page.descending {
+                height.saturating_sub(1)
+            } else {
+                height.saturating_add(1)
+            }
+        });
+        for height in page.order(page.from_height(heights, from)) {
+            let block = self.get(height)?;
+            if filter.matches(&block) && page.push(height, block) {
+                break;
+            }
+        }
+
+        let (blocks, cursor) = page.finish();
+        Ok(ListReturns {
+            nb_blocks,
+            blocks,
+            cursor: cursor.map(|height| height.to_be_bytes().to_vec().into()),
+        })
+    }
+
+    fn transactions(&self, args: TransactionsArgs) -> Result<TransactionsReturns, ManyError> {
+        let filter = args.filter.unwrap_or_default();
+        let cursor = args.cursor.as_ref().map(transaction_cursor).transpose()?;
+        let heights = self.heights(filter.height_range.as_ref())?;
+        let nb_transactions = if heights.is_empty() {
+            0
+        } else {
+            self.txs_counts.range(heights.clone()).map(|(_, n)| n).sum()
+        };
+        let mut page = Page::new(args.order.as_ref(), args.count, cursor);
+
+        'blocks: for height in page.order(page.from_height(heights, cursor.map(|(h, _)| h))) {
+            let txs = self.get(height)?.txs.into_iter().enumerate();
+            for (index, txn) in page.order(txs) {
+                if filter.matches(height, &txn) && page.push((height, index as u64), txn) {
+                    break 'blocks;
+                }
+            }
+        }
+
+        let (transactions, cursor) = page.finish();
+        Ok(TransactionsReturns {
+            nb_transactions,
+            transactions,
+            cursor: cursor
+                .map(|(height, index)| [height.to_be_bytes(), index.to_be_bytes()].concat().into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RequestMessage;
+    use crate::types::blockchain::{BlockFilter, TransactionFilter};
+    use crate::types::identity::testing::identity;
+    use crate::types::VecOrSingle;
+
+    fn commit_block<S: BlockStorage>(
+        store: &mut BlockStore<S>,
+        time: u64,
+        txs: Vec<Vec<u8>>,
+        retain_height: u64,
+    ) -> BlockIdentifier {
+        store.begin_block(&AbciBlock { time: Some(time) }).unwrap();
+        let hash = vec![txs.len() as u8; 8].into();
+        for tx in txs {
+            store.add_transaction(tx).unwrap();
+        }
+        store.end_block().unwrap();
+        store
+            .commit(&AbciCommitInfo {
+                retain_height,
+                hash,
+            })
+            .unwrap()
+    }
+
+    fn commit(
+        store: &mut BlockStore<InMemoryBlockStorage>,
+        txs: &[&str],
+        retain_height: u64,
+    ) -> BlockIdentifier {
+        let txs = txs.iter().map(|tx| tx.as_bytes().to_vec()).collect();
+        commit_block(store, 1000, txs, retain_height)
+    }
+
+    fn request(seed: u32, method: &str) -> Vec<u8> {
+        RequestMessage {
+            from: Some(identity(seed)),
+            method: method.to_string(),
+            timestamp: Some(std::time::UNIX_EPOCH),
+            ..Default::default()
+        }
+        .to_bytes()
+        .unwrap()
+    }
+
+    fn block(
+        store: &BlockStore<InMemoryBlockStorage>,
+        query: SingleBlockQuery,
+    ) -> Result<Block, ManyError> {
+        store.block(BlockArgs { query }).map(|r| r.block)
+    }
+
+    #[test]
+    fn blocks_and_transactions() {
+        let mut store = BlockStore::load(InMemoryBlockStorage::default()).unwrap();
+        assert!(store.add_transaction(b"tx".to_vec()).is_err());
+
+        let first = commit(&mut store, &["a", "b"], 0);
+        let second = commit(&mut store, &[], 0);
+        assert_eq!(first.height, 1);
+        assert_eq!(second.height, 2);
+        assert_ne!(first.hash, second.hash);
+
+        let info = store.info().unwrap();
+        assert_eq!(info.latest_block, second);
+        assert_eq!(info.app_hash, Some(vec![0; 8]));
+        assert_eq!(info.retained_height, None);
+
+        let by_hash = block(&store, SingleBlockQuery::Hash(first.hash.clone())).unwrap();
+        assert_eq!(by_hash.id, first);
+        assert_eq!(by_hash.txs_count, 2);
+        assert_eq!(by_hash.timestamp, Timestamp::new(1000).unwrap());
+        let by_height = block(&store, SingleBlockQuery::Height(2)).unwrap();
+        assert_eq!(by_height.parent, first);
+
+        assert_eq!(
+            block(&store, SingleBlockQuery::Height(3)).err(),
+            Some(height_out_of_bound(3, 1, 2))
+        );
+        assert_eq!(
+            block(&store, SingleBlockQuery::Hash(vec![1; 32])).err(),
+            Some(unknown_block())
+        );
+
+        let hash = Sha256::digest(b"b").to_vec();
+        let txn = store
+            .transaction(TransactionArgs {
+                query: SingleTransactionQuery::Hash(hash.clone()),
+            })
+            .unwrap()
+            .txn;
+        assert_eq!(txn.id.hash, hash);
+        assert_eq!(txn.content, Some(b"b".to_vec()));
+        assert!(store
+            .transaction(TransactionArgs {
+                query: SingleTransactionQuery::Hash(vec![0; 32]),
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn retain_height_and_reload() {
+        let mut store = BlockStore::load(InMemoryBlockStorage::default()).unwrap();
+        let first = commit(&mut store, &["a"], 0);
+        commit(&mut store, &["b"], 0);
+        let third = commit(&mut store, &["c"], 2);
+
+        assert_eq!(store.info().unwrap().retained_height, Some(2));
+        assert_eq!(
+            block(&store, SingleBlockQuery::Height(1)).err(),
+            Some(height_out_of_bound(1, 2, 3))
+        );
+        assert!(block(&store, SingleBlockQuery::Hash(first.hash)).is_err());
+        assert!(store
+            .transaction(TransactionArgs {
+                query: SingleTransactionQuery::Hash(Sha256::digest(b"a").to_vec()),
+            })
+            .is_err());
+
+        let store = BlockStore::load(store.storage().clone()).unwrap();
+        let info = store.info().unwrap();
+        assert_eq!(info.latest_block, third);
+        assert_eq!(info.retained_height, Some(2));
+        assert!(block(&store, SingleBlockQuery::Height(2)).is_ok());
+    }
+
+    #[test]
+    fn duplicate_transactions() {
+        let mut store = BlockStore::load(InMemoryBlockStorage::default()).unwrap();
+        assert!(store.begin_block(&AbciBlock { time: None }).is_err());
+
+        commit(&mut store, &["a"], 0);
+        let second = commit(&mut store, &["a", "b"], 0);
+        let transaction = |store: &BlockStore<InMemoryBlockStorage>, tx: &str| {
+            store.transaction(TransactionArgs {
+                query: SingleTransactionQuery::Hash(Sha256::digest(tx.as_bytes()).to_vec()),
+            })
+        };
+
+        // Pruning the first block keeps the transaction of the second one.
+        commit(&mut store, &[], 2);
+        assert_eq!(store.info().unwrap().retained_height, Some(2));
+        assert!(transaction(&store, "a").is_ok());
+        assert!(block(&store, SingleBlockQuery::Hash(second.hash)).is_ok());
+
+        commit(&mut store, &[], 3);
+        assert_eq!(transaction(&store, "a").err(), Some(unknown_transaction()));
+        assert_eq!(transaction(&store, "b").err(), Some(unknown_transaction()));
+    }
+
+    #[test]
+    fn list() {
+        let mut store = BlockStore::load(InMemoryBlockStorage::default()).unwrap();
+        for height in 1..=5 {
+            commit_block(&mut store, 999 + height, vec![], 0);
+        }
+        let heights = |args: &ListArgs| -> Vec<u64> {
+            store
+                .list(args.clone())
+                .unwrap()
+                .blocks
+                .iter()
+                .map(|b| b.id.height)
+                .collect()
+        };
+
+        let all = store.list(ListArgs::default()).unwrap();
+        assert_eq!(all.nb_blocks, 5);
+        assert_eq!(all.blocks.len(), 5);
+        assert!(all.cursor.is_none());
+
+        let mut args = ListArgs {
+            count: Some(2),
+            order: Some(SortOrder::Descending),
+            ..Default::default()
+        };
+        let first = store.list(args.clone()).unwrap();
+        assert_eq!(heights(&args), vec![5, 4]);
+        args.cursor = first.cursor;
+        let second = store.list(args.clone()).unwrap();
+        assert_eq!(heights(&args), vec![3, 2]);
+        args.cursor = second.cursor;
+        let third = store.list(args.clone()).unwrap();
+        assert_eq!(heights(&args), vec![1]);
+        assert!(third.cursor.is_none());
+
+        let args = ListArgs {
+            filter: Some(BlockFilter {
+                height_range: Some(CborRange {
+                    start: Bound::Included(2),
+                    end: Bound::Excluded(4),
+                }),
+                date_range: None,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(heights(&args), vec![2, 3]);
+
+        let args = ListArgs {
+            filter: Some(BlockFilter {
+                height_range: None,
+                date_range: Some(CborRange {
+                    start: Bound::Included(Timestamp::new(1003).unwrap()),
+                    end: Bound::Unbounded,
+                }),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(heights(&args), vec![4, 5]);
+        // Only the height range is used to count blocks.
+        assert_eq!(store.list(args).unwrap().nb_blocks, 5);
+
+        let args = ListArgs {
+            filter: Some(BlockFilter {
+                height_range: Some(CborRange {
+                    start: Bound::Included(6),
+                    end: Bound::Unbounded,
+                }),
+                date_range: None,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(store.list(args).err(), Some(height_out_of_bound(6, 1, 5)));
+
+        let args = ListArgs {
+            cursor: Some(vec![1].into()),
+            ..Default::default()
+        };
+        assert_eq!(store.list(args).err(), Some(invalid_cursor()));
+    }
+
+    #[test]
+    fn transactions() {
+        let mut store = BlockStore::load(InMemoryBlockStorage::default()).unwrap();
+        commit_block(
+            &mut store,
+            1000,
+            vec![request(1, "ledger.send"), request(2, "ledger.send")],
+            0,
+        );
+        commit_block(
+            &mut store,
+            1001,
+            vec![b"not a request".to_vec(), request(1, "kvstore.put")],
+            0,
+        );
+        let contents = |args: &TransactionsArgs| -> Vec<Vec<u8>> {
+            store
+                .transactions(args.clone())
+                .unwrap()
+                .transactions
+                .into_iter()
+                .map(|txn| txn.content.unwrap())
+                .collect()
+        };
+        let filter = |sender: Option<u32>, method: Option<&str>| TransactionFilter {
+            height_range: None,
+            sender: sender.map(|seed| VecOrSingle(vec![identity(seed)])),
+            method: method.map(|m| VecOrSingle(vec![m.to_string()])),
+        };
+
+        let all = store.transactions(TransactionsArgs::default()).unwrap();
+        assert_eq!(all.nb_transactions, 4);
+
+        let mut args = TransactionsArgs {
+            filter: Some(filter(Some(1), None)),
+            ..Default::default()
+        };
+        assert_eq!(
+            contents(&args),
+            vec![request(1, "ledger.send"), request(1, "kvstore.put")]
+        );
+        args.order = Some(SortOrder::Descending);
+        assert_eq!(
+            contents(&args),
+            vec![request(1, "kvstore.put"), request(1, "ledger.send")]
+        );
+
+        let mut args = TransactionsArgs {
+            count: Some(1),
+            filter: Some(filter(None, Some("ledger.send"))),
+            ..Default::default()
+        };
+        let first = store.transactions(args.clone()).unwrap();
+        assert_eq!(first.nb_transactions, 4);
+        assert_eq!(contents(&args), vec![request(1, "ledger.send")]);
+        args.cursor = first.cursor;
+        let second = store.transactions(args.clone()).unwrap();
+        assert_eq!(contents(&args), vec![request(2, "ledger.send")]);
+        assert!(second.cursor.is_none());
+
+        let args = TransactionsArgs {
+            filter: Some(filter(Some(1), Some("kvstore.put"))),
+            ..Default::default()
+        };
+        assert_eq!(contents(&args), vec![request(1, "kvstore.put")]);
+    }
+
+    #[derive(Default)]
+    struct CountingStorage(InMemoryBlockStorage, std::sync::atomic::AtomicUsize);
+
+    impl BlockStorage for CountingStorage {
+        fn get(&self, height: u64) -> Result<Option<Block>, ManyError> {
+            self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.0.get(height)
+        }
+
+        fn put(&mut self, block: &Block) -> Result<(), ManyError> {
+            self.0.put(block)
+        }
+
+        fn remove(&mut self, height: u64) -> Result<(), ManyError> {
+            self.0.remove(height)
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, ManyError>> + '_> {
+            self.0.iter()
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let mut store = BlockStore::load(CountingStorage::default()).unwrap();
+        for height in 1..=10u64 {
+            commit_block(
+                &mut store,
+                999 + height,
+                vec![height.to_be_bytes().to_vec()],
+                0,
+            );
+        }
+        let gets = |store: &BlockStore<CountingStorage>| {
+            store
+                .storage()
+                .1
+                .swap(0, std::sync::atomic::Ordering::Relaxed)
+        };
+
+        let mut args = ListArgs {
+            count: Some(3),
+            order: Some(SortOrder::Descending),
+            ..Default::default()
+        };
+        let first = store.list(args.clone()).unwrap();
+        assert_eq!(first.nb_blocks, 10);
+        assert_eq!(
+            first.blocks.iter().map(|b| b.id.height).collect::<Vec<_>>(),
+            vec![10, 9, 8]
+        );
+        // Only the page and one more block to know there is a next page.
+        assert_eq!(gets(&store), 4);
+        args.cursor = first.cursor;
+        let second = store.list(args).unwrap();
+        assert_eq!(
+            second
+                .blocks
+                .iter()
+                .map(|b| b.id.height)
+                .collect::<Vec<_>>(),
+            vec![7, 6, 5]
+        );
+        assert_eq!(gets(&store), 4);
+
+        let mut args = TransactionsArgs {
+            count: Some(3),
+            ..Default::default()
+        };
+        let first = store.transactions(args.clone()).unwrap();
+        assert_eq!(first.nb_transactions, 10);
+        assert_eq!(gets(&store), 4);
+        args.cursor = first.cursor;
+        let second = store.transactions(args).unwrap();
+        assert_eq!(
+            second
+                .transactions
+                .into_iter()
+                .map(|txn| txn.content.unwrap())
+                .collect::<Vec<_>>(),
+            (4..=6u64)
+                .map(|height| height.to_be_bytes().to_vec())
+                .collect::<Vec<_>>()
+        );
+        // The block of the cursor is loaded again, as it can have more
+        // transactions.
+        assert_eq!(gets(&store), 5);
+    }
+}
@@ -18,6 +18,10 @@ define_attribute_many_error!(
         4: pub fn anonymous_cannot_hold_funds() => "Anonymous is not a valid account identity.",
         5: pub fn invalid_initial_state(expected, actual)
             => "Invalid initial state hash. Expected '{expected}', was '{actual}'.",
+        6: pub fn invalid_schedule(details) => "Invalid schedule: {details}.",
+        7: pub fn unknown_scheduled_send(token) => "Scheduled send '{token}' unknown.",
+        8: pub fn cannot_cancel_scheduled_send()
+            => "Only the sender or source account can cancel a scheduled send.",
     }
 );
 
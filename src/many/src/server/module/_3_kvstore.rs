@@ -1,19 +1,70 @@
-use crate::{Identity, ManyError};
+use crate::{define_attribute_many_error, Identity, ManyError};
 use many_macros::many_module;
+use minicbor::bytes::ByteVec;
 
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
 pub mod get;
 pub mod info;
+pub mod list;
+pub mod query;
 pub use get::*;
 pub use info::*;
+pub use list::*;
+pub use query::*;
+
+define_attribute_many_error!(
+    attribute 3 => {
+        1: pub fn permission_denied() => "You do not have the authorization to modify this key.",
+        2: pub fn key_not_found(key) => "Key '{key}' was not found.",
+        3: pub fn conflict()
+            => "The current value of the key does not match the expected precondition.",
+        4: pub fn unknown_upload(upload) => "Upload '{upload}' unknown.",
+        5: pub fn invalid_chunk(index) => "Chunk {index} is invalid.",
+        6: pub fn upload_incomplete() => "Some chunks of the upload are missing.",
+        7: pub fn upload_mismatch(details) => "Uploaded content does not match: {details}.",
+        8: pub fn invalid_ttl(ttl) => "A TTL of {ttl} seconds is out of range.",
+    }
+);
+
+/// The hash of a value, as used by put preconditions. This is the SHA-256 of the value.
+pub fn value_hash(value: &[u8]) -> ByteVec {
+    use sha2::Digest;
+    ByteVec::from(sha2::Sha256::digest(value).to_vec())
+}
+
+/// Incremental version of [value_hash], for values received in chunks.
+#[derive(Clone, Default)]
+pub struct ValueHasher(sha2::Sha256);
+
+impl ValueHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.0.update(data);
+    }
+
+    pub fn finalize(self) -> ByteVec {
+        use sha2::Digest;
+        ByteVec::from(self.0.finalize().to_vec())
+    }
+}
 
 #[many_module(name = KvStoreModule, id = 3, namespace = kvstore, many_crate = crate)]
 #[cfg_attr(test, automock)]
 pub trait KvStoreModuleBackend: Send {
     fn info(&self, sender: &Identity, args: InfoArg) -> Result<InfoReturns, ManyError>;
+    /// Returns the value of a key. Keys whose expiration passed are not returned
+    /// (see [GetReturns::at]).
     fn get(&self, sender: &Identity, args: GetArgs) -> Result<GetReturns, ManyError>;
+    fn list(&self, sender: &Identity, args: ListArgs) -> Result<ListReturns, ManyError>;
+    /// Returns the value and metadata of a key. Keys whose expiration passed are
+    /// not returned (see [QueryReturns::at]).
+    fn query(&self, sender: &Identity, args: QueryArgs) -> Result<QueryReturns, ManyError>;
 }
 
 #[cfg(test)]
@@ -21,8 +72,9 @@ mod tests {
     use super::*;
     use crate::server::module::testutils::{call_module, call_module_cbor};
     use crate::types::identity::testing::identity;
-    use minicbor::bytes::ByteVec;
+    use crate::types::CborRange;
     use mockall::predicate;
+    use std::ops::Bound;
     use std::sync::{Arc, Mutex};
 
     #[test]
@@ -33,18 +85,26 @@ mod tests {
             .times(1)
             .return_const(Ok(InfoReturns {
                 hash: ByteVec::from(vec![9u8; 8]),
+                expired_count: Some(2),
+                pending_expiry_count: Some(3),
             }));
         let module = super::KvStoreModule::new(Arc::new(Mutex::new(mock)));
         let info_returns: InfoReturns =
             minicbor::decode(&call_module(1, &module, "kvstore.info", "null").unwrap()).unwrap();
 
         assert_eq!(info_returns.hash, ByteVec::from(vec![9u8; 8]));
+        assert_eq!(info_returns.expired_count, Some(2));
+        assert_eq!(info_returns.pending_expiry_count, Some(3));
     }
 
     #[test]
     fn get() {
         let data = GetArgs {
             key: ByteVec::from(vec![5, 6, 7]),
+            range: Some(CborRange {
+                start: Bound::Included(1),
+                end: Bound::Unbounded,
+            }),
         };
         let mut mock = MockKvStoreModuleBackend::new();
         mock.expect_get()
@@ -52,6 +112,7 @@ mod tests {
         .times(1).returning(|_id, _args| {
             Ok(GetReturns {
                 value: Some(ByteVec::from(vec![1, 2, 3, 4])),
+                size: Some(5),
             })
         });
         let module = super::KvStoreModule::new(Arc::new(Mutex::new(mock)));
@@ -62,5 +123,66 @@ mod tests {
         .unwrap();
 
         assert_eq!(get_returns.value, Some(ByteVec::from(vec![1, 2, 3, 4])));
+        assert_eq!(get_returns.size, Some(5));
+    }
+
+    #[test]
+    fn list() {
+        let data = ListArgs {
+            prefix: Some(ByteVec::from(b"/users/".to_vec())),
+            count: Some(2),
+            ..Default::default()
+        };
+        let mut mock = MockKvStoreModuleBackend::new();
+        mock.expect_list()
+            .with(predicate::eq(identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_id, args| {
+                let keys = std::collections::BTreeSet::from([
+                    ByteVec::from(b"/users/1".to_vec()),
+                    ByteVec::from(b"/users/2".to_vec()),
+                    ByteVec::from(b"/users/3".to_vec()),
+                ]);
+                Ok(args.paginate(keys.iter()))
+            });
+        let module = super::KvStoreModule::new(Arc::new(Mutex::new(mock)));
+
+        let list_returns: ListReturns = minicbor::decode(
+            &call_module_cbor(1, &module, "kvstore.list", minicbor::to_vec(data).unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(list_returns.keys.len(), 2);
+        assert_eq!(
+            list_returns.cursor,
+            Some(ByteVec::from(b"/users/2".to_vec()))
+        );
+    }
+
+    #[test]
+    fn query() {
+        let data = QueryArgs {
+            key: ByteVec::from(vec![5, 6, 7]),
+        };
+        let metadata = KeyMetadata::new(&identity(2), crate::types::Timestamp::now(), None);
+        let mut mock = MockKvStoreModuleBackend::new();
+        mock.expect_query()
+            .with(predicate::eq(identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .return_const(Ok(QueryReturns {
+                value: Some(ByteVec::from(vec![1, 2, 3, 4])),
+                metadata: Some(metadata.clone()),
+            }));
+        let module = super::KvStoreModule::new(Arc::new(Mutex::new(mock)));
+
+        let query_returns: QueryReturns = minicbor::decode(
+            &call_module_cbor(1, &module, "kvstore.query", minicbor::to_vec(data).unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(query_returns.value, Some(ByteVec::from(vec![1, 2, 3, 4])));
+        assert_eq!(query_returns.metadata, Some(metadata));
     }
 }
@@ -1,11 +1,57 @@
+use super::KeyMetadata;
+use crate::types::{CborRange, Timestamp};
+use crate::ManyError;
 use minicbor::bytes::ByteVec;
 use minicbor::{Decode, Encode};
+use std::ops::{Bound, Range};
+
+/// Maximum number of bytes returned by a single `kvstore.get` call. Larger
+/// values must be read in ranges.
+pub const KVSTORE_READ_MAX_SIZE: u64 = 1_048_576; // 1MB
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
 #[cbor(map)]
 pub struct GetArgs {
     #[n(0)]
     pub key: ByteVec,
+
+    /// Range of bytes of the value to return. The whole value if None.
+    #[n(1)]
+    pub range: Option<CborRange<u64>>,
+}
+
+impl GetArgs {
+    /// The bytes selected by the range of this query, for a value of `size` bytes.
+    /// The range is clamped to the value and to [KVSTORE_READ_MAX_SIZE].
+    pub fn bounds(&self, size: u64) -> Range<u64> {
+        let (start, end) = match &self.range {
+            None => (0, size),
+            Some(range) => (
+                match range.start {
+                    Bound::Included(s) => s,
+                    Bound::Excluded(s) => s.saturating_add(1),
+                    Bound::Unbounded => 0,
+                },
+                match range.end {
+                    Bound::Included(e) => e.saturating_add(1),
+                    Bound::Excluded(e) => e,
+                    Bound::Unbounded => size,
+                },
+            ),
+        };
+
+        let start = start.min(size);
+        let end = end
+            .min(size)
+            .min(start.saturating_add(KVSTORE_READ_MAX_SIZE));
+        start..end.max(start)
+    }
+
+    /// Select the part of a value returned by this query.
+    pub fn select<'a>(&self, value: &'a [u8]) -> &'a [u8] {
+        let Range { start, end } = self.bounds(value.len() as u64);
+        &value[start as usize..end as usize]
+    }
 }
 
 #[derive(Clone, Encode, Decode)]
@@ -13,4 +59,194 @@ pub struct GetArgs {
 pub struct GetReturns {
     #[n(0)]
     pub value: Option<ByteVec>,
+
+    /// Total size of the value, so large values can be read in ranges.
+    #[n(1)]
+    pub size: Option<u64>,
+}
+
+impl GetReturns {
+    /// The returns of a get for a key with this value and metadata at `now`,
+    /// selecting the range of the query. An expired key is returned as if it did
+    /// not exist.
+    pub fn at(
+        args: &GetArgs,
+        value: Option<&[u8]>,
+        metadata: Option<&KeyMetadata>,
+        now: Timestamp,
+    ) -> Self {
+        match value {
+            Some(value) if !metadata.map_or(false, |m| m.is_expired(now)) => Self {
+                value: Some(args.select(value).to_vec().into()),
+                size: Some(value.len() as u64),
+            },
+            _ => Self {
+                value: None,
+                size: None,
+            },
+        }
+    }
+}
+
+/// The manifest of a value uploaded in chunks. Backends store it in place of the
+/// value and assemble the value from its chunks when it is read.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ChunkManifest {
+    /// Total size of the value.
+    #[n(0)]
+    pub size: u64,
+
+    /// Hash of the whole value (see [super::value_hash]).
+    #[n(1)]
+    pub hash: ByteVec,
+
+    /// Size of every chunk but the last one.
+    #[n(2)]
+    pub chunk_size: u64,
+
+    /// Hashes of the chunks, in order.
+    #[n(3)]
+    pub chunks: Vec<ByteVec>,
+}
+
+impl ChunkManifest {
+    /// Read the bytes selected by a query, only fetching the chunks covering them.
+    /// `read_chunk` returns the data of a chunk from its hash.
+    pub fn read<F>(&self, args: &GetArgs, mut read_chunk: F) -> Result<Vec<u8>, ManyError>
+    where
+        F: FnMut(&ByteVec) -> Result<Vec<u8>, ManyError>,
+    {
+        let Range { start, end } = args.bounds(self.size);
+        let mut result = Vec::with_capacity((end - start) as usize);
+        if start == end || self.chunk_size == 0 {
+            return Ok(result);
+        }
+
+        let first = start / self.chunk_size;
+        let last = (end - 1) / self.chunk_size;
+        for index in first..=last {
+            let hash = self
+                .chunks
+                .get(index as usize)
+                .ok_or_else(|| ManyError::unknown("Chunk missing from manifest."))?;
+            let chunk = read_chunk(hash)?;
+
+            let offset = index * self.chunk_size;
+            let from = start.saturating_sub(offset) as usize;
+            let to = ((end - offset) as usize).min(chunk.len());
+            if from < to {
+                result.extend_from_slice(&chunk[from..to]);
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::kvstore::value_hash;
+
+    fn args(start: Bound<u64>, end: Bound<u64>) -> GetArgs {
+        GetArgs {
+            key: ByteVec::from(vec![1]),
+            range: Some(CborRange { start, end }),
+        }
+    }
+
+    #[test]
+    fn select() {
+        let value = b"0123456789";
+        let all = GetArgs {
+            key: ByteVec::from(vec![1]),
+            range: None,
+        };
+        assert_eq!(all.select(value), b"0123456789");
+        assert_eq!(
+            args(Bound::Included(2), Bound::Excluded(5)).select(value),
+            b"234"
+        );
+        assert_eq!(
+            args(Bound::Excluded(7), Bound::Unbounded).select(value),
+            b"89"
+        );
+        assert_eq!(
+            args(Bound::Included(8), Bound::Included(u64::MAX)).select(value),
+            b"89"
+        );
+        assert!(args(Bound::Included(20), Bound::Unbounded)
+            .select(value)
+            .is_empty());
+        assert!(args(Bound::Included(5), Bound::Excluded(2))
+            .select(value)
+            .is_empty());
+    }
+
+    #[test]
+    fn get_expired() {
+        let mut metadata = KeyMetadata::new(
+            &crate::types::identity::testing::identity(1),
+            Timestamp::new(1000).unwrap(),
+            None,
+        );
+        metadata
+            .set_ttl(Timestamp::new(1000).unwrap(), Some(60))
+            .unwrap();
+        let args = args(Bound::Included(1), Bound::Unbounded);
+
+        let returns = GetReturns::at(
+            &args,
+            Some(b"0123"),
+            Some(&metadata),
+            Timestamp::new(1059).unwrap(),
+        );
+        assert_eq!(returns.value, Some(ByteVec::from(b"123".to_vec())));
+        assert_eq!(returns.size, Some(4));
+
+        let returns = GetReturns::at(
+            &args,
+            Some(b"0123"),
+            Some(&metadata),
+            Timestamp::new(1060).unwrap(),
+        );
+        assert_eq!(returns.value, None);
+        assert_eq!(returns.size, None);
+    }
+
+    #[test]
+    fn read_manifest() {
+        let chunks: Vec<Vec<u8>> = vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()];
+        let manifest = ChunkManifest {
+            size: 10,
+            hash: value_hash(b"0123456789"),
+            chunk_size: 4,
+            chunks: chunks.iter().map(|c| value_hash(c)).collect(),
+        };
+        let read_chunk = |hash: &ByteVec| {
+            Ok(chunks
+                .iter()
+                .find(|c| &value_hash(c) == hash)
+                .unwrap()
+                .clone())
+        };
+
+        assert_eq!(
+            manifest
+                .read(&args(Bound::Included(3), Bound::Excluded(9)), read_chunk)
+                .unwrap(),
+            b"345678".to_vec()
+        );
+
+        // Only the chunks covering the range are read.
+        let mut read = 0;
+        let result = manifest
+            .read(&args(Bound::Included(8), Bound::Unbounded), |hash| {
+                read += 1;
+                read_chunk(hash)
+            })
+            .unwrap();
+        assert_eq!(result, b"89".to_vec());
+        assert_eq!(read, 1);
+    }
 }
@@ -8,4 +8,12 @@ pub type InfoArg = EmptyArg;
 pub struct InfoReturns {
     #[n(0)]
     pub hash: ByteVec,
+
+    /// Number of keys that expired and were removed.
+    #[n(1)]
+    pub expired_count: Option<u64>,
+
+    /// Number of keys with an expiration that did not pass yet.
+    #[n(2)]
+    pub pending_expiry_count: Option<u64>,
 }
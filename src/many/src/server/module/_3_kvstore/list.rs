@@ -0,0 +1,152 @@
+use crate::types::{CborRange, SortOrder};
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+
+/// Maximum number of keys returned by a single `kvstore.list` call.
+pub const KVSTORE_LIST_MAX_COUNT: u64 = 1000;
+
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListArgs {
+    #[n(0)]
+    pub prefix: Option<ByteVec>,
+
+    #[n(1)]
+    pub range: Option<CborRange<ByteVec>>,
+
+    #[n(2)]
+    pub order: Option<SortOrder>,
+
+    #[n(3)]
+    pub count: Option<u64>,
+
+    /// A cursor returned by a previous call, to continue listing from there.
+    #[n(4)]
+    pub cursor: Option<ByteVec>,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListReturns {
+    #[n(0)]
+    pub keys: Vec<ByteVec>,
+
+    /// Opaque cursor to pass to the next call if there are more keys. None if
+    /// this is the last page.
+    #[n(1)]
+    pub cursor: Option<ByteVec>,
+}
+
+impl ListArgs {
+    /// Whether a key is selected by the prefix and range of this query.
+    pub fn matches(&self, key: &ByteVec) -> bool {
+        self.prefix
+            .as_ref()
+            .map_or(true, |prefix| key.starts_with(prefix))
+            && self.range.as_ref().map_or(true, |range| range.contains(key))
+    }
+
+    /// Select a page of keys from an iterator of keys sorted in ascending order,
+    /// applying the prefix, range, order, count and cursor of this query.
+    pub fn paginate<'a, I>(&self, keys: I) -> ListReturns
+    where
+        I: DoubleEndedIterator<Item = &'a ByteVec> + 'a,
+    {
+        let descending = self.order == Some(SortOrder::Descending);
+        let count = self
+            .count
+            .map_or(KVSTORE_LIST_MAX_COUNT, |c| c.min(KVSTORE_LIST_MAX_COUNT)) as usize;
+
+        let keys: Box<dyn Iterator<Item = &'a ByteVec> + 'a> = if descending {
+            Box::new(keys.rev())
+        } else {
+            Box::new(keys)
+        };
+
+        // The cursor is the last key of the previous page.
+        let cursor = self.cursor.as_ref();
+        let mut keys: Vec<ByteVec> = keys
+            .filter(|k| {
+                self.matches(k)
+                    && cursor.map_or(true, |c| if descending { *k < c } else { *k > c })
+            })
+            .take(count + 1)
+            .cloned()
+            .collect();
+
+        let cursor = if keys.len() > count {
+            keys.truncate(count);
+            keys.last().cloned()
+        } else {
+            None
+        };
+
+        ListReturns { keys, cursor }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::ops::Bound;
+
+    fn keys() -> BTreeSet<ByteVec> {
+        ["/users/1", "/users/2", "/users/3", "/users/4", "/groups/1"]
+            .iter()
+            .map(|k| ByteVec::from(k.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn key(k: &str) -> ByteVec {
+        ByteVec::from(k.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn prefix() {
+        let keys = keys();
+        let args = ListArgs {
+            prefix: Some(key("/users/")),
+            ..Default::default()
+        };
+        let result = args.paginate(keys.iter());
+        assert_eq!(result.keys.len(), 4);
+        assert_eq!(result.cursor, None);
+    }
+
+    #[test]
+    fn range_descending() {
+        let keys = keys();
+        let args = ListArgs {
+            range: Some(CborRange {
+                start: Bound::Included(key("/users/2")),
+                end: Bound::Excluded(key("/users/4")),
+            }),
+            order: Some(SortOrder::Descending),
+            ..Default::default()
+        };
+        let result = args.paginate(keys.iter());
+        assert_eq!(result.keys, vec![key("/users/3"), key("/users/2")]);
+    }
+
+    #[test]
+    fn cursor() {
+        let keys = keys();
+        let mut args = ListArgs {
+            prefix: Some(key("/users/")),
+            count: Some(3),
+            ..Default::default()
+        };
+        let first = args.paginate(keys.iter());
+        assert_eq!(
+            first.keys,
+            vec![key("/users/1"), key("/users/2"), key("/users/3")]
+        );
+        assert_eq!(first.cursor, Some(key("/users/3")));
+
+        args.cursor = first.cursor;
+        let second = args.paginate(keys.iter());
+        assert_eq!(second.keys, vec![key("/users/4")]);
+        assert_eq!(second.cursor, None);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::server::module::account::{AccountMap, Role};
+use crate::server::module::kvstore::{invalid_ttl, permission_denied};
+use crate::types::Timestamp;
+use crate::{Identity, ManyError};
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// Additional identities allowed to write to a key, on top of its owner.
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct KeyAcl {
+    /// Identities that can write to the key.
+    #[n(0)]
+    pub identities: BTreeSet<Identity>,
+
+    /// Accounts mapped to the roles that grant writing to the key. Any identity
+    /// holding one of those roles on the account can write.
+    #[n(1)]
+    pub roles: BTreeMap<Identity, BTreeSet<Role>>,
+}
+
+impl KeyAcl {
+    /// Whether the ACL allows an identity, checking account roles that did not
+    /// expire at a specific time.
+    pub fn allows(&self, id: &Identity, accounts: Option<&AccountMap>, now: Timestamp) -> bool {
+        if self.identities.contains(id) {
+            return true;
+        }
+
+        accounts.map_or(false, |accounts| {
+            self.roles.iter().any(|(account, roles)| {
+                roles
+                    .iter()
+                    .any(|role| accounts.has_role_at(account, id, role.clone(), now))
+            })
+        })
+    }
+}
+
+/// Metadata stored alongside each key.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct KeyMetadata {
+    #[n(0)]
+    pub owner: Identity,
+
+    #[n(1)]
+    pub created: Timestamp,
+
+    #[n(2)]
+    pub modified: Timestamp,
+
+    #[n(3)]
+    pub acl: Option<KeyAcl>,
+
+    /// When the key expires. Expired keys are treated as if they did not exist,
+    /// and removed by the backend.
+    #[n(4)]
+    pub expiration: Option<Timestamp>,
+}
+
+impl KeyMetadata {
+    pub fn new(owner: &Identity, now: Timestamp, acl: Option<KeyAcl>) -> Self {
+        Self {
+            owner: *owner,
+            created: now,
+            modified: now,
+            acl,
+            expiration: None,
+        }
+    }
+
+    /// Set the expiration of the key from a put's TTL. A put without TTL removes
+    /// any previous expiration. A TTL whose expiration cannot be represented is
+    /// an error.
+    pub fn set_ttl(&mut self, now: Timestamp, ttl_in_secs: Option<u64>) -> Result<(), ManyError> {
+        self.expiration = match ttl_in_secs {
+            Some(ttl) => Some(
+                now.0
+                    .checked_add(Duration::from_secs(ttl))
+                    .map(Timestamp)
+                    .ok_or_else(|| invalid_ttl(ttl))?,
+            ),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expiration
+            .map_or(false, |expiration| expiration <= now)
+    }
+
+    /// Whether an identity can write to (or delete) the key. The owner can
+    /// always write. If the owner is an account, its owners can also write.
+    pub fn can_write(&self, id: &Identity, accounts: Option<&AccountMap>, now: Timestamp) -> bool {
+        &self.owner == id
+            || accounts.map_or(false, |accounts| {
+                accounts.has_role_at(&self.owner, id, Role::Owner, now)
+            })
+            || self
+                .acl
+                .as_ref()
+                .map_or(false, |acl| acl.allows(id, accounts, now))
+    }
+
+    pub fn needs_write(
+        &self,
+        id: &Identity,
+        accounts: Option<&AccountMap>,
+        now: Timestamp,
+    ) -> Result<(), ManyError> {
+        if self.can_write(id, accounts, now) {
+            Ok(())
+        } else {
+            Err(permission_denied())
+        }
+    }
+
+    /// Update the modification time of the key.
+    pub fn touch(&mut self, now: Timestamp) {
+        self.modified = now;
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct QueryArgs {
+    #[n(0)]
+    pub key: ByteVec,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct QueryReturns {
+    #[n(0)]
+    pub value: Option<ByteVec>,
+
+    #[n(1)]
+    pub metadata: Option<KeyMetadata>,
+}
+
+impl QueryReturns {
+    /// The returns of a query for a key with this value and metadata at `now`.
+    /// An expired key is returned as if it did not exist.
+    pub fn at(value: Option<ByteVec>, metadata: Option<KeyMetadata>, now: Timestamp) -> Self {
+        if metadata.as_ref().map_or(false, |m| m.is_expired(now)) {
+            Self {
+                value: None,
+                metadata: None,
+            }
+        } else {
+            Self { value, metadata }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::account::{Account, CreateArgs};
+    use crate::types::identity::testing::identity;
+
+    #[test]
+    fn can_write() {
+        let mut accounts = AccountMap::new(Identity::public_key_raw([0; 28]));
+        let (account, _) = accounts
+            .insert(Account::create(
+                &identity(10),
+                CreateArgs {
+                    description: None,
+                    roles: Some(BTreeMap::from([(
+                        identity(11),
+                        BTreeSet::from([Role::CanLedgerTransact]),
+                    )])),
+                    features: Default::default(),
+                },
+            ))
+            .unwrap();
+
+        let now = Timestamp::now();
+        let mut metadata = KeyMetadata::new(&identity(1), now, None);
+        assert!(metadata.can_write(&identity(1), None, now));
+        assert!(!metadata.can_write(&identity(2), None, now));
+
+        metadata.acl = Some(KeyAcl {
+            identities: BTreeSet::from([identity(2)]),
+            roles: BTreeMap::from([(account, BTreeSet::from([Role::CanLedgerTransact]))]),
+        });
+        assert!(metadata.can_write(&identity(2), None, now));
+        assert!(!metadata.can_write(&identity(11), None, now));
+        assert!(metadata.can_write(&identity(11), Some(&accounts), now));
+        assert!(metadata
+            .needs_write(&identity(10), Some(&accounts), now)
+            .is_err());
+
+        // Owners of an account owning the key can write.
+        let metadata = KeyMetadata::new(&account, now, None);
+        assert!(metadata.can_write(&identity(10), Some(&accounts), now));
+        assert!(!metadata.can_write(&identity(11), Some(&accounts), now));
+
+        // Expired roles do not give access.
+        let mut metadata = KeyMetadata::new(&identity(1), now, None);
+        metadata.acl = Some(KeyAcl {
+            identities: BTreeSet::new(),
+            roles: BTreeMap::from([(account, BTreeSet::from([Role::CanLedgerTransact]))]),
+        });
+        accounts.get_mut(&account).unwrap().add_role_until(
+            &identity(12),
+            Role::CanLedgerTransact,
+            Timestamp::new(1000).unwrap(),
+        );
+        assert!(metadata.can_write(&identity(12), Some(&accounts), Timestamp::new(999).unwrap()));
+        assert!(!metadata.can_write(
+            &identity(12),
+            Some(&accounts),
+            Timestamp::new(1000).unwrap()
+        ));
+    }
+
+    #[test]
+    fn ttl() {
+        let now = Timestamp::new(1000).unwrap();
+        let mut metadata = KeyMetadata::new(&identity(1), now, None);
+        assert!(!metadata.is_expired(Timestamp::new(u32::MAX as u64).unwrap()));
+
+        metadata.set_ttl(now, Some(60)).unwrap();
+        assert_eq!(metadata.expiration, Some(Timestamp::new(1060).unwrap()));
+        assert!(!metadata.is_expired(Timestamp::new(1059).unwrap()));
+        assert!(metadata.is_expired(Timestamp::new(1060).unwrap()));
+
+        assert_eq!(
+            metadata.set_ttl(now, Some(u64::MAX)),
+            Err(invalid_ttl(u64::MAX))
+        );
+        assert_eq!(metadata.expiration, Some(Timestamp::new(1060).unwrap()));
+
+        metadata.set_ttl(now, None).unwrap();
+        assert!(!metadata.is_expired(Timestamp::new(1060).unwrap()));
+    }
+
+    #[test]
+    fn query_expired() {
+        let mut metadata = KeyMetadata::new(&identity(1), Timestamp::new(1000).unwrap(), None);
+        metadata
+            .set_ttl(Timestamp::new(1000).unwrap(), Some(60))
+            .unwrap();
+        let value = Some(ByteVec::from(vec![1, 2, 3]));
+
+        let returns = QueryReturns::at(
+            value.clone(),
+            Some(metadata.clone()),
+            Timestamp::new(1059).unwrap(),
+        );
+        assert_eq!(returns.value, value);
+        assert_eq!(returns.metadata, Some(metadata.clone()));
+
+        let returns = QueryReturns::at(value, Some(metadata), Timestamp::new(1060).unwrap());
+        assert_eq!(returns.value, None);
+        assert_eq!(returns.metadata, None);
+    }
+}
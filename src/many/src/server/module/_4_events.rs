@@ -6,9 +6,11 @@ use mockall::{automock, predicate::*};
 
 mod info;
 mod list;
+mod store;
 
 pub use info::*;
 pub use list::*;
+pub use store::*;
 
 #[many_module(name = EventsModule, id = 4, namespace = events, many_crate = crate)]
 #[cfg_attr(test, automock)]
@@ -58,6 +60,7 @@ mod tests {
             count: Some(1),
             order: None,
             filter: None,
+            cursor: Some(vec![1, 1, 1, 2].into()),
         };
         let mut mock = MockEventsModuleBackend::new();
         mock.expect_list()
@@ -76,6 +79,7 @@ mod tests {
                             amount: TokenAmount::from(1000u64),
                         },
                     }],
+                    cursor: None,
                 })
             });
         let module = super::EventsModule::new(Arc::new(Mutex::new(mock)));
@@ -1,6 +1,11 @@
+use crate::types::events::{EventId, EventLog};
 use crate::types::{events, SortOrder};
+use minicbor::bytes::ByteVec;
 use minicbor::{Decode, Encode};
 
+/// Maximum number of events returned by a single `events.list` call.
+pub const EVENTS_LIST_MAX_COUNT: u64 = 100;
+
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
 #[cbor(map)]
 pub struct ListArgs {
@@ -12,6 +17,10 @@ pub struct ListArgs {
 
     #[n(2)]
     pub filter: Option<events::EventFilter>,
+
+    /// A cursor returned by a previous call, to continue listing from there.
+    #[n(3)]
+    pub cursor: Option<ByteVec>,
 }
 
 #[derive(Encode, Decode)]
@@ -22,4 +31,144 @@ pub struct ListReturns {
 
     #[n(1)]
     pub events: Vec<events::EventLog>,
+
+    /// Opaque cursor to pass to the next call if there are more events. None if
+    /// this is the last page.
+    #[n(2)]
+    pub cursor: Option<ByteVec>,
+}
+
+impl ListArgs {
+    /// Select a page of events from an iterator of events sorted by ascending id,
+    /// applying the filter, order, count and cursor of this query. `nb_events` is
+    /// the number of events matching the filter.
+    pub fn paginate<'a, I>(&self, events: I) -> ListReturns
+    where
+        I: DoubleEndedIterator<Item = &'a EventLog> + 'a,
+    {
+        let descending = self.order == Some(SortOrder::Descending);
+        let count = self
+            .count
+            .map_or(EVENTS_LIST_MAX_COUNT, |c| c.min(EVENTS_LIST_MAX_COUNT)) as usize;
+
+        let events: Box<dyn Iterator<Item = &'a EventLog> + 'a> = if descending {
+            Box::new(events.rev())
+        } else {
+            Box::new(events)
+        };
+
+        // The cursor is the id of the last event of the previous page.
+        let cursor = self.cursor.clone().map(EventId::from);
+        let mut nb_events = 0;
+        let mut page = Vec::new();
+        for event in events {
+            if !self.filter.as_ref().map_or(true, |f| f.matches(event)) {
+                continue;
+            }
+            nb_events += 1;
+
+            let after_cursor = cursor.as_ref().map_or(true, |c| {
+                if descending {
+                    &event.id < c
+                } else {
+                    &event.id > c
+                }
+            });
+            if after_cursor && page.len() <= count {
+                page.push(event.clone());
+            }
+        }
+
+        let cursor = if page.len() > count {
+            page.truncate(count);
+            page.last().map(|e| e.id.clone().into())
+        } else {
+            None
+        };
+
+        ListReturns {
+            nb_events,
+            events: page,
+            cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::events::{EventFilter, EventInfo, EventKind};
+    use crate::types::Timestamp;
+    use crate::Identity;
+
+    fn events() -> Vec<EventLog> {
+        (1u64..=5)
+            .map(|i| EventLog {
+                id: EventId::from(i),
+                time: Timestamp::now(),
+                content: if i % 2 == 0 {
+                    EventInfo::AccountDisable {
+                        account: Identity::public_key_raw([i as u8; 28]),
+                    }
+                } else {
+                    EventInfo::Send {
+                        from: Identity::public_key_raw([i as u8; 28]),
+                        to: Identity::anonymous(),
+                        symbol: Default::default(),
+                        amount: Default::default(),
+                    }
+                },
+            })
+            .collect()
+    }
+
+    fn ids(returns: &ListReturns) -> Vec<EventId> {
+        returns.events.iter().map(|e| e.id.clone()).collect()
+    }
+
+    #[test]
+    fn cursor() {
+        let events = events();
+        let mut args = ListArgs {
+            count: Some(2),
+            order: Some(SortOrder::Descending),
+            filter: None,
+            cursor: None,
+        };
+
+        let first = args.paginate(events.iter());
+        assert_eq!(first.nb_events, 5);
+        assert_eq!(ids(&first), vec![EventId::from(5u64), EventId::from(4u64)]);
+
+        args.cursor = first.cursor;
+        let second = args.paginate(events.iter());
+        assert_eq!(ids(&second), vec![EventId::from(3u64), EventId::from(2u64)]);
+
+        args.cursor = second.cursor;
+        let third = args.paginate(events.iter());
+        assert_eq!(ids(&third), vec![EventId::from(1u64)]);
+        assert!(third.cursor.is_none());
+    }
+
+    #[test]
+    fn filter() {
+        let events = events();
+        let args = ListArgs {
+            count: None,
+            order: None,
+            filter: Some(EventFilter {
+                kind: Some(vec![EventKind::Send].into()),
+                ..Default::default()
+            }),
+            cursor: None,
+        };
+
+        let returns = args.paginate(events.iter());
+        assert_eq!(returns.nb_events, 3);
+        assert_eq!(
+            ids(&returns),
+            vec![EventId::from(1u64), EventId::from(3u64), EventId::from(5u64)]
+        );
+        assert!(returns.cursor.is_none());
+    }
 }
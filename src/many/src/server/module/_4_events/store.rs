@@ -0,0 +1,474 @@
+use super::{
+    EventsModuleBackend, InfoArgs, InfoReturn, ListArgs, ListReturns, EVENTS_LIST_MAX_COUNT,
+};
+use crate::types::events::{EventFilter, EventId, EventInfo, EventKind, EventLog};
+use crate::types::ledger::Symbol;
+use crate::types::{CborRange, SortOrder, Timestamp};
+use crate::{Identity, ManyError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// Persistence of the events of an [EventStore].
+pub trait EventStorage: Send {
+    /// Persist a new event.
+    fn insert(&mut self, log: &EventLog) -> Result<(), ManyError>;
+
+    fn get(&self, id: &EventId) -> Result<Option<EventLog>, ManyError>;
+
+    /// All the events stored, used to rebuild the indexes when loading.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<EventLog, ManyError>> + '_>;
+}
+
+/// An [EventStorage] that keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryEventStorage {
+    events: BTreeMap<EventId, EventLog>,
+}
+
+impl EventStorage for InMemoryEventStorage {
+    fn insert(&mut self, log: &EventLog) -> Result<(), ManyError> {
+        self.events.insert(log.id.clone(), log.clone());
+        Ok(())
+    }
+
+    fn get(&self, id: &EventId) -> Result<Option<EventLog>, ManyError> {
+        Ok(self.events.get(id).cloned())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<EventLog, ManyError>> + '_> {
+        Box::new(self.events.values().cloned().map(Ok))
+    }
+}
+
+/// A destination for the events emitted by backends.
+pub trait EventSink: Send {
+    fn emit(&mut self, time: Timestamp, content: EventInfo) -> Result<(), ManyError>;
+}
+
+/// Collects the events, ignoring their time.
+impl EventSink for Vec<EventInfo> {
+    fn emit(&mut self, _time: Timestamp, content: EventInfo) -> Result<(), ManyError> {
+        self.push(content);
+        Ok(())
+    }
+}
+
+/// Shares a sink between backends, e.g. an [EventStore] also used as the
+/// events module backend.
+impl<T: EventSink> EventSink for Arc<Mutex<T>> {
+    fn emit(&mut self, time: Timestamp, content: EventInfo) -> Result<(), ManyError> {
+        self.lock()
+            .map_err(|_| ManyError::unknown("Event sink mutex poisoned.".to_string()))?
+            .emit(time, content)
+    }
+}
+
+/// A reusable event log. It assigns monotonic ids to events and indexes them
+/// by account, kind, symbol and time to answer `events.list` without scanning
+/// every event. It implements [EventsModuleBackend] directly.
+///
+/// `events.list` only loads events from the cursor until the page is full. Its
+/// `nb_events` is the number of events matching the indexed criteria of the
+/// filter (account, kind, symbol, date and id range), which is exact unless
+/// other criteria are used.
+pub struct EventStore<S: EventStorage> {
+    storage: S,
+    next_id: u64,
+    ids: BTreeSet<EventId>,
+    by_account: BTreeMap<Identity, BTreeSet<EventId>>,
+    by_kind: BTreeMap<EventKind, BTreeSet<EventId>>,
+    by_symbol: BTreeMap<Symbol, BTreeSet<EventId>>,
+    by_time: BTreeMap<Timestamp, BTreeSet<EventId>>,
+}
+
+impl<S: EventStorage> EventStore<S> {
+    /// Create a store from a storage, indexing the events already in it.
+    pub fn load(storage: S) -> Result<Self, ManyError> {
+        let mut store = Self {
+            storage,
+            next_id: 0,
+            ids: BTreeSet::new(),
+            by_account: BTreeMap::new(),
+            by_kind: BTreeMap::new(),
+            by_symbol: BTreeMap::new(),
+            by_time: BTreeMap::new(),
+        };
+
+        let events = store.storage.iter().collect::<Result<Vec<_>, _>>()?;
+        for log in &events {
+            store.index(log);
+        }
+        Ok(store)
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Add an event to the log, returning its id.
+    pub fn add(&mut self, time: Timestamp, content: EventInfo) -> Result<EventId, ManyError> {
+        let log = EventLog {
+            id: EventId::from(self.next_id),
+            time,
+            content,
+        };
+        self.storage.insert(&log)?;
+        self.index(&log);
+        Ok(log.id)
+    }
+
+    fn index(&mut self, log: &EventLog) {
+        // Ids are big-endian u64s so they sort in the order they were assigned.
+        let id = log.id.clone();
+        let n = id.as_ref().iter().fold(0u64, |acc, b| {
+            acc.saturating_mul(256).saturating_add(u64::from(*b))
+        });
+        self.next_id = self.next_id.max(n.saturating_add(1));
+
+        for address in log.addresses() {
+            self.by_account
+                .entry(address)
+                .or_default()
+                .insert(id.clone());
+        }
+        self.by_kind
+            .entry(log.kind())
+            .or_default()
+            .insert(id.clone());
+        if let Some(symbol) = log.symbol() {
+            self.by_symbol
+                .entry(*symbol)
+                .or_default()
+                .insert(id.clone());
+        }
+        self.by_time.entry(log.time).or_default().insert(id.clone());
+        self.ids.insert(id);
+    }
+
+    /// The ids of the events that can match a filter, using the indexes. This is
+    /// a superset of the matching events; the filter still has to be checked.
+    fn candidates(&self, filter: &EventFilter) -> BTreeSet<EventId> {
+        let mut sets: Vec<BTreeSet<EventId>> = Vec::new();
+
+        if let Some(accounts) = &filter.account {
+            sets.push(union(
+                accounts.iter().filter_map(|a| self.by_account.get(a)),
+            ));
+        }
+        if let Some(kinds) = &filter.kind {
+            sets.push(union(kinds.iter().filter_map(|k| self.by_kind.get(k))));
+        }
+        if let Some(symbols) = &filter.symbol {
+            sets.push(union(symbols.iter().filter_map(|s| self.by_symbol.get(s))));
+        }
+        if let Some(range) = &filter.date_range {
+            sets.push(if is_empty(range) {
+                BTreeSet::new()
+            } else {
+                union(self.by_time.range(*range).map(|(_, ids)| ids))
+            });
+        }
+        if let Some(range) = &filter.id_range {
+            sets.push(if is_empty(range) {
+                BTreeSet::new()
+            } else {
+                self.ids.range(range.clone()).cloned().collect()
+            });
+        }
+
+        // Intersect starting from the smallest set.
+        sets.sort_by_key(|s| s.len());
+        let mut sets = sets.into_iter();
+        match sets.next() {
+            None => self.ids.clone(),
+            Some(first) => sets.fold(first, |acc, set| acc.intersection(&set).cloned().collect()),
+        }
+    }
+}
+
+impl<S: EventStorage> EventSink for EventStore<S> {
+    fn emit(&mut self, time: Timestamp, content: EventInfo) -> Result<(), ManyError> {
+        self.add(time, content).map(|_| ())
+    }
+}
+
+impl<S: EventStorage> EventsModuleBackend for EventStore<S> {
+    fn info(&self, _args: InfoArgs) -> Result<InfoReturn, ManyError> {
+        Ok(InfoReturn {
+            total: self.ids.len() as u64,
+            event_types: self.by_kind.keys().copied().collect(),
+        })
+    }
+
+    fn list(&self, args: ListArgs) -> Result<ListReturns, ManyError> {
+        let candidates;
+        let ids = match &args.filter {
+            Some(filter) => {
+                candidates = self.candidates(filter);
+                &candidates
+            }
+            None => &self.ids,
+        };
+
+        let descending = args.order == Some(SortOrder::Descending);
+        let count = args
+            .count
+            .map_or(EVENTS_LIST_MAX_COUNT, |c| c.min(EVENTS_LIST_MAX_COUNT))
+            as usize;
+
+        // The cursor is the id of the last event of the previous page.
+        let range = match args.cursor.clone().map(EventId::from) {
+            None => (Bound::Unbounded, Bound::Unbounded),
+            Some(cursor) if descending => (Bound::Unbounded, Bound::Excluded(cursor)),
+            Some(cursor) => (Bound::Excluded(cursor), Bound::Unbounded),
+        };
+        let after_cursor = ids.range(range);
+        let after_cursor: Box<dyn Iterator<Item = &EventId> + '_> = if descending {
+            Box::new(after_cursor.rev())
+        } else {
+            Box::new(after_cursor)
+        };
+
+        let mut events = Vec::new();
+        for id in after_cursor {
+            if events.len() > count {
+                break;
+            }
+            if let Some(log) = self.storage.get(id)? {
+                if args.filter.as_ref().map_or(true, |f| f.matches(&log)) {
+                    events.push(log);
+                }
+            }
+        }
+
+        let cursor = if events.len() > count {
+            events.truncate(count);
+            events.last().map(|e| e.id.clone().into())
+        } else {
+            None
+        };
+
+        Ok(ListReturns {
+            nb_events: ids.len() as u64,
+            events,
+            cursor,
+        })
+    }
+}
+
+fn union<'a, I: Iterator<Item = &'a BTreeSet<EventId>>>(sets: I) -> BTreeSet<EventId> {
+    sets.flat_map(|set| set.iter().cloned()).collect()
+}
+
+/// Whether a range cannot contain anything. [BTreeMap::range] panics on those.
+fn is_empty<T: Ord>(range: &CborRange<T>) -> bool {
+    match (&range.start, &range.end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::identity::testing::identity;
+    use crate::types::ledger::TokenAmount;
+    use crate::types::SortOrder;
+
+    fn send(from: u32, to: u32, symbol: u32) -> EventInfo {
+        EventInfo::Send {
+            from: identity(from),
+            to: identity(to),
+            symbol: identity(symbol),
+            amount: TokenAmount::from(10u64),
+        }
+    }
+
+    fn store() -> EventStore<InMemoryEventStorage> {
+        let mut store = EventStore::load(InMemoryEventStorage::default()).unwrap();
+        store
+            .add(Timestamp::new(1000).unwrap(), send(1, 2, 100))
+            .unwrap();
+        store
+            .add(Timestamp::new(1001).unwrap(), send(2, 3, 100))
+            .unwrap();
+        store
+            .add(
+                Timestamp::new(1002).unwrap(),
+                EventInfo::AccountDisable {
+                    account: identity(1),
+                },
+            )
+            .unwrap();
+        store
+            .add(Timestamp::new(1003).unwrap(), send(3, 1, 101))
+            .unwrap();
+        store
+    }
+
+    fn list(store: &EventStore<InMemoryEventStorage>, filter: EventFilter) -> Vec<EventId> {
+        store
+            .list(ListArgs {
+                count: None,
+                order: Some(SortOrder::Ascending),
+                filter: Some(filter),
+                cursor: None,
+            })
+            .unwrap()
+            .events
+            .into_iter()
+            .map(|e| e.id)
+            .collect()
+    }
+
+    #[test]
+    fn info() {
+        let info = store().info(InfoArgs {}).unwrap();
+        assert_eq!(info.total, 4);
+        assert_eq!(
+            info.event_types,
+            vec![EventKind::Send, EventKind::AccountDisable]
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let store = store();
+        assert_eq!(
+            list(
+                &store,
+                EventFilter {
+                    account: Some(vec![identity(1)].into()),
+                    ..Default::default()
+                }
+            ),
+            vec![
+                EventId::from(0u64),
+                EventId::from(2u64),
+                EventId::from(3u64)
+            ]
+        );
+        assert_eq!(
+            list(
+                &store,
+                EventFilter {
+                    account: Some(vec![identity(1)].into()),
+                    kind: Some(vec![EventKind::Send].into()),
+                    symbol: Some(vec![identity(100)].into()),
+                    ..Default::default()
+                }
+            ),
+            vec![EventId::from(0u64)]
+        );
+        assert_eq!(
+            list(
+                &store,
+                EventFilter {
+                    date_range: Some(CborRange {
+                        start: Bound::Excluded(Timestamp::new(1000).unwrap()),
+                        end: Bound::Included(Timestamp::new(1002).unwrap()),
+                    }),
+                    ..Default::default()
+                }
+            ),
+            vec![EventId::from(1u64), EventId::from(2u64)]
+        );
+        assert!(list(
+            &store,
+            EventFilter {
+                id_range: Some(CborRange {
+                    start: Bound::Excluded(2u64.into()),
+                    end: Bound::Excluded(1u64.into()),
+                }),
+                ..Default::default()
+            }
+        )
+        .is_empty());
+    }
+
+    /// A storage counting the events it loads.
+    #[derive(Default)]
+    struct CountingStorage(InMemoryEventStorage, std::sync::atomic::AtomicUsize);
+
+    impl EventStorage for CountingStorage {
+        fn insert(&mut self, log: &EventLog) -> Result<(), ManyError> {
+            self.0.insert(log)
+        }
+
+        fn get(&self, id: &EventId) -> Result<Option<EventLog>, ManyError> {
+            self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.0.get(id)
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = Result<EventLog, ManyError>> + '_> {
+            self.0.iter()
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let mut store = EventStore::load(CountingStorage::default()).unwrap();
+        for i in 0..10 {
+            store
+                .add(Timestamp::new(1000 + i).unwrap(), send(1, 2, 100))
+                .unwrap();
+        }
+
+        let mut args = ListArgs {
+            count: Some(3),
+            order: Some(SortOrder::Descending),
+            filter: None,
+            cursor: None,
+        };
+        let first = store.list(args.clone()).unwrap();
+        assert_eq!(first.nb_events, 10);
+        assert_eq!(
+            first
+                .events
+                .iter()
+                .map(|e| e.id.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                EventId::from(9u64),
+                EventId::from(8u64),
+                EventId::from(7u64)
+            ]
+        );
+        // Only the page and one more event to know there is a next page.
+        assert_eq!(
+            store.storage().1.load(std::sync::atomic::Ordering::Relaxed),
+            4
+        );
+
+        args.cursor = first.cursor;
+        let second = store.list(args).unwrap();
+        assert_eq!(
+            second
+                .events
+                .iter()
+                .map(|e| e.id.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                EventId::from(6u64),
+                EventId::from(5u64),
+                EventId::from(4u64)
+            ]
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let storage = store().storage().clone();
+        let mut store = EventStore::load(storage).unwrap();
+        assert_eq!(store.info(InfoArgs {}).unwrap().total, 4);
+
+        let id = store
+            .add(Timestamp::new(1004).unwrap(), send(1, 2, 100))
+            .unwrap();
+        assert_eq!(id, EventId::from(4u64));
+    }
+}
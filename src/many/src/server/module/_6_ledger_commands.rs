@@ -4,14 +4,30 @@ use many_macros::many_module;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
+mod schedule;
 mod send;
 
+pub use schedule::*;
 pub use send::*;
 
 #[many_module(name = LedgerCommandsModule, id = 6, namespace = ledger, many_crate = crate)]
 #[cfg_attr(test, automock)]
 pub trait LedgerCommandsModuleBackend: Send {
     fn send(&mut self, sender: &Identity, args: SendArgs) -> Result<SendReturns, ManyError>;
+
+    /// Schedule a transfer to be executed at a later time, possibly recurring.
+    fn schedule_send(
+        &mut self,
+        sender: &Identity,
+        args: ScheduleSendArgs,
+    ) -> Result<ScheduleSendReturns, ManyError>;
+
+    /// Cancel a scheduled transfer before (all of) its executions happened.
+    fn cancel_scheduled_send(
+        &mut self,
+        sender: &Identity,
+        args: CancelScheduledSendArgs,
+    ) -> Result<CancelScheduledSendReturns, ManyError>;
 }
 
 #[cfg(test)]
@@ -46,4 +62,61 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn schedule_send() {
+        let data = ScheduleSendArgs {
+            from: None,
+            to: identity(2),
+            amount: TokenAmount::from(512u16),
+            symbol: identity(3),
+            schedule: Schedule::recurring(crate::types::Timestamp::now(), 3600, Some(12)),
+        };
+        let mut mock = MockLedgerCommandsModuleBackend::new();
+        mock.expect_schedule_send()
+            .with(predicate::eq(tests::identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_sender, _args| {
+                Ok(ScheduleSendReturns {
+                    token: vec![1, 2, 3].into(),
+                })
+            });
+        let module = super::LedgerCommandsModule::new(Arc::new(Mutex::new(mock)));
+
+        let result: ScheduleSendReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "ledger.scheduleSend",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result.token.to_vec(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn cancel_scheduled_send() {
+        let data = CancelScheduledSendArgs {
+            token: vec![1, 2, 3].into(),
+        };
+        let mut mock = MockLedgerCommandsModuleBackend::new();
+        mock.expect_cancel_scheduled_send()
+            .with(predicate::eq(tests::identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_sender, _args| Ok(CancelScheduledSendReturns {}));
+        let module = super::LedgerCommandsModule::new(Arc::new(Mutex::new(mock)));
+
+        let _: CancelScheduledSendReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "ledger.cancelScheduledSend",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+    }
 }
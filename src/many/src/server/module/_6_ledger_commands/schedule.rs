@@ -0,0 +1,257 @@
+use crate::server::module::ledger::{invalid_schedule, SendArgs};
+use crate::server::module::EmptyReturn;
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::Timestamp;
+use crate::{Identity, ManyError};
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+use std::time::Duration;
+
+/// When a scheduled transfer should be executed. A schedule without an interval
+/// executes exactly once, at `start`.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Schedule {
+    #[n(0)]
+    pub start: Timestamp,
+
+    /// If set, the transfer repeats every `interval_in_secs` seconds after `start`.
+    #[n(1)]
+    pub interval_in_secs: Option<u64>,
+
+    /// The maximum number of executions of a recurring transfer. Unbounded if unset.
+    #[n(2)]
+    pub count: Option<u64>,
+}
+
+impl Schedule {
+    pub fn once(at: Timestamp) -> Self {
+        Self {
+            start: at,
+            interval_in_secs: None,
+            count: None,
+        }
+    }
+
+    pub fn recurring(start: Timestamp, interval_in_secs: u64, count: Option<u64>) -> Self {
+        Self {
+            start,
+            interval_in_secs: Some(interval_in_secs),
+            count,
+        }
+    }
+
+    pub fn is_recurring(&self) -> bool {
+        self.interval_in_secs.is_some()
+    }
+
+    /// Verify that a schedule can be accepted at time `now`. The first execution
+    /// must be in the future, and recurring schedules need a non-zero interval and count.
+    pub fn validate(&self, now: Timestamp) -> Result<(), ManyError> {
+        if self.start <= now {
+            return Err(invalid_schedule("start time must be in the future"));
+        }
+        if self.interval_in_secs == Some(0) {
+            return Err(invalid_schedule("interval cannot be zero"));
+        }
+        if self.count == Some(0) {
+            return Err(invalid_schedule("count cannot be zero"));
+        }
+        Ok(())
+    }
+
+    /// Returns the time of the n-th execution (starting at 0), or None if the
+    /// schedule does not have that many executions.
+    ///
+    /// ```
+    /// # use many::server::module::ledger::Schedule;
+    /// # use many::types::Timestamp;
+    /// let schedule = Schedule::recurring(Timestamp::new(1000).unwrap(), 10, Some(2));
+    /// assert_eq!(schedule.nth(0), Some(Timestamp::new(1000).unwrap()));
+    /// assert_eq!(schedule.nth(1), Some(Timestamp::new(1010).unwrap()));
+    /// assert_eq!(schedule.nth(2), None);
+    /// ```
+    pub fn nth(&self, n: u64) -> Option<Timestamp> {
+        if self.count.map_or(false, |count| n >= count) {
+            return None;
+        }
+        match (n, self.interval_in_secs) {
+            (0, _) => Some(self.start),
+            (_, None) => None,
+            (n, Some(interval)) => self
+                .start
+                .0
+                .checked_add(Duration::from_secs(interval.checked_mul(n)?))
+                .map(Timestamp),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ScheduleSendArgs {
+    #[n(0)]
+    pub from: Option<Identity>,
+
+    #[n(1)]
+    pub to: Identity,
+
+    #[n(2)]
+    pub amount: TokenAmount,
+
+    #[n(3)]
+    pub symbol: Symbol,
+
+    #[n(4)]
+    pub schedule: Schedule,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ScheduleSendReturns {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct CancelScheduledSendArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type CancelScheduledSendReturns = EmptyReturn;
+
+/// A scheduled transfer, as kept in storage by a backend. This is useful as utility
+/// for managing scheduled transfers in your backend.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ScheduledSend {
+    /// The identity that scheduled the transfer.
+    #[n(0)]
+    pub sender: Identity,
+
+    #[n(1)]
+    pub from: Identity,
+
+    #[n(2)]
+    pub to: Identity,
+
+    #[n(3)]
+    pub amount: TokenAmount,
+
+    #[n(4)]
+    pub symbol: Symbol,
+
+    #[n(5)]
+    pub schedule: Schedule,
+
+    /// Number of times this transfer was executed.
+    #[n(6)]
+    pub executed: u64,
+}
+
+impl ScheduledSend {
+    pub fn new(sender: &Identity, args: ScheduleSendArgs) -> Self {
+        Self {
+            sender: *sender,
+            from: args.from.unwrap_or(*sender),
+            to: args.to,
+            amount: args.amount,
+            symbol: args.symbol,
+            schedule: args.schedule,
+            executed: 0,
+        }
+    }
+
+    /// The time of the next execution, or None if the schedule is exhausted.
+    pub fn next_execution(&self) -> Option<Timestamp> {
+        self.schedule.nth(self.executed)
+    }
+
+    pub fn is_due(&self, now: Timestamp) -> bool {
+        self.next_execution().map_or(false, |t| t <= now)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_execution().is_none()
+    }
+
+    /// Only the identity that scheduled the transfer, or the account it is sent
+    /// from, can cancel it.
+    pub fn can_cancel(&self, id: &Identity) -> bool {
+        &self.sender == id || &self.from == id
+    }
+
+    /// Record an execution and return the transfer to apply to the ledger.
+    pub fn execute(&mut self) -> SendArgs {
+        self.executed += 1;
+        SendArgs {
+            from: Some(self.from),
+            to: self.to,
+            amount: self.amount.clone(),
+            symbol: self.symbol,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::identity::testing::identity;
+
+    #[test]
+    fn schedule_once() {
+        let schedule = Schedule::once(Timestamp::new(1000).unwrap());
+        assert!(!schedule.is_recurring());
+        assert_eq!(schedule.nth(0), Some(Timestamp::new(1000).unwrap()));
+        assert_eq!(schedule.nth(1), None);
+    }
+
+    #[test]
+    fn schedule_unbounded() {
+        let schedule = Schedule::recurring(Timestamp::new(1000).unwrap(), 60, None);
+        assert_eq!(schedule.nth(100), Some(Timestamp::new(7000).unwrap()));
+        assert_eq!(schedule.nth(u64::MAX), None);
+    }
+
+    #[test]
+    fn schedule_validate() {
+        let now = Timestamp::new(1000).unwrap();
+        let later = Timestamp::new(2000).unwrap();
+        assert!(Schedule::once(later).validate(now).is_ok());
+        assert!(Schedule::once(now).validate(now).is_err());
+        assert!(Schedule::recurring(later, 0, None).validate(now).is_err());
+        assert!(Schedule::recurring(later, 10, Some(0)).validate(now).is_err());
+        assert!(Schedule::recurring(later, 10, Some(3)).validate(now).is_ok());
+    }
+
+    #[test]
+    fn scheduled_send_execute() {
+        let mut scheduled = ScheduledSend::new(
+            &identity(1),
+            ScheduleSendArgs {
+                from: None,
+                to: identity(2),
+                amount: 10u16.into(),
+                symbol: identity(3),
+                schedule: Schedule::recurring(Timestamp::new(1000).unwrap(), 10, Some(2)),
+            },
+        );
+
+        assert!(!scheduled.is_due(Timestamp::new(999).unwrap()));
+        assert!(scheduled.is_due(Timestamp::new(1000).unwrap()));
+        let send = scheduled.execute();
+        assert_eq!(send.from, Some(identity(1)));
+        assert_eq!(send.to, identity(2));
+
+        assert!(!scheduled.is_due(Timestamp::new(1005).unwrap()));
+        assert!(scheduled.is_due(Timestamp::new(1010).unwrap()));
+        scheduled.execute();
+        assert!(scheduled.is_done());
+        assert!(!scheduled.is_due(Timestamp::new(5000).unwrap()));
+
+        assert!(scheduled.can_cancel(&identity(1)));
+        assert!(!scheduled.can_cancel(&identity(2)));
+    }
+}
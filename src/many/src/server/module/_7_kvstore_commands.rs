@@ -4,16 +4,54 @@ use many_macros::many_module;
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
+mod batch;
 mod delete;
 mod put;
+mod transfer;
+mod upload;
+pub use batch::*;
 pub use delete::*;
 pub use put::*;
+pub use transfer::*;
+pub use upload::*;
 
 #[many_module(name = KvStoreCommandsModule, id = 7, namespace = kvstore, many_crate = crate)]
 #[cfg_attr(test, automock)]
 pub trait KvStoreCommandsModuleBackend: Send {
     fn put(&mut self, sender: &Identity, args: PutArgs) -> Result<PutReturn, ManyError>;
     fn delete(&mut self, sender: &Identity, args: DeleteArgs) -> Result<DeleteReturn, ManyError>;
+
+    /// Transfer the ownership of a key to another identity.
+    fn transfer(
+        &mut self,
+        sender: &Identity,
+        args: TransferArgs,
+    ) -> Result<TransferReturn, ManyError>;
+
+    /// Apply several puts and deletes atomically.
+    fn batch(&mut self, sender: &Identity, args: BatchArgs) -> Result<BatchReturn, ManyError>;
+
+    /// Start uploading a value too large for a single put.
+    #[many(deny_anonymous)]
+    fn begin_upload(
+        &mut self,
+        sender: &Identity,
+        args: BeginUploadArgs,
+    ) -> Result<BeginUploadReturns, ManyError>;
+
+    /// Send a chunk of an upload.
+    fn put_chunk(
+        &mut self,
+        sender: &Identity,
+        args: PutChunkArgs,
+    ) -> Result<PutChunkReturns, ManyError>;
+
+    /// Verify an upload and store its manifest under the key.
+    fn commit_upload(
+        &mut self,
+        sender: &Identity,
+        args: CommitUploadArgs,
+    ) -> Result<CommitUploadReturns, ManyError>;
 }
 
 #[cfg(test)]
@@ -30,6 +68,9 @@ mod tests {
         let data = PutArgs {
             key: ByteVec::from(vec![1]),
             value: ByteVec::from(vec![2]),
+            acl: None,
+            precondition: None,
+            ttl_in_secs: Some(60),
         };
 
         let mut mock = MockKvStoreCommandsModuleBackend::new();
@@ -75,4 +116,147 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn transfer() {
+        let data = TransferArgs {
+            key: ByteVec::from(vec![1]),
+            new_owner: tests::identity(2),
+        };
+
+        let mut mock = MockKvStoreCommandsModuleBackend::new();
+        mock.expect_transfer()
+            .with(
+                predicate::eq(tests::identity(1)),
+                predicate::eq(data.clone()),
+            )
+            .times(1)
+            .returning(|_sender, _args| Ok(TransferReturn {}));
+        let module = super::KvStoreCommandsModule::new(Arc::new(Mutex::new(mock)));
+
+        let _: TransferReturn = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "kvstore.transfer",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn batch() {
+        let data = BatchArgs {
+            operations: vec![
+                BatchOperation::Put(PutArgs {
+                    key: ByteVec::from(vec![1]),
+                    value: ByteVec::from(vec![2]),
+                    acl: None,
+                    precondition: Some(PutPrecondition::MustNotExist),
+                    ttl_in_secs: None,
+                }),
+                BatchOperation::Delete(DeleteArgs {
+                    key: ByteVec::from(vec![3]),
+                }),
+            ],
+        };
+
+        let mut mock = MockKvStoreCommandsModuleBackend::new();
+        mock.expect_batch()
+            .with(
+                predicate::eq(tests::identity(1)),
+                predicate::eq(data.clone()),
+            )
+            .times(1)
+            .returning(|_sender, _args| Ok(BatchReturn {}));
+        let module = super::KvStoreCommandsModule::new(Arc::new(Mutex::new(mock)));
+
+        let _: BatchReturn = minicbor::decode(
+            &call_module_cbor(1, &module, "kvstore.batch", minicbor::to_vec(data).unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn upload() {
+        let begin = BeginUploadArgs {
+            key: ByteVec::from(vec![1]),
+            acl: None,
+        };
+        let chunk = PutChunkArgs {
+            upload: ByteVec::from(vec![9]),
+            index: 0,
+            data: ByteVec::from(vec![2, 3]),
+        };
+        let commit = CommitUploadArgs {
+            upload: ByteVec::from(vec![9]),
+            size: 2,
+            hash: crate::server::module::kvstore::value_hash(&[2, 3]),
+        };
+
+        let mut mock = MockKvStoreCommandsModuleBackend::new();
+        mock.expect_begin_upload()
+            .with(predicate::eq(tests::identity(1)), predicate::eq(begin.clone()))
+            .times(1)
+            .returning(|_sender, _args| {
+                Ok(BeginUploadReturns {
+                    upload: ByteVec::from(vec![9]),
+                })
+            });
+        mock.expect_put_chunk()
+            .with(predicate::eq(tests::identity(1)), predicate::eq(chunk.clone()))
+            .times(1)
+            .returning(|_sender, _args| Ok(PutChunkReturns {}));
+        mock.expect_commit_upload()
+            .with(predicate::eq(tests::identity(1)), predicate::eq(commit.clone()))
+            .times(1)
+            .returning(|_sender, _args| Ok(CommitUploadReturns {}));
+        let module = super::KvStoreCommandsModule::new(Arc::new(Mutex::new(mock)));
+
+        let returns: BeginUploadReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "kvstore.beginUpload",
+                minicbor::to_vec(begin.clone()).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(returns.upload.to_vec(), vec![9]);
+
+        let _: PutChunkReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "kvstore.putChunk",
+                minicbor::to_vec(chunk).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let _: CommitUploadReturns = minicbor::decode(
+            &call_module_cbor(
+                1,
+                &module,
+                "kvstore.commitUpload",
+                minicbor::to_vec(commit).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // Anonymous uploads are refused.
+        assert!(call_module_cbor(
+            0,
+            &module,
+            "kvstore.beginUpload",
+            minicbor::to_vec(begin).unwrap()
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,159 @@
+use crate::server::module::kvstore::{DeleteArgs, PutArgs};
+use crate::server::module::EmptyReturn;
+use minicbor::data::Type;
+use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
+
+pub(super) const KVSTORE_BATCH_MAX_OPERATIONS: usize = 100;
+
+/// A single operation of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchOperation {
+    Put(PutArgs),
+    Delete(DeleteArgs),
+}
+
+impl<C> Encode<C> for BatchOperation {
+    fn encode<W: encode::Write>(
+        &self,
+        e: &mut Encoder<W>,
+        _: &mut C,
+    ) -> Result<(), encode::Error<W::Error>> {
+        match self {
+            BatchOperation::Put(args) => {
+                e.map(1)?.u8(0)?.encode(args)?;
+            }
+            BatchOperation::Delete(args) => {
+                e.map(1)?.u8(1)?.encode(args)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for BatchOperation {
+    fn decode(d: &mut Decoder<'b>, _: &mut C) -> Result<Self, decode::Error> {
+        if d.map()? != Some(1) {
+            return Err(decode::Error::message(
+                "Invalid length for batch operation map.",
+            ));
+        }
+
+        match d.u8()? {
+            0 => Ok(BatchOperation::Put(d.decode()?)),
+            1 => Ok(BatchOperation::Delete(d.decode()?)),
+            x => Err(decode::Error::unknown_variant(u32::from(x))),
+        }
+    }
+}
+
+/// A list of puts and deletes that should be applied atomically; either all
+/// operations succeed (including their preconditions) or none is applied.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct BatchArgs {
+    #[n(0)]
+    #[cbor(decode_with = "decode_operations")]
+    pub operations: Vec<BatchOperation>,
+}
+
+pub type BatchReturn = EmptyReturn;
+
+/// Operations decoder. Check if the number of operations is less than or equal
+/// to the maximum allowed, without decoding more than that. Each operation checks
+/// its own key and value sizes.
+fn decode_operations<C>(
+    d: &mut minicbor::Decoder,
+    _: &mut C,
+) -> Result<Vec<BatchOperation>, minicbor::decode::Error> {
+    let over_limit = || minicbor::decode::Error::message("Number of operations over limit");
+
+    match d.datatype()? {
+        Type::Array | Type::ArrayIndef => {
+            // Definite arrays declare their length upfront.
+            if let Some(len) = d.probe().array()? {
+                if len > KVSTORE_BATCH_MAX_OPERATIONS as u64 {
+                    return Err(over_limit());
+                }
+            }
+
+            let mut operations = Vec::new();
+            for operation in d.array_iter::<BatchOperation>()? {
+                if operations.len() == KVSTORE_BATCH_MAX_OPERATIONS {
+                    return Err(over_limit());
+                }
+                operations.push(operation?);
+            }
+            Ok(operations)
+        }
+        x => Err(minicbor::decode::Error::type_mismatch(x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minicbor::bytes::ByteVec;
+
+    fn put(key: u8) -> BatchOperation {
+        BatchOperation::Put(PutArgs {
+            key: ByteVec::from(vec![key]),
+            value: ByteVec::from(vec![1]),
+            acl: None,
+            precondition: None,
+            ttl_in_secs: None,
+        })
+    }
+
+    #[test]
+    fn batch_encode() {
+        let batch = BatchArgs {
+            operations: vec![
+                put(1),
+                BatchOperation::Delete(DeleteArgs {
+                    key: ByteVec::from(vec![2]),
+                }),
+            ],
+        };
+        let enc = minicbor::to_vec(&batch).unwrap();
+        assert_eq!(minicbor::decode::<BatchArgs>(&enc).unwrap(), batch);
+    }
+
+    #[test]
+    fn batch_over_limit() {
+        let batch = BatchArgs {
+            operations: (0..=KVSTORE_BATCH_MAX_OPERATIONS)
+                .map(|i| put(i as u8))
+                .collect(),
+        };
+        let enc = minicbor::to_vec(&batch).unwrap();
+        let dec = minicbor::decode::<BatchArgs>(&enc);
+        assert!(dec.is_err());
+        assert_eq!(
+            dec.unwrap_err().to_string(),
+            "decode error: Number of operations over limit"
+        );
+    }
+
+    #[test]
+    fn batch_indefinite_over_limit() {
+        let mut e = Encoder::new(Vec::new());
+        e.map(1).unwrap().u8(0).unwrap().begin_array().unwrap();
+        for i in 0..=KVSTORE_BATCH_MAX_OPERATIONS {
+            e.encode(put(i as u8)).unwrap();
+        }
+        e.end().unwrap();
+
+        let dec = minicbor::decode::<BatchArgs>(e.writer());
+        assert_eq!(
+            dec.unwrap_err().to_string(),
+            "decode error: Number of operations over limit"
+        );
+
+        let mut e = Encoder::new(Vec::new());
+        e.map(1).unwrap().u8(0).unwrap().begin_array().unwrap();
+        e.encode(put(1)).unwrap();
+        e.end().unwrap();
+        let dec = minicbor::decode::<BatchArgs>(e.writer()).unwrap();
+        assert_eq!(dec.operations, vec![put(1)]);
+    }
+}
@@ -6,6 +6,7 @@ use minicbor::{Decode, Encode};
 #[cbor(map)]
 pub struct DeleteArgs {
     #[n(0)]
+    #[cbor(decode_with = "super::put::decode_key")]
     pub key: ByteVec,
 }
 
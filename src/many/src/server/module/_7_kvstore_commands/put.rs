@@ -1,11 +1,13 @@
 use minicbor::bytes::ByteVec;
-use minicbor::{Decode, Encode};
+use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
 use minicbor::data::Type;
 
+use crate::server::module::kvstore::{conflict, value_hash, KeyAcl};
 use crate::server::module::EmptyReturn;
+use crate::ManyError;
 
 const KVSTORE_KEY_MAX_SIZE: usize = 248; // size is u8 but storage is in "/store/" (7 bytes long);
-const KVSTORE_VALUE_MAX_SIZE: usize = 64000; // 64kB
+pub(super) const KVSTORE_VALUE_MAX_SIZE: usize = 64000; // 64kB
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
 #[cbor(map)]
@@ -17,10 +19,84 @@ pub struct PutArgs {
     #[n(1)]
     #[cbor(decode_with = "decode_value")]
     pub value: ByteVec,
+
+    /// Identities allowed to write to this key, in addition to its owner. Only
+    /// the owner can set this.
+    #[n(2)]
+    pub acl: Option<KeyAcl>,
+
+    /// If set, the put only happens if the current value matches the precondition.
+    #[n(3)]
+    pub precondition: Option<PutPrecondition>,
+
+    /// Number of seconds after which the key expires and is removed. Never if None.
+    #[n(4)]
+    pub ttl_in_secs: Option<u64>,
+}
+
+/// A condition on the current value of a key for a put to be applied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PutPrecondition {
+    /// The key must not exist.
+    MustNotExist,
+
+    /// The current value must have this hash (see [value_hash]).
+    ValueHash(ByteVec),
+}
+
+impl PutPrecondition {
+    /// Check the precondition against the current value of the key, returning a
+    /// conflict error if it does not hold.
+    pub fn check(&self, current: Option<&[u8]>) -> Result<(), ManyError> {
+        match (self, current) {
+            (PutPrecondition::MustNotExist, None) => Ok(()),
+            (PutPrecondition::ValueHash(expected), Some(value))
+                if &value_hash(value) == expected =>
+            {
+                Ok(())
+            }
+            _ => Err(conflict()),
+        }
+    }
+}
+
+impl<C> Encode<C> for PutPrecondition {
+    fn encode<W: encode::Write>(
+        &self,
+        e: &mut Encoder<W>,
+        _: &mut C,
+    ) -> Result<(), encode::Error<W::Error>> {
+        match self {
+            PutPrecondition::MustNotExist => {
+                e.map(1)?.u8(0)?.null()?;
+            }
+            PutPrecondition::ValueHash(hash) => {
+                e.map(1)?.u8(1)?.bytes(hash)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'b, C> Decode<'b, C> for PutPrecondition {
+    fn decode(d: &mut Decoder<'b>, _: &mut C) -> Result<Self, decode::Error> {
+        if d.map()? != Some(1) {
+            return Err(decode::Error::message("Invalid length for put precondition map."));
+        }
+
+        match d.u8()? {
+            0 => {
+                d.skip()?;
+                Ok(PutPrecondition::MustNotExist)
+            }
+            1 => Ok(PutPrecondition::ValueHash(d.bytes()?.to_vec().into())),
+            x => Err(decode::Error::unknown_variant(u32::from(x))),
+        }
+    }
 }
 
 /// Data decoder. Check if the key is less than or equal to the maximum allowed size
-fn decode_key<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, minicbor::decode::Error> {
+pub(super) fn decode_key<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, minicbor::decode::Error> {
     match d.datatype()? {
         Type::Bytes => {
             let data = d.bytes()?;
@@ -29,12 +105,12 @@ fn decode_key<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, minicb
             }
             Ok(data.to_vec().into())
         }
-        _ => unimplemented!(),
+        x => Err(minicbor::decode::Error::type_mismatch(x)),
     }
 }
 
 /// Data decoder. Check if the value is less than or equal to the maximum allowed size
-fn decode_value<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, minicbor::decode::Error> {
+pub(super) fn decode_value<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, minicbor::decode::Error> {
     match d.datatype()? {
         Type::Bytes => {
             let data = d.bytes()?;
@@ -43,13 +119,15 @@ fn decode_value<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<ByteVec, mini
             }
             Ok(data.to_vec().into())
         }
-        _ => unimplemented!(),
+        x => Err(minicbor::decode::Error::type_mismatch(x)),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{PutArgs, KVSTORE_KEY_MAX_SIZE, KVSTORE_VALUE_MAX_SIZE};
+    use super::{PutArgs, PutPrecondition, KVSTORE_KEY_MAX_SIZE, KVSTORE_VALUE_MAX_SIZE};
+    use crate::server::module::kvstore::{conflict, value_hash, DeleteArgs, TransferArgs};
+    use crate::types::identity::testing::identity;
     use minicbor::bytes::ByteVec;  
     
     #[test]
@@ -57,6 +135,9 @@ mod tests {
         let tx = PutArgs {
             key: ByteVec::from(vec![1u8; KVSTORE_KEY_MAX_SIZE + 1]),
             value: ByteVec::from(vec![2]),
+            acl: None,
+            precondition: None,
+            ttl_in_secs: None,
         };
 
         let enc = minicbor::to_vec(&tx).unwrap();
@@ -65,11 +146,26 @@ mod tests {
         assert_eq!(dec.unwrap_err().to_string(), "decode error: Key size over limit");
     }
 
+    #[test]
+    fn transfer_key_over_limit() {
+        let tx = TransferArgs {
+            key: ByteVec::from(vec![1u8; KVSTORE_KEY_MAX_SIZE + 1]),
+            new_owner: identity(1),
+        };
+
+        let enc = minicbor::to_vec(&tx).unwrap();
+        let dec = minicbor::decode::<TransferArgs>(&enc);
+        assert_eq!(dec.unwrap_err().to_string(), "decode error: Key size over limit");
+    }
+
     #[test]
     fn put_value_over_limit() {
         let tx = PutArgs {
             key: ByteVec::from(vec![1]),
             value: ByteVec::from(vec![1u8; KVSTORE_VALUE_MAX_SIZE + 1]),
+            acl: None,
+            precondition: None,
+            ttl_in_secs: None,
         };
 
         let enc = minicbor::to_vec(&tx).unwrap();
@@ -77,6 +173,41 @@ mod tests {
         assert!(dec.is_err());
         assert_eq!(dec.unwrap_err().to_string(), "decode error: Value size over limit");
     }
+
+    #[test]
+    fn put_integer_key() {
+        let mut e = minicbor::Encoder::new(Vec::new());
+        e.map(2).unwrap();
+        e.u8(0).unwrap().u8(1).unwrap();
+        e.u8(1).unwrap().bytes(&[2]).unwrap();
+
+        let dec = minicbor::decode::<PutArgs>(e.writer());
+        assert!(dec.is_err());
+        assert!(minicbor::decode::<DeleteArgs>(&[0xa1, 0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn precondition() {
+        let must_not_exist = PutPrecondition::MustNotExist;
+        assert!(must_not_exist.check(None).is_ok());
+        assert_eq!(must_not_exist.check(Some(&b"foo"[..])), Err(conflict()));
+
+        let hash = PutPrecondition::ValueHash(value_hash(b"foo"));
+        assert!(hash.check(Some(&b"foo"[..])).is_ok());
+        assert_eq!(hash.check(Some(&b"bar"[..])), Err(conflict()));
+        assert_eq!(hash.check(None), Err(conflict()));
+    }
+
+    #[test]
+    fn precondition_encode() {
+        for precondition in [
+            PutPrecondition::MustNotExist,
+            PutPrecondition::ValueHash(ByteVec::from(vec![1, 2, 3])),
+        ] {
+            let enc = minicbor::to_vec(&precondition).unwrap();
+            assert_eq!(minicbor::decode::<PutPrecondition>(&enc).unwrap(), precondition);
+        }
+    }
 }
 
 pub type PutReturn = EmptyReturn;
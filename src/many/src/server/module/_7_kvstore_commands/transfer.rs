@@ -0,0 +1,17 @@
+use crate::server::module::EmptyReturn;
+use crate::Identity;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct TransferArgs {
+    #[n(0)]
+    #[cbor(decode_with = "super::put::decode_key")]
+    pub key: ByteVec,
+
+    #[n(1)]
+    pub new_owner: Identity,
+}
+
+pub type TransferReturn = EmptyReturn;
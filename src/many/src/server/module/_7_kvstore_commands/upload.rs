@@ -0,0 +1,277 @@
+use crate::server::module::kvstore::{
+    invalid_chunk, permission_denied, upload_incomplete, upload_mismatch, value_hash,
+    ChunkManifest, KeyAcl, ValueHasher,
+};
+use crate::server::module::EmptyReturn;
+use crate::{Identity, ManyError};
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+use std::collections::BTreeMap;
+
+/// Size of the chunks of an upload. Every chunk but the last one must be exactly
+/// this size.
+pub const KVSTORE_CHUNK_MAX_SIZE: usize = super::put::KVSTORE_VALUE_MAX_SIZE;
+
+/// Maximum size of a value uploaded in chunks.
+pub const KVSTORE_UPLOAD_MAX_SIZE: u64 = 1 << 30; // 1GB
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct BeginUploadArgs {
+    #[n(0)]
+    #[cbor(decode_with = "super::put::decode_key")]
+    pub key: ByteVec,
+
+    #[n(1)]
+    pub acl: Option<KeyAcl>,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct BeginUploadReturns {
+    /// Opaque token identifying the upload in the following calls.
+    #[n(0)]
+    pub upload: ByteVec,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct PutChunkArgs {
+    #[n(0)]
+    pub upload: ByteVec,
+
+    #[n(1)]
+    pub index: u64,
+
+    #[n(2)]
+    #[cbor(decode_with = "super::put::decode_value")]
+    pub data: ByteVec,
+}
+
+pub type PutChunkReturns = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct CommitUploadArgs {
+    #[n(0)]
+    pub upload: ByteVec,
+
+    /// Total size of the value.
+    #[n(1)]
+    pub size: u64,
+
+    /// Hash of the whole value (see [crate::server::module::kvstore::value_hash]).
+    #[n(2)]
+    pub hash: ByteVec,
+}
+
+pub type CommitUploadReturns = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ChunkInfo {
+    #[n(0)]
+    pub size: u64,
+
+    #[n(1)]
+    pub hash: ByteVec,
+}
+
+/// An upload in progress. This is useful as utility for managing uploads in your
+/// backend. The data of the chunks is stored by the backend, e.g. by hash.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Upload {
+    #[n(0)]
+    pub owner: Identity,
+
+    #[n(1)]
+    pub key: ByteVec,
+
+    #[n(2)]
+    pub acl: Option<KeyAcl>,
+
+    /// The chunks received so far, by index.
+    #[n(3)]
+    pub chunks: BTreeMap<u64, ChunkInfo>,
+}
+
+impl Upload {
+    pub fn new(sender: &Identity, args: BeginUploadArgs) -> Self {
+        Self {
+            owner: *sender,
+            key: args.key,
+            acl: args.acl,
+            chunks: BTreeMap::new(),
+        }
+    }
+
+    /// Record a chunk. Sending a chunk again replaces it.
+    pub fn put_chunk(&mut self, sender: &Identity, args: &PutChunkArgs) -> Result<(), ManyError> {
+        if sender != &self.owner {
+            return Err(permission_denied());
+        }
+        if args.index >= KVSTORE_UPLOAD_MAX_SIZE / KVSTORE_CHUNK_MAX_SIZE as u64 {
+            return Err(invalid_chunk(args.index));
+        }
+
+        self.chunks.insert(
+            args.index,
+            ChunkInfo {
+                size: args.data.len() as u64,
+                hash: value_hash(&args.data),
+            },
+        );
+        Ok(())
+    }
+
+    /// Verify that the upload is complete and matches the size and hash given,
+    /// returning the manifest to store under the key. `read_chunk` returns the
+    /// data of a chunk from its hash.
+    pub fn commit<F>(
+        &self,
+        sender: &Identity,
+        args: &CommitUploadArgs,
+        mut read_chunk: F,
+    ) -> Result<ChunkManifest, ManyError>
+    where
+        F: FnMut(&ByteVec) -> Result<Vec<u8>, ManyError>,
+    {
+        if sender != &self.owner {
+            return Err(permission_denied());
+        }
+        if self.chunks.is_empty()
+            || self
+                .chunks
+                .keys()
+                .enumerate()
+                .any(|(i, index)| i as u64 != *index)
+        {
+            return Err(upload_incomplete());
+        }
+
+        let last = self.chunks.len() as u64 - 1;
+        let mut size = 0u64;
+        for (index, chunk) in &self.chunks {
+            if *index != last && chunk.size != KVSTORE_CHUNK_MAX_SIZE as u64 {
+                return Err(invalid_chunk(index));
+            }
+            size += chunk.size;
+        }
+        if size != args.size {
+            return Err(upload_mismatch("size"));
+        }
+
+        let mut hasher = ValueHasher::new();
+        for chunk in self.chunks.values() {
+            hasher.update(&read_chunk(&chunk.hash)?);
+        }
+        let hash = hasher.finalize();
+        if hash != args.hash {
+            return Err(upload_mismatch("hash"));
+        }
+
+        Ok(ChunkManifest {
+            size,
+            hash,
+            chunk_size: KVSTORE_CHUNK_MAX_SIZE as u64,
+            chunks: self.chunks.values().map(|c| c.hash.clone()).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::identity::testing::identity;
+
+    fn put_chunk(upload: &mut Upload, index: u64, data: Vec<u8>) -> Result<(), ManyError> {
+        upload.put_chunk(
+            &identity(1),
+            &PutChunkArgs {
+                upload: ByteVec::from(vec![1]),
+                index,
+                data: ByteVec::from(data),
+            },
+        )
+    }
+
+    #[test]
+    fn chunk_over_limit() {
+        let args = PutChunkArgs {
+            upload: ByteVec::from(vec![1]),
+            index: 0,
+            data: ByteVec::from(vec![1u8; KVSTORE_CHUNK_MAX_SIZE + 1]),
+        };
+
+        let enc = minicbor::to_vec(&args).unwrap();
+        let dec = minicbor::decode::<PutChunkArgs>(&enc);
+        assert!(dec.is_err());
+        assert_eq!(dec.unwrap_err().to_string(), "decode error: Value size over limit");
+    }
+
+    #[test]
+    fn commit() {
+        let first = vec![1u8; KVSTORE_CHUNK_MAX_SIZE];
+        let second = vec![2u8; 10];
+        let content = [first.clone(), second.clone()].concat();
+        let chunks = BTreeMap::from([(value_hash(&first), first), (value_hash(&second), second)]);
+        let read_chunk = |hash: &ByteVec| Ok(chunks[hash].clone());
+
+        let mut upload = Upload::new(
+            &identity(1),
+            BeginUploadArgs {
+                key: ByteVec::from(b"doc".to_vec()),
+                acl: None,
+            },
+        );
+        let args = CommitUploadArgs {
+            upload: ByteVec::from(vec![1]),
+            size: content.len() as u64,
+            hash: value_hash(&content),
+        };
+
+        assert_eq!(
+            upload.put_chunk(
+                &identity(2),
+                &PutChunkArgs {
+                    upload: ByteVec::from(vec![1]),
+                    index: 0,
+                    data: ByteVec::from(vec![]),
+                }
+            ),
+            Err(permission_denied())
+        );
+
+        put_chunk(&mut upload, 1, vec![2u8; 10]).unwrap();
+        assert_eq!(
+            upload.commit(&identity(1), &args, read_chunk),
+            Err(upload_incomplete())
+        );
+
+        // Chunks other than the last must be full.
+        put_chunk(&mut upload, 0, vec![1u8; 10]).unwrap();
+        assert_eq!(
+            upload.commit(&identity(1), &args, read_chunk),
+            Err(invalid_chunk("0"))
+        );
+
+        put_chunk(&mut upload, 0, vec![1u8; KVSTORE_CHUNK_MAX_SIZE]).unwrap();
+        assert_eq!(
+            upload.commit(
+                &identity(1),
+                &CommitUploadArgs {
+                    hash: value_hash(b"other"),
+                    ..args.clone()
+                },
+                read_chunk
+            ),
+            Err(upload_mismatch("hash"))
+        );
+
+        let manifest = upload.commit(&identity(1), &args, read_chunk).unwrap();
+        assert_eq!(manifest.size, content.len() as u64);
+        assert_eq!(manifest.hash, value_hash(&content));
+        assert_eq!(manifest.chunks.len(), 2);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::message::error::Reason;
 use crate::server::module::EmptyReturn;
-use crate::types::{Either, VecOrSingle};
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::{Either, Timestamp, VecOrSingle};
 use crate::{Identity, ManyError};
 use many_macros::many_module;
 use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
@@ -8,27 +9,95 @@ use std::collections::{BTreeMap, BTreeSet};
 
 pub mod errors;
 pub mod features;
+mod store;
 
-#[derive(
-    Copy,
-    Clone,
-    Debug,
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    strum_macros::AsRefStr,
-    strum_macros::Display,
-    strum_macros::EnumIter,
-    strum_macros::EnumString,
-)]
-#[repr(u8)]
-#[strum(serialize_all = "camelCase")]
+pub use store::*;
+
+/// A role given to an identity in an account. Besides the roles defined by the
+/// protocol, applications can define their own roles, namespaced as
+/// `namespace:name` (e.g. `kvstore:canWrite`).
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Role {
     Owner,
     CanLedgerTransact,
     CanMultisigSubmit,
     CanMultisigApprove,
+    CanEscrowArbitrate,
+    Custom(String),
+}
+
+impl Role {
+    /// All the roles defined by the protocol.
+    pub const BUILTIN: [Role; 5] = [
+        Role::Owner,
+        Role::CanLedgerTransact,
+        Role::CanMultisigSubmit,
+        Role::CanMultisigApprove,
+        Role::CanEscrowArbitrate,
+    ];
+
+    /// Create an application-defined role. The name must be namespaced, as
+    /// `namespace:name`.
+    pub fn custom(name: impl ToString) -> Result<Self, ManyError> {
+        let name = name.to_string();
+        let valid = name.split_once(':').map_or(false, |(namespace, name)| {
+            let valid_part = |part: &str| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+            };
+            valid_part(namespace) && valid_part(name)
+        });
+
+        if valid {
+            Ok(Self::Custom(name))
+        } else {
+            Err(errors::unknown_role(name))
+        }
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Role::Custom(_))
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        match self {
+            Role::Owner => "owner",
+            Role::CanLedgerTransact => "canLedgerTransact",
+            Role::CanMultisigSubmit => "canMultisigSubmit",
+            Role::CanMultisigApprove => "canMultisigApprove",
+            Role::CanEscrowArbitrate => "canEscrowArbitrate",
+            Role::Custom(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = ManyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::BUILTIN
+            .into_iter()
+            .find(|r| r == &s)
+            .map_or_else(|| Role::custom(s), Ok)
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = ManyError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
 }
 
 impl PartialEq<&str> for Role {
@@ -77,9 +146,12 @@ impl<'it> Iterator for AccountMapIterator<'it> {
 
 /// A map of Subresource IDs to account. It should have a non-anonymous identity as the identity,
 /// and the inner map will contains subresource identities as keys.
+///
+/// Subresource IDs are never reused, even after an account is removed or deleted.
 pub struct AccountMap {
     id: Identity,
     inner: BTreeMap<u32, Account>,
+    next_subresource_id: u32,
 }
 
 impl AccountMap {
@@ -87,9 +159,22 @@ impl AccountMap {
         Self {
             id,
             inner: Default::default(),
+            next_subresource_id: 0,
         }
     }
 
+    /// Set the first subresource ID that can be given to a new account. Backends
+    /// that persist accounts should persist [AccountMap::next_subresource_id] and
+    /// restore it with this, so IDs of deleted accounts are not reused.
+    pub fn with_next_subresource_id(mut self, next: u32) -> Self {
+        self.next_subresource_id = next;
+        self
+    }
+
+    pub fn next_subresource_id(&self) -> u32 {
+        self.next_subresource_id
+    }
+
     pub fn contains(&self, identity: &Identity) -> bool {
         self.get(identity).is_some()
     }
@@ -113,11 +198,32 @@ impl AccountMap {
     }
 
     pub fn insert(&mut self, account: Account) -> Result<(Identity, Option<Account>), ManyError> {
-        let subid = self.inner.keys().last().map_or(0, |x| x + 1);
+        let subid = self
+            .inner
+            .keys()
+            .last()
+            .map_or(0, |x| x + 1)
+            .max(self.next_subresource_id);
         let id = self.id.with_subresource_id(subid)?;
+        self.next_subresource_id = subid + 1;
         Ok((id, self.inner.insert(subid, account)))
     }
 
+    /// Insert an account under a specific identity, e.g. when loading accounts
+    /// from a storage.
+    pub fn insert_at(
+        &mut self,
+        identity: &Identity,
+        account: Account,
+    ) -> Result<Option<Account>, ManyError> {
+        let subid = match identity.subresource_id() {
+            Some(subid) if identity.matches(&self.id) => subid,
+            _ => return Err(errors::unknown_account(identity)),
+        };
+        self.next_subresource_id = self.next_subresource_id.max(subid + 1);
+        Ok(self.inner.insert(subid, account))
+    }
+
     pub fn remove(&mut self, identity: &Identity) -> Option<Account> {
         if identity.matches(&self.id) {
             if let Some(subid) = identity.subresource_id() {
@@ -127,6 +233,54 @@ impl AccountMap {
         None
     }
 
+    /// Disable an account, providing a reason or not.
+    pub fn disable(
+        &mut self,
+        identity: &Identity,
+        reason: Option<Reason<u64>>,
+    ) -> Result<(), ManyError> {
+        self.get_mut(identity)
+            .ok_or_else(|| errors::unknown_account(identity))?
+            .disable(reason);
+        Ok(())
+    }
+
+    /// Re-enable a disabled account.
+    pub fn enable(&mut self, identity: &Identity) -> Result<(), ManyError> {
+        let account = self
+            .get_mut(identity)
+            .ok_or_else(|| errors::unknown_account(identity))?;
+        if !account.is_disabled() {
+            return Err(errors::account_not_disabled(identity));
+        }
+        account.enable();
+        Ok(())
+    }
+
+    /// Permanently delete a disabled account. `balances` are the ledger balances
+    /// of the account, which must all be zero. The subresource ID of the account
+    /// is not reused.
+    pub fn delete<'a>(
+        &mut self,
+        identity: &Identity,
+        balances: impl IntoIterator<Item = (&'a Symbol, &'a TokenAmount)>,
+    ) -> Result<Account, ManyError> {
+        let account = self
+            .get(identity)
+            .ok_or_else(|| errors::unknown_account(identity))?;
+        if !account.is_disabled() {
+            return Err(errors::account_not_disabled(identity));
+        }
+        if let Some((symbol, _)) = balances.into_iter().find(|(_, amount)| !amount.is_zero()) {
+            return Err(errors::account_has_balance(symbol));
+        }
+
+        self.remove(identity)
+            .ok_or_else(|| errors::unknown_account(identity))
+    }
+
+    /// Whether an ID has a role in an account that did not expire. See
+    /// [Account::has_role].
     pub fn has_role(&self, account: &Identity, id: &Identity, role: Role) -> bool {
         if let Some(account) = self.get(account) {
             account.has_role(id, role)
@@ -135,6 +289,22 @@ impl AccountMap {
         }
     }
 
+    /// Whether an ID has a role in an account that did not expire at a
+    /// specific time.
+    pub fn has_role_at(
+        &self,
+        account: &Identity,
+        id: &Identity,
+        role: Role,
+        now: Timestamp,
+    ) -> bool {
+        if let Some(account) = self.get(account) {
+            account.has_role_at(id, role, now)
+        } else {
+            false
+        }
+    }
+
     pub fn needs_role(
         &self,
         account: &Identity,
@@ -148,6 +318,20 @@ impl AccountMap {
         }
     }
 
+    pub fn needs_role_at(
+        &self,
+        account: &Identity,
+        id: &Identity,
+        role: impl IntoIterator<Item = Role>,
+        now: Timestamp,
+    ) -> Result<(), ManyError> {
+        if let Some(account) = self.get(account) {
+            account.needs_role_at(id, role, now)
+        } else {
+            Err(errors::unknown_account(account))
+        }
+    }
+
     pub fn iter(&self) -> AccountMapIterator {
         AccountMapIterator(self.id, self.inner.iter())
     }
@@ -168,6 +352,10 @@ pub struct Account {
 
     #[n(3)]
     pub disabled: Option<Either<bool, Reason<u64>>>,
+
+    /// When time-bounded roles expire. Roles without an expiration are permanent.
+    #[n(4)]
+    pub role_expirations: Option<BTreeMap<Identity, BTreeMap<Role, Timestamp>>>,
 }
 
 impl Account {
@@ -187,6 +375,7 @@ impl Account {
             roles,
             features,
             disabled: None,
+            role_expirations: None,
         }
     }
 
@@ -198,6 +387,15 @@ impl Account {
         })
     }
 
+    /// Re-enable a disabled account.
+    pub fn enable(&mut self) {
+        self.disabled = None;
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        !matches!(self.disabled, None | Some(Either::Left(false)))
+    }
+
     pub fn set_description(&mut self, desc: Option<impl ToString>) {
         self.description = desc.map(|d| d.to_string());
     }
@@ -209,30 +407,51 @@ impl Account {
         &self.roles
     }
 
+    /// Whether an ID has a role that did not expire at the current time. Use
+    /// [Account::has_role_at] with the block time where determinism matters.
     pub fn has_role<R: TryInto<Role>>(&self, id: &Identity, role: R) -> bool {
+        self.has_role_at(id, role, Timestamp::now())
+    }
+
+    /// Whether an ID has a role that did not expire at a specific time.
+    pub fn has_role_at<R: TryInto<Role>>(&self, id: &Identity, role: R, now: Timestamp) -> bool {
         let role: Role = if let Ok(r) = role.try_into() {
             r
         } else {
             return false;
         };
         self.roles.get(id).map_or(false, |v| v.contains(&role))
+            && self.role_expiration(id, &role).map_or(true, |e| now < e)
     }
 
-    /// Verify that an ID has the proper role, or return an
-    pub fn needs_role<R: TryInto<Role> + std::fmt::Display + Copy>(
+    /// Verify that an ID has the proper role that did not expire at the current
+    /// time, or return an error. Use [Account::needs_role_at] with the block
+    /// time where determinism matters.
+    pub fn needs_role<R: TryInto<Role> + std::fmt::Display + Clone>(
         &self,
         id: &Identity,
         role: impl IntoIterator<Item = R>,
+    ) -> Result<(), ManyError> {
+        self.needs_role_at(id, role, Timestamp::now())
+    }
+
+    /// Same as [Account::needs_role], checking the role expirations at a
+    /// specific time.
+    pub fn needs_role_at<R: TryInto<Role> + std::fmt::Display + Clone>(
+        &self,
+        id: &Identity,
+        role: impl IntoIterator<Item = R>,
+        now: Timestamp,
     ) -> Result<(), ManyError> {
         let mut first = None;
         for role in role {
-            let cp = role;
+            let cp = role.clone();
             match role.try_into() {
                 Ok(r) => {
-                    first.get_or_insert(r);
-                    if self.has_role(id, r) {
+                    if self.has_role_at(id, r.clone(), now) {
                         return Ok(());
                     }
+                    first.get_or_insert(r);
                 }
                 Err(_) => return Err(errors::unknown_role(cp)),
             }
@@ -240,14 +459,73 @@ impl Account {
         Err(errors::user_needs_role(first.unwrap_or(Role::Owner)))
     }
 
+    /// Add a permanent role. If the ID already had the role with an expiration,
+    /// the role becomes permanent.
     pub fn add_role<R: Into<Role>>(&mut self, id: &Identity, role: R) -> bool {
-        self.roles.entry(*id).or_default().insert(role.into())
+        let role = role.into();
+        self.clear_role_expiration(id, &role);
+        self.roles.entry(*id).or_default().insert(role)
+    }
+
+    /// Add a role that expires at a specific time.
+    pub fn add_role_until<R: Into<Role>>(
+        &mut self,
+        id: &Identity,
+        role: R,
+        expiration: Timestamp,
+    ) -> bool {
+        let role = role.into();
+        self.role_expirations
+            .get_or_insert_with(BTreeMap::new)
+            .entry(*id)
+            .or_default()
+            .insert(role.clone(), expiration);
+        self.roles.entry(*id).or_default().insert(role)
     }
 
     pub fn remove_role<R: Into<Role>>(&mut self, id: &Identity, role: R) -> bool {
-        self.roles
-            .get_mut(id)
-            .map_or(false, |v| v.remove(&role.into()))
+        let role = role.into();
+        self.clear_role_expiration(id, &role);
+        self.roles.get_mut(id).map_or(false, |v| v.remove(&role))
+    }
+
+    /// The time at which a role of an ID expires, if it was given with an
+    /// expiration.
+    pub fn role_expiration(&self, id: &Identity, role: &Role) -> Option<Timestamp> {
+        self.role_expirations.as_ref()?.get(id)?.get(role).copied()
+    }
+
+    /// Remove the roles that expired at a specific time, returning them.
+    pub fn remove_expired_roles(&mut self, now: Timestamp) -> BTreeMap<Identity, BTreeSet<Role>> {
+        let mut expired: BTreeMap<Identity, BTreeSet<Role>> = BTreeMap::new();
+        for (id, roles) in self.role_expirations.iter().flatten() {
+            for (role, expiration) in roles {
+                if *expiration <= now {
+                    expired.entry(*id).or_default().insert(role.clone());
+                }
+            }
+        }
+
+        for (id, roles) in &expired {
+            for role in roles {
+                self.remove_role(id, role.clone());
+            }
+        }
+        expired
+    }
+
+    fn clear_role_expiration(&mut self, id: &Identity, role: &Role) {
+        if let Some(expirations) = &mut self.role_expirations {
+            if let Some(roles) = expirations.get_mut(id) {
+                roles.remove(role);
+                if roles.is_empty() {
+                    expirations.remove(id);
+                }
+            }
+            if expirations.is_empty() {
+                self.role_expirations = None;
+            }
+        }
     }
 
     pub fn get_roles(&self, id: &Identity) -> BTreeSet<Role> {
@@ -330,6 +608,10 @@ pub struct AddRolesArgs {
 
     #[n(1)]
     pub roles: BTreeMap<Identity, BTreeSet<Role>>,
+
+    /// When the roles expire. They are permanent if None.
+    #[n(2)]
+    pub expiration: Option<Timestamp>,
 }
 
 pub type AddRolesReturn = EmptyReturn;
@@ -367,6 +649,9 @@ pub struct InfoReturn {
 
     #[n(3)]
     pub disabled: Option<Either<bool, Reason<u64>>>,
+
+    #[n(4)]
+    pub role_expirations: Option<BTreeMap<Identity, BTreeMap<Role, Timestamp>>>,
 }
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
@@ -378,6 +663,24 @@ pub struct DisableArgs {
 
 pub type DisableReturn = EmptyReturn;
 
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct EnableArgs {
+    #[n(0)]
+    pub account: Identity,
+}
+
+pub type EnableReturn = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct DeleteArgs {
+    #[n(0)]
+    pub account: Identity,
+}
+
+pub type DeleteReturn = EmptyReturn;
+
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
 #[cbor(map)]
 pub struct AddFeaturesArgs {
@@ -438,6 +741,13 @@ pub trait AccountModuleBackend: Send {
     fn disable(&mut self, sender: &Identity, args: DisableArgs)
         -> Result<DisableReturn, ManyError>;
 
+    /// Re-enable a disabled account. The sender must be an owner, or this must be
+    /// executed through multisig.
+    fn enable(&mut self, sender: &Identity, args: EnableArgs) -> Result<EnableReturn, ManyError>;
+
+    /// Permanently delete a disabled account. All its ledger balances must be zero.
+    fn delete(&mut self, sender: &Identity, args: DeleteArgs) -> Result<DeleteReturn, ManyError>;
+
     /// Add additional features to an account.
     fn add_features(
         &mut self,
@@ -491,6 +801,7 @@ mod module_tests {
                     roles: account.roles.clone(),
                     features: account.features.clone(),
                     disabled: None,
+                    role_expirations: account.role_expirations.clone(),
                 })
             }
         });
@@ -658,3 +969,105 @@ fn needs_role() {
         .needs_role(&Identity::public_key_raw([1; 28]), [Role::Owner])
         .is_err());
 }
+
+#[test]
+fn custom_roles() {
+    use std::str::FromStr;
+    let role = Role::from_str("kvstore:canWrite").unwrap();
+    assert_eq!(role, Role::Custom("kvstore:canWrite".to_string()));
+    assert!(role.is_custom());
+    assert_eq!(
+        minicbor::decode::<Role>(&minicbor::to_vec(&role).unwrap()).unwrap(),
+        role
+    );
+
+    assert!(Role::from_str("unknownRole").is_err());
+    assert!(Role::custom("kvstore:").is_err());
+    assert!(Role::custom(":canWrite").is_err());
+    assert!(Role::custom("kvstore:can write").is_err());
+}
+
+#[test]
+fn role_expiry() {
+    let owner = Identity::public_key_raw([0; 28]);
+    let id = Identity::public_key_raw([1; 28]);
+    let mut account = Account::create(
+        &owner,
+        CreateArgs {
+            description: None,
+            roles: None,
+            features: Default::default(),
+        },
+    );
+    let role = Role::custom("kvstore:canWrite").unwrap();
+    account.add_role_until(&id, role.clone(), Timestamp::new(1000).unwrap());
+
+    assert!(account.has_role_at(&id, role.clone(), Timestamp::new(999).unwrap()));
+    assert!(!account.has_role_at(&id, role.clone(), Timestamp::new(1000).unwrap()));
+    assert!(account
+        .needs_role_at(&id, [role.clone()], Timestamp::new(1000).unwrap())
+        .is_err());
+    // Without a time, expirations are checked against the current time.
+    assert!(!account.has_role(&id, role.clone()));
+    assert!(account.needs_role(&id, [role.clone()]).is_err());
+
+    assert!(account
+        .remove_expired_roles(Timestamp::new(999).unwrap())
+        .is_empty());
+    assert_eq!(
+        account.remove_expired_roles(Timestamp::new(1000).unwrap()),
+        BTreeMap::from([(id, BTreeSet::from([role.clone()]))])
+    );
+    assert!(!account.has_role_at(&id, role.clone(), Timestamp::new(0).unwrap()));
+    assert_eq!(account.role_expirations, None);
+
+    // Granting a role again without expiration makes it permanent.
+    account.add_role_until(&id, Role::CanLedgerTransact, Timestamp::new(1000).unwrap());
+    account.add_role(&id, Role::CanLedgerTransact);
+    assert!(account.has_role_at(&id, Role::CanLedgerTransact, Timestamp::new(2000).unwrap()));
+}
+
+#[test]
+fn enable_delete() {
+    let owner = Identity::public_key_raw([1; 28]);
+    let symbol = Identity::public_key_raw([2; 28]);
+    let mut map = AccountMap::new(Identity::public_key_raw([0; 28]));
+    let create = || {
+        Account::create(
+            &owner,
+            CreateArgs {
+                description: None,
+                roles: None,
+                features: Default::default(),
+            },
+        )
+    };
+    let (first, _) = map.insert(create()).unwrap();
+    let (second, _) = map.insert(create()).unwrap();
+
+    assert_eq!(map.enable(&second), Err(errors::account_not_disabled(second)));
+    assert_eq!(
+        map.delete(&second, []).unwrap_err(),
+        errors::account_not_disabled(second)
+    );
+
+    map.disable(&first, None).unwrap();
+    assert!(map.get(&first).unwrap().is_disabled());
+    map.enable(&first).unwrap();
+    assert!(!map.get(&first).unwrap().is_disabled());
+
+    map.disable(&second, None).unwrap();
+    let balance = TokenAmount::from(10u64);
+    let zero = TokenAmount::zero();
+    assert_eq!(
+        map.delete(&second, [(&symbol, &balance)]).unwrap_err(),
+        errors::account_has_balance(symbol)
+    );
+    assert!(map.delete(&second, [(&symbol, &zero)]).is_ok());
+    assert!(!map.contains(&second));
+
+    // The subresource ID of a deleted account is not reused.
+    let (third, _) = map.insert(create()).unwrap();
+    assert_eq!(third.subresource_id(), Some(2));
+    assert_eq!(map.next_subresource_id(), 3);
+}
@@ -6,5 +6,8 @@ define_attribute_many_error!(
         2: pub fn unknown_role(role) => "Account does not know role '{role}'.",
         3: pub fn user_needs_role(role) => "Sender needs role '{role}' to perform this operation.",
         4: pub fn account_must_own_itself() => "Unable to remove owner role from the account itself.",
+        5: pub fn account_not_disabled(id) => "Account {id} is not disabled.",
+        6: pub fn account_has_balance(symbol) => "Account has a non-zero balance of {symbol}.",
+        7: pub fn account_disabled(id) => "Account {id} is disabled.",
     }
 );
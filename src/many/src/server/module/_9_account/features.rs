@@ -6,6 +6,8 @@ use minicbor::{Decode, Encode};
 use std::collections::BTreeSet;
 
 pub mod multisig;
+pub mod recovery;
+pub mod spending_limits;
 
 /// See feature `_0_account_ledger`.
 pub mod ledger {
@@ -37,6 +39,12 @@ pub mod ledger {
 
 pub type FeatureId = u32;
 
+/// A positive integer in a feature argument. Arguments hold signed integers, so
+/// values over `i64::MAX` are saturated rather than wrapped.
+fn positive_int(value: u64) -> CborAny {
+    CborAny::Int(i64::try_from(value).unwrap_or(i64::MAX))
+}
+
 /// An Account Feature.
 #[derive(Encode, Decode, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 #[repr(transparent)]
@@ -156,6 +164,9 @@ pub trait TryCreateFeature: Sized {
 
 pub trait FeatureInfo {
     fn as_feature(&self) -> Feature;
+
+    /// The roles used by this feature. Features can declare application-defined
+    /// roles here (see [Role::custom]).
     fn roles() -> BTreeSet<Role>;
 }
 
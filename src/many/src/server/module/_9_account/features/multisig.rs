@@ -4,9 +4,9 @@ use crate::server::module::account::features::{Feature, FeatureId, TryCreateFeat
 use crate::server::module::account::Role;
 use crate::server::module::ledger::SendArgs;
 use crate::server::module::EmptyReturn;
-use crate::types::events::AccountMultisigTransaction;
+use crate::types::events::{AccountMultisigTransaction, EventKind};
 use crate::types::ledger::TokenAmount;
-use crate::types::Timestamp;
+use crate::types::{SortOrder, Timestamp, VecOrSingle};
 use crate::{Identity, ManyError};
 use many_macros::many_module;
 use minicbor::bytes::ByteVec;
@@ -14,6 +14,9 @@ use minicbor::data::Type;
 use minicbor::{encode, Decode, Decoder, Encode, Encoder};
 use std::collections::{BTreeMap, BTreeSet};
 
+mod engine;
+pub use engine::*;
+
 pub mod errors {
     use crate::define_attribute_many_error;
     define_attribute_many_error!(
@@ -23,6 +26,7 @@ pub mod errors {
             102: pub fn transaction_type_unsupported() => "This transaction is not supported.",
             103: pub fn cannot_execute_transaction() => "This transaction cannot be executed yet.",
             104: pub fn transaction_expired_or_withdrawn() => "This transaction expired or was withdrawn.",
+            105: pub fn method_not_allowed(method) => "Method '{method}' cannot be called by this account.",
         }
     );
 }
@@ -38,6 +42,51 @@ pub struct MultisigAccountFeatureArg {
 
     #[n(2)]
     pub execute_automatically: Option<bool>,
+
+    /// The weight of the approval of each identity. Identities not listed have
+    /// a weight of 1.
+    #[n(3)]
+    pub weights: Option<BTreeMap<Identity, u64>>,
+
+    /// Thresholds for specific kinds of transactions, replacing `threshold`.
+    #[n(4)]
+    pub kind_thresholds: Option<BTreeMap<EventKind, u64>>,
+
+    /// The methods that can be submitted as generic calls. No method can be
+    /// submitted if None.
+    #[n(5)]
+    pub allowed_methods: Option<BTreeSet<String>>,
+}
+
+impl MultisigAccountFeatureArg {
+    /// The threshold of a kind of transaction, if the account defines one.
+    pub fn threshold_for(&self, kind: EventKind) -> Option<u64> {
+        self.kind_threshold(kind).or(self.threshold)
+    }
+
+    /// The threshold set for a specific kind of transaction. Transactions of
+    /// this kind cannot be submitted with a lower threshold.
+    pub fn kind_threshold(&self, kind: EventKind) -> Option<u64> {
+        self.kind_thresholds
+            .as_ref()
+            .and_then(|t| t.get(&kind).copied())
+    }
+
+    /// The weight of the approval of an identity.
+    pub fn weight_of(&self, id: &Identity) -> u64 {
+        self.weights
+            .as_ref()
+            .and_then(|w| w.get(id).copied())
+            .unwrap_or(1)
+    }
+
+    /// Whether a method can be submitted as a generic call. Methods must be
+    /// allowed explicitly.
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .as_ref()
+            .map_or(false, |m| m.contains(method))
+    }
 }
 
 #[derive(Default)]
@@ -55,9 +104,36 @@ impl MultisigAccountFeature {
             threshold,
             timeout_in_secs,
             execute_automatically,
+            weights: None,
+            kind_thresholds: None,
+            allowed_methods: None,
         })
     }
 
+    pub fn with_weight(mut self, id: Identity, weight: u64) -> Self {
+        self.arg
+            .weights
+            .get_or_insert_with(BTreeMap::new)
+            .insert(id, weight);
+        self
+    }
+
+    pub fn with_kind_threshold(mut self, kind: EventKind, threshold: u64) -> Self {
+        self.arg
+            .kind_thresholds
+            .get_or_insert_with(BTreeMap::new)
+            .insert(kind, threshold);
+        self
+    }
+
+    pub fn with_allowed_method(mut self, method: impl ToString) -> Self {
+        self.arg
+            .allowed_methods
+            .get_or_insert_with(BTreeSet::new)
+            .insert(method.to_string());
+        self
+    }
+
     pub fn from_arg(arg: MultisigAccountFeatureArg) -> Self {
         Self { arg }
     }
@@ -86,12 +162,42 @@ impl TryCreateFeature for MultisigAccountFeature {
                     CborAny::Bool(x) => Some(*x),
                     _ => None,
                 });
+                // Weights and kind thresholds were added later and are optional.
+                let weights = m
+                    .get(&CborAny::Int(3))
+                    .map(|v| {
+                        decode_map(v, |k| match k {
+                            CborAny::Bytes(b) => Identity::from_bytes(b).ok(),
+                            _ => None,
+                        })
+                    })
+                    .transpose()?;
+                let kind_thresholds = m
+                    .get(&CborAny::Int(4))
+                    .map(|v| decode_map(v, kind_from_cbor))
+                    .transpose()?;
+                let allowed_methods = m
+                    .get(&CborAny::Int(5))
+                    .map(|v| match v {
+                        CborAny::Array(a) => a
+                            .iter()
+                            .map(|m| match m {
+                                CborAny::String(m) => Ok(m.clone()),
+                                _ => Err(ManyError::invalid_attribute_arguments()),
+                            })
+                            .collect(),
+                        _ => Err(ManyError::invalid_attribute_arguments()),
+                    })
+                    .transpose()?;
 
                 Ok(Self {
                     arg: MultisigAccountFeatureArg {
                         threshold,
                         timeout_in_secs,
                         execute_automatically,
+                        weights,
+                        kind_thresholds,
+                        allowed_methods,
                     },
                 })
             }
@@ -104,14 +210,48 @@ impl super::FeatureInfo for MultisigAccountFeature {
     fn as_feature(&self) -> Feature {
         let mut map = BTreeMap::<CborAny, CborAny>::new();
         if let Some(threshold) = self.arg.threshold {
-            map.insert(CborAny::Int(0), CborAny::Int(threshold as i64));
+            map.insert(CborAny::Int(0), super::positive_int(threshold));
         }
         if let Some(timeout_in_secs) = self.arg.timeout_in_secs {
-            map.insert(CborAny::Int(1), CborAny::Int(timeout_in_secs as i64));
+            map.insert(CborAny::Int(1), super::positive_int(timeout_in_secs));
         }
         if let Some(execute_automatically) = self.arg.execute_automatically {
             map.insert(CborAny::Int(2), CborAny::Bool(execute_automatically));
         }
+        if let Some(weights) = &self.arg.weights {
+            map.insert(
+                CborAny::Int(3),
+                CborAny::Map(
+                    weights
+                        .iter()
+                        .map(|(id, w)| (CborAny::Bytes(id.to_vec()), super::positive_int(*w)))
+                        .collect(),
+                ),
+            );
+        }
+        if let Some(kind_thresholds) = &self.arg.kind_thresholds {
+            map.insert(
+                CborAny::Int(4),
+                CborAny::Map(
+                    kind_thresholds
+                        .iter()
+                        .map(|(k, t)| (CborAny::String(k.to_string()), super::positive_int(*t)))
+                        .collect(),
+                ),
+            );
+        }
+        if let Some(allowed_methods) = &self.arg.allowed_methods {
+            map.insert(
+                CborAny::Int(5),
+                CborAny::Array(
+                    allowed_methods
+                        .iter()
+                        .cloned()
+                        .map(CborAny::String)
+                        .collect(),
+                ),
+            );
+        }
 
         Feature::with_id(Self::ID).with_argument(CborAny::Map(map))
     }
@@ -121,6 +261,33 @@ impl super::FeatureInfo for MultisigAccountFeature {
     }
 }
 
+/// Decode a map of positive integers in a feature argument.
+fn decode_map<K: Ord>(
+    value: &CborAny,
+    key: impl Fn(&CborAny) -> Option<K>,
+) -> Result<BTreeMap<K, u64>, ManyError> {
+    match value {
+        CborAny::Map(m) => m
+            .iter()
+            .map(|(k, v)| match (key(k), v) {
+                (Some(k), CborAny::Int(v)) => u64::try_from(*v)
+                    .map(|v| (k, v))
+                    .map_err(|_| ManyError::invalid_attribute_arguments()),
+                _ => Err(ManyError::invalid_attribute_arguments()),
+            })
+            .collect(),
+        _ => Err(ManyError::invalid_attribute_arguments()),
+    }
+}
+
+/// Event kinds are stored in feature arguments by name, e.g. `account-add-roles`.
+fn kind_from_cbor(value: &CborAny) -> Option<EventKind> {
+    match value {
+        CborAny::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
 const MULTISIG_MEMO_DATA_MAX_SIZE: usize = 4000; //4kB
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
@@ -205,6 +372,36 @@ fn decode_data<C>(d: &mut minicbor::Decoder, _: &mut C) -> Result<Option<ByteVec
     }
 }
 
+/// Weights and thresholds decoder. Check that the values fit in the signed
+/// integers of feature arguments.
+fn decode_positive_map<'b, C, K: Ord + Decode<'b, C>>(
+    d: &mut Decoder<'b>,
+    ctx: &mut C,
+) -> Result<Option<BTreeMap<K, u64>>, minicbor::decode::Error> {
+    let map = Option::<BTreeMap<K, u64>>::decode(d, ctx)?;
+    if map
+        .iter()
+        .flat_map(|m| m.values())
+        .any(|v| i64::try_from(*v).is_err())
+    {
+        return Err(minicbor::decode::Error::message("Value out of range"));
+    }
+    Ok(map)
+}
+
+/// A generic transaction, calling a method of the server as the account. The
+/// method must be allowed by the multisig feature of the account.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct CallArgs {
+    #[n(0)]
+    pub method: String,
+
+    /// The CBOR encoded argument of the method.
+    #[n(1)]
+    pub argument: ByteVec,
+}
+
 #[derive(Clone, Debug, Encode, Decode)]
 #[cbor(map)]
 pub struct SubmitTransactionReturn {
@@ -291,6 +488,10 @@ pub struct InfoReturn {
 
     #[n(8)]
     pub state: MultisigTransactionState,
+
+    /// The weight of the approvers, if they are not all 1.
+    #[n(9)]
+    pub weights: Option<BTreeMap<Identity, u64>>,
 }
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq)]
@@ -307,6 +508,14 @@ pub struct SetDefaultsArgs {
 
     #[n(3)]
     pub execute_automatically: Option<bool>,
+
+    #[n(4)]
+    #[cbor(decode_with = "decode_positive_map")]
+    pub weights: Option<BTreeMap<Identity, u64>>,
+
+    #[n(5)]
+    #[cbor(decode_with = "decode_positive_map")]
+    pub kind_thresholds: Option<BTreeMap<EventKind, u64>>,
 }
 
 pub type SetDefaultsReturn = EmptyReturn;
@@ -345,6 +554,81 @@ pub struct WithdrawArgs {
 
 pub type WithdrawReturn = EmptyReturn;
 
+/// Maximum number of transactions returned by a single `account.multisigList` call.
+pub const MULTISIG_LIST_MAX_COUNT: u64 = 100;
+
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ListArgs {
+    #[n(0)]
+    pub account: Option<Identity>,
+
+    #[n(1)]
+    pub state: Option<VecOrSingle<MultisigTransactionState>>,
+
+    #[n(2)]
+    pub submitter: Option<Identity>,
+
+    /// Only list the pending transactions that the sender can approve but did
+    /// not approve yet.
+    #[n(3)]
+    pub awaiting_approval: Option<bool>,
+
+    #[n(4)]
+    pub count: Option<u64>,
+
+    #[n(5)]
+    pub order: Option<SortOrder>,
+
+    /// A cursor returned by a previous call, to continue listing from there.
+    #[n(6)]
+    pub cursor: Option<ByteVec>,
+}
+
+impl ListArgs {
+    /// Whether a transaction of an account matches the filters of this query.
+    pub fn matches(&self, sender: &Identity, account: &Identity, info: &InfoReturn) -> bool {
+        self.account.as_ref().map_or(true, |a| a == account)
+            && self
+                .state
+                .as_ref()
+                .map_or(true, |s| s.iter().any(|s| *s == info.state))
+            && self.submitter.as_ref().map_or(true, |s| *s == info.submitter)
+            && (self.awaiting_approval != Some(true)
+                || (info.state == MultisigTransactionState::Pending
+                    && info.approvers.get(sender).map_or(false, |a| !a.approved)))
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+#[cbor(map)]
+pub struct ListItem {
+    #[n(0)]
+    pub token: ByteVec,
+
+    #[n(1)]
+    pub account: Identity,
+
+    #[n(2)]
+    pub info: InfoReturn,
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+#[cbor(map)]
+pub struct ListReturn {
+    /// The number of transactions matching the filters.
+    #[n(0)]
+    pub nb_transactions: u64,
+
+    #[n(1)]
+    pub transactions: Vec<ListItem>,
+
+    /// Opaque cursor to pass to the next call if there are more transactions.
+    /// None if this is the last page.
+    #[n(2)]
+    pub cursor: Option<ByteVec>,
+}
+
 #[many_module(name = AccountMultisigModule, namespace = account, many_crate = crate)]
 pub trait AccountMultisigModuleBackend: Send {
     fn multisig_submit_transaction(
@@ -353,6 +637,7 @@ pub trait AccountMultisigModuleBackend: Send {
         args: SubmitTransactionArgs,
     ) -> Result<SubmitTransactionReturn, ManyError>;
     fn multisig_info(&self, sender: &Identity, args: InfoArgs) -> Result<InfoReturn, ManyError>;
+    fn multisig_list(&self, sender: &Identity, args: ListArgs) -> Result<ListReturn, ManyError>;
     fn multisig_set_defaults(
         &mut self,
         sender: &Identity,
@@ -382,11 +667,90 @@ pub trait AccountMultisigModuleBackend: Send {
 
 #[cfg(test)]
 mod tests {
-    use super::SubmitTransactionArgs;
+    use super::{MultisigAccountFeature, SetDefaultsArgs, SubmitTransactionArgs};
     use crate::{
-        server::module::account::{features::multisig::MULTISIG_MEMO_DATA_MAX_SIZE, DisableArgs},
-        types::{events::AccountMultisigTransaction, identity::testing::identity},
+        cbor::CborAny,
+        server::module::account::{
+            features::{
+                multisig::MULTISIG_MEMO_DATA_MAX_SIZE, Feature, FeatureInfo, TryCreateFeature,
+            },
+            DisableArgs,
+        },
+        types::{
+            events::{AccountMultisigTransaction, EventKind},
+            identity::testing::identity,
+        },
     };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn feature_arg() {
+        let feature = MultisigAccountFeature::create(Some(2), None, Some(true))
+            .with_weight(identity(1), 3)
+            .with_kind_threshold(EventKind::AccountAddRoles, 4);
+        let arg = MultisigAccountFeature::try_create(&feature.as_feature())
+            .unwrap()
+            .arg;
+        assert_eq!(arg.weight_of(&identity(1)), 3);
+        assert_eq!(arg.weight_of(&identity(2)), 1);
+        assert_eq!(arg.threshold_for(EventKind::AccountAddRoles), Some(4));
+        assert_eq!(arg.threshold_for(EventKind::Send), Some(2));
+
+        // Arguments without weights and kind thresholds are still valid.
+        let arg = MultisigAccountFeature::try_create(&Feature::with_id(1).with_argument(
+            CborAny::Map(BTreeMap::from([(CborAny::Int(0), CborAny::Int(2))])),
+        ))
+        .unwrap()
+        .arg;
+        assert_eq!(arg.threshold_for(EventKind::AccountAddRoles), Some(2));
+        assert!(arg.weights.is_none());
+
+        let negative_weight = CborAny::Map(BTreeMap::from([(
+            CborAny::Bytes(identity(1).to_vec()),
+            CborAny::Int(-1),
+        )]));
+        let feature = Feature::with_id(1).with_argument(CborAny::Map(BTreeMap::from([(
+            CborAny::Int(3),
+            negative_weight,
+        )])));
+        assert!(MultisigAccountFeature::try_create(&feature).is_err());
+
+        // Values that do not fit in an argument are saturated.
+        let feature = MultisigAccountFeature::create(Some(u64::MAX), None, None)
+            .with_weight(identity(1), u64::MAX)
+            .with_kind_threshold(EventKind::AccountAddRoles, u64::MAX);
+        let arg = MultisigAccountFeature::try_create(&feature.as_feature())
+            .unwrap()
+            .arg;
+        assert_eq!(arg.weight_of(&identity(1)), i64::MAX as u64);
+        assert_eq!(
+            arg.threshold_for(EventKind::AccountAddRoles),
+            Some(i64::MAX as u64)
+        );
+    }
+
+    #[test]
+    fn set_defaults_values() {
+        let mut args = SetDefaultsArgs {
+            account: identity(1),
+            threshold: None,
+            timeout_in_secs: None,
+            execute_automatically: None,
+            weights: Some(BTreeMap::from([(identity(2), i64::MAX as u64)])),
+            kind_thresholds: Some(BTreeMap::from([(EventKind::Send, i64::MAX as u64)])),
+        };
+        let enc = minicbor::to_vec(&args).unwrap();
+        assert_eq!(minicbor::decode::<SetDefaultsArgs>(&enc).unwrap(), args);
+
+        args.weights = Some(BTreeMap::from([(identity(2), u64::MAX)]));
+        let enc = minicbor::to_vec(&args).unwrap();
+        assert!(minicbor::decode::<SetDefaultsArgs>(&enc).is_err());
+
+        args.weights = None;
+        args.kind_thresholds = Some(BTreeMap::from([(EventKind::Send, i64::MAX as u64 + 1)]));
+        let enc = minicbor::to_vec(&args).unwrap();
+        assert!(minicbor::decode::<SetDefaultsArgs>(&enc).is_err());
+    }
 
     #[test]
     fn memo_size() {
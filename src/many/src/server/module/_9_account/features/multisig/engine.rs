@@ -0,0 +1,1498 @@
+use super::errors;
+use super::*;
+use crate::message::RequestMessage;
+use crate::server::module::account::features::FeatureInfo;
+use crate::server::module::account::Account;
+use crate::server::module::events::EventSink;
+use crate::server::ManyServer;
+use crate::types::events::EventInfo;
+use crate::types::SortOrder;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Threshold of a transaction when neither the transaction nor the account
+/// defines one.
+pub const MULTISIG_DEFAULT_THRESHOLD: u64 = 1;
+
+/// Timeout of a transaction when neither the transaction nor the account
+/// defines one.
+pub const MULTISIG_DEFAULT_TIMEOUT_IN_SECS: u64 = 60 * 60 * 24; // 1 day
+
+/// Maximum timeout of a transaction.
+pub const MULTISIG_MAXIMUM_TIMEOUT_IN_SECS: u64 = 185 * 60 * 60 * 24; // 185 days
+
+pub const MULTISIG_DEFAULT_EXECUTE_AUTOMATICALLY: bool = false;
+
+/// What a [MultisigEngine] needs from the rest of the server.
+pub trait MultisigExecutor: Send {
+    /// Get an account, to check roles and its multisig defaults.
+    fn account(&self, id: &Identity) -> Result<Account, ManyError>;
+
+    /// Replace the multisig feature of an account, for `account.multisigSetDefaults`.
+    fn set_defaults(
+        &mut self,
+        id: &Identity,
+        feature: MultisigAccountFeature,
+    ) -> Result<(), ManyError>;
+
+    /// Execute an approved transaction as the account, returning the CBOR
+    /// encoded result of the call.
+    fn execute(
+        &mut self,
+        account: &Identity,
+        transaction: &AccountMultisigTransaction,
+    ) -> Result<Vec<u8>, ManyError>;
+
+    /// Record an executed send that was over the spending limits of `spender`,
+    /// submitted with [MultisigEngine::submit_routed_send], e.g. in the
+    /// [SpendingHistory](crate::server::module::account::features::spending_limits::SpendingHistory)
+    /// of the account.
+    fn record_spending(
+        &mut self,
+        spender: &Identity,
+        send: &SendArgs,
+        time: Timestamp,
+    ) -> Result<(), ManyError>;
+}
+
+/// Dispatches generic calls ([CallArgs]) to the modules of a [ManyServer], as
+/// the account, for executors to use.
+///
+/// A call blocks until the module returns. The module called must not share
+/// its backend with the engine executing the transaction, or it deadlocks.
+#[derive(Clone)]
+pub struct ManyServerDispatcher {
+    server: Arc<Mutex<ManyServer>>,
+}
+
+impl ManyServerDispatcher {
+    pub fn new(server: Arc<Mutex<ManyServer>>) -> Self {
+        Self { server }
+    }
+
+    pub fn call(&self, account: &Identity, args: &CallArgs) -> Result<Vec<u8>, ManyError> {
+        let server = self.server.clone();
+        let message = RequestMessage {
+            version: Some(1),
+            from: Some(*account),
+            method: args.method.clone(),
+            data: args.argument.to_vec(),
+            ..Default::default()
+        };
+
+        // Modules are async and this might already run within a runtime, so
+        // the call runs on its own thread.
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| ManyError::unknown(e.to_string()))?
+                .block_on(ManyServer::execute_message(&server, message))
+        })
+        .join()
+        .map_err(|_| ManyError::unknown("The call panicked.".to_string()))??
+        .data
+    }
+}
+
+/// A multisig transaction tracked by a [MultisigEngine].
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct MultisigTransaction {
+    #[n(0)]
+    pub account: Identity,
+
+    #[n(1)]
+    pub memo: Option<String>,
+
+    #[n(2)]
+    pub transaction: AccountMultisigTransaction,
+
+    #[n(3)]
+    pub submitter: Identity,
+
+    #[n(4)]
+    pub approvers: BTreeMap<Identity, ApproverInfo>,
+
+    #[n(5)]
+    pub threshold: u64,
+
+    #[n(6)]
+    pub execute_automatically: bool,
+
+    #[n(7)]
+    pub timeout: Timestamp,
+
+    #[n(8)]
+    pub data: Option<ByteVec>,
+
+    #[n(9)]
+    pub state: MultisigTransactionState,
+
+    /// The weight of the approvers when the transaction was submitted, if they
+    /// are not all 1.
+    #[n(10)]
+    pub weights: Option<BTreeMap<Identity, u64>>,
+
+    /// The identity whose spending limits this send exceeded, if it was
+    /// submitted with [MultisigEngine::submit_routed_send].
+    #[n(11)]
+    pub spender: Option<Identity>,
+}
+
+impl MultisigTransaction {
+    pub fn weight_of(&self, id: &Identity) -> u64 {
+        self.weights
+            .as_ref()
+            .and_then(|w| w.get(id).copied())
+            .unwrap_or(1)
+    }
+
+    /// The sum of the weights of the approvers who approved the transaction.
+    pub fn approvals(&self) -> u64 {
+        self.approvers
+            .iter()
+            .filter(|(_, a)| a.approved)
+            .fold(0u64, |acc, (id, _)| acc.saturating_add(self.weight_of(id)))
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.approvals() >= self.threshold
+    }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.state == MultisigTransactionState::Pending && now >= self.timeout
+    }
+
+    /// The information returned by `account.multisigInfo`, at a specific time.
+    pub fn info(&self, now: Timestamp) -> InfoReturn {
+        InfoReturn {
+            memo: self.memo.clone(),
+            transaction: self.transaction.clone(),
+            submitter: self.submitter,
+            approvers: self.approvers.clone(),
+            threshold: self.threshold,
+            execute_automatically: self.execute_automatically,
+            timeout: self.timeout,
+            data: self.data.clone(),
+            state: if self.is_expired(now) {
+                MultisigTransactionState::Expired
+            } else {
+                self.state.clone()
+            },
+            weights: self.weights.clone(),
+        }
+    }
+}
+
+/// Persistence of the transactions of a [MultisigEngine].
+pub trait MultisigStorage: Send {
+    fn get(&self, token: &ByteVec) -> Result<Option<MultisigTransaction>, ManyError>;
+
+    /// Persist a transaction, submitted or modified.
+    fn put(&mut self, token: &ByteVec, transaction: &MultisigTransaction) -> Result<(), ManyError>;
+
+    /// All the transactions stored, sorted by token.
+    fn iter(
+        &self,
+    ) -> Box<dyn Iterator<Item = Result<(ByteVec, MultisigTransaction), ManyError>> + '_>;
+}
+
+/// A [MultisigStorage] that keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryMultisigStorage {
+    transactions: BTreeMap<ByteVec, MultisigTransaction>,
+}
+
+impl MultisigStorage for InMemoryMultisigStorage {
+    fn get(&self, token: &ByteVec) -> Result<Option<MultisigTransaction>, ManyError> {
+        Ok(self.transactions.get(token).cloned())
+    }
+
+    fn put(&mut self, token: &ByteVec, transaction: &MultisigTransaction) -> Result<(), ManyError> {
+        self.transactions.insert(token.clone(), transaction.clone());
+        Ok(())
+    }
+
+    fn iter(
+        &self,
+    ) -> Box<dyn Iterator<Item = Result<(ByteVec, MultisigTransaction), ManyError>> + '_> {
+        Box::new(
+            self.transactions
+                .iter()
+                .map(|(token, tx)| Ok((token.clone(), tx.clone()))),
+        )
+    }
+}
+
+/// A reusable multisig engine. It keeps track of approvals, computes thresholds
+/// and timeouts from the account defaults, expires transactions and executes
+/// approved transactions through a [MultisigExecutor]. It implements
+/// [AccountMultisigModuleBackend] directly.
+///
+/// The submitter of a transaction approves it. The owners of the account and
+/// the identities with the `canMultisigApprove` role can approve it.
+///
+/// A transaction whose execution fails stays pending, and the error is returned
+/// by the call that executed it, including approvals that execute it
+/// automatically.
+pub struct MultisigEngine<S: MultisigStorage, X: MultisigExecutor, E: EventSink> {
+    storage: S,
+    executor: X,
+    events: E,
+    next_token: u64,
+    /// The pending transactions, by timeout, so they can be expired without
+    /// going through all the transactions.
+    timeouts: BTreeSet<(Timestamp, ByteVec)>,
+    time: Option<Timestamp>,
+}
+
+impl<S: MultisigStorage, X: MultisigExecutor, E: EventSink> MultisigEngine<S, X, E> {
+    /// Create an engine from a storage, with the transactions already in it.
+    pub fn load(storage: S, executor: X, events: E) -> Result<Self, ManyError> {
+        let mut next_token = 0;
+        let mut timeouts = BTreeSet::new();
+        for entry in storage.iter() {
+            let (token, tx) = entry?;
+            // Tokens are big-endian u64s.
+            let n = token.iter().fold(0u64, |acc, b| {
+                acc.saturating_mul(256).saturating_add(u64::from(*b))
+            });
+            next_token = next_token.max(n.saturating_add(1));
+            if tx.state == MultisigTransactionState::Pending {
+                timeouts.insert((tx.timeout, token));
+            }
+        }
+
+        Ok(Self {
+            storage,
+            executor,
+            events,
+            next_token,
+            timeouts,
+            time: None,
+        })
+    }
+
+    /// Set the time used for timeouts and events, e.g. the time of the current
+    /// block. The system time is used if None.
+    pub fn set_time(&mut self, time: Option<Timestamp>) {
+        self.time = time;
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn executor(&self) -> &X {
+        &self.executor
+    }
+
+    pub fn executor_mut(&mut self) -> &mut X {
+        &mut self.executor
+    }
+
+    pub fn events(&self) -> &E {
+        &self.events
+    }
+
+    fn now(&self) -> Timestamp {
+        self.time.unwrap_or_else(Timestamp::now)
+    }
+
+    fn emit(&mut self, content: EventInfo) -> Result<(), ManyError> {
+        let now = self.now();
+        self.events.emit(now, content)
+    }
+
+    /// Expire the pending transactions whose timeout passed, emitting an
+    /// `AccountMultisigExpired` event for each. This is done before every
+    /// operation, and can also be called periodically (e.g. at every block).
+    /// Returns the tokens of the expired transactions.
+    pub fn expire(&mut self) -> Result<Vec<ByteVec>, ManyError> {
+        let now = self.now();
+        let mut expired = Vec::new();
+        while let Some((timeout, token)) = self.timeouts.iter().next().cloned() {
+            if timeout > now {
+                break;
+            }
+            self.timeouts.remove(&(timeout, token.clone()));
+            expired.push(token);
+        }
+
+        let mut tokens = Vec::with_capacity(expired.len());
+        for token in expired {
+            let mut tx = self.get(&token)?;
+            tx.state = MultisigTransactionState::Expired;
+            self.storage.put(&token, &tx)?;
+            self.emit(EventInfo::AccountMultisigExpired {
+                account: tx.account,
+                token: token.clone(),
+                time: now,
+            })?;
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn get(&self, token: &ByteVec) -> Result<MultisigTransaction, ManyError> {
+        self.storage
+            .get(token)?
+            .ok_or_else(errors::transaction_cannot_be_found)
+    }
+
+    /// Get a transaction that can still be approved or executed.
+    fn pending(&mut self, token: &ByteVec) -> Result<MultisigTransaction, ManyError> {
+        self.expire()?;
+        let tx = self.get(token)?;
+        if tx.state != MultisigTransactionState::Pending {
+            return Err(errors::transaction_expired_or_withdrawn());
+        }
+        Ok(tx)
+    }
+
+    /// Submit a send over the spending limits of the sender, as routed by
+    /// [route_send](crate::server::module::account::features::spending_limits::route_send).
+    /// The sender needs a role allowing it to send from the account rather than
+    /// to submit transactions, and only approves the send if it is an approver
+    /// of the account. The send is recorded with
+    /// [MultisigExecutor::record_spending] once executed.
+    pub fn submit_routed_send(
+        &mut self,
+        sender: &Identity,
+        args: SubmitTransactionArgs,
+    ) -> Result<SubmitTransactionReturn, ManyError> {
+        match args.transaction.as_ref() {
+            AccountMultisigTransaction::Send(send) if send.from == Some(args.account) => {}
+            _ => return Err(errors::transaction_type_unsupported()),
+        }
+        self.executor.account(&args.account)?.needs_role_at(
+            sender,
+            [Role::Owner, Role::CanLedgerTransact],
+            self.now(),
+        )?;
+        self.submit(sender, args, Some(*sender))
+    }
+
+    fn submit(
+        &mut self,
+        sender: &Identity,
+        args: SubmitTransactionArgs,
+        spender: Option<Identity>,
+    ) -> Result<SubmitTransactionReturn, ManyError> {
+        self.expire()?;
+        let now = self.now();
+        let account = self.executor.account(&args.account)?;
+        let defaults = feature(&account)?.arg;
+        if spender.is_none() {
+            account.needs_role_at(sender, [Role::Owner, Role::CanMultisigSubmit], now)?;
+        }
+        let kind = match args.transaction.as_ref() {
+            AccountMultisigTransaction::AccountMultisigCall(call) => {
+                if !defaults.allows_method(&call.method) {
+                    return Err(errors::method_not_allowed(&call.method));
+                }
+                // A call has the threshold of the method it calls, so it cannot
+                // be used to lower the threshold of a kind of transaction.
+                EventKind::from_method(&call.method).unwrap_or_else(|| args.transaction.kind())
+            }
+            transaction => transaction.kind(),
+        };
+        // The threshold of the account, or of the kind, is a minimum for the
+        // submitter.
+        let threshold = args.threshold.unwrap_or_default().max(
+            defaults
+                .threshold_for(kind)
+                .unwrap_or(MULTISIG_DEFAULT_THRESHOLD),
+        );
+        let timeout_in_secs = args
+            .timeout_in_secs
+            .or(defaults.timeout_in_secs)
+            .unwrap_or(MULTISIG_DEFAULT_TIMEOUT_IN_SECS)
+            .min(MULTISIG_MAXIMUM_TIMEOUT_IN_SECS);
+        let execute_automatically = args
+            .execute_automatically
+            .or(defaults.execute_automatically)
+            .unwrap_or(MULTISIG_DEFAULT_EXECUTE_AUTOMATICALLY);
+        let timeout = Timestamp(now.0 + Duration::from_secs(timeout_in_secs));
+
+        let mut approvers: BTreeMap<Identity, ApproverInfo> = account
+            .roles
+            .keys()
+            .filter(|id| {
+                **id != args.account
+                    && (account.has_role_at(id, Role::Owner, now)
+                        || account.has_role_at(id, Role::CanMultisigApprove, now))
+            })
+            .map(|id| (*id, ApproverInfo::default()))
+            .collect();
+        // A routed send is not approved by a spender who cannot approve.
+        if spender.is_none() || approvers.contains_key(sender) {
+            approvers.insert(*sender, ApproverInfo { approved: true });
+        }
+        let weights = defaults.weights.map(|w| {
+            w.into_iter()
+                .filter(|(id, _)| approvers.contains_key(id))
+                .collect()
+        });
+
+        let token = ByteVec::from(self.next_token.to_be_bytes().to_vec());
+        self.next_token += 1;
+
+        let tx = MultisigTransaction {
+            account: args.account,
+            memo: args.memo.clone(),
+            transaction: *args.transaction.clone(),
+            submitter: *sender,
+            approvers,
+            threshold,
+            execute_automatically,
+            timeout,
+            data: args.data.clone(),
+            state: MultisigTransactionState::Pending,
+            weights,
+            spender,
+        };
+        self.storage.put(&token, &tx)?;
+        self.timeouts.insert((timeout, token.clone()));
+        self.emit(EventInfo::AccountMultisigSubmit {
+            submitter: *sender,
+            account: args.account,
+            memo: args.memo,
+            transaction: args.transaction,
+            token: Some(token.clone()),
+            threshold,
+            timeout,
+            execute_automatically,
+            data: args.data,
+        })?;
+
+        if tx.execute_automatically && tx.is_approved() {
+            self.execute_transaction(&token, tx, None)?;
+        }
+        Ok(SubmitTransactionReturn { token })
+    }
+
+    /// Verify that a generic call is still allowed when it is executed, as the
+    /// account might have changed its allowed methods since it was submitted.
+    fn check_call(&self, tx: &MultisigTransaction) -> Result<(), ManyError> {
+        if let AccountMultisigTransaction::AccountMultisigCall(call) = &tx.transaction {
+            if !feature(&self.executor.account(&tx.account)?)?
+                .arg
+                .allows_method(&call.method)
+            {
+                return Err(errors::method_not_allowed(&call.method));
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute an approved transaction. If the execution fails, the error is
+    /// returned and the transaction stays pending, so it can be executed again
+    /// before it expires.
+    fn execute_transaction(
+        &mut self,
+        token: &ByteVec,
+        mut tx: MultisigTransaction,
+        executer: Option<Identity>,
+    ) -> Result<ResponseMessage, ManyError> {
+        self.check_call(&tx)?;
+        let data = self.executor.execute(&tx.account, &tx.transaction)?;
+        match (&tx.transaction, &tx.spender) {
+            (AccountMultisigTransaction::AccountMultisigCall(call), _) => {
+                self.emit(EventInfo::AccountMultisigCall {
+                    account: tx.account,
+                    method: call.method.clone(),
+                    argument: call.argument.clone(),
+                })?;
+            }
+            (AccountMultisigTransaction::Send(send), Some(spender)) => {
+                let now = self.now();
+                self.executor.record_spending(spender, send, now)?;
+            }
+            _ => {}
+        }
+        let response = ResponseMessage {
+            from: tx.account,
+            data: Ok(data),
+            timestamp: Some(self.now().0),
+            ..Default::default()
+        };
+
+        tx.state = if executer.is_some() {
+            MultisigTransactionState::ExecutedManually
+        } else {
+            MultisigTransactionState::ExecutedAutomatically
+        };
+        self.storage.put(token, &tx)?;
+        self.timeouts.remove(&(tx.timeout, token.clone()));
+        self.emit(EventInfo::AccountMultisigExecute {
+            account: tx.account,
+            token: token.clone(),
+            executer,
+            response: response.clone(),
+        })?;
+        Ok(response)
+    }
+}
+
+fn feature(account: &Account) -> Result<MultisigAccountFeature, ManyError> {
+    account
+        .feature::<MultisigAccountFeature>()
+        .ok_or_else(|| ManyError::attribute_not_found(MultisigAccountFeature::ID))
+}
+
+impl<S: MultisigStorage, X: MultisigExecutor, E: EventSink> AccountMultisigModuleBackend
+    for MultisigEngine<S, X, E>
+{
+    fn multisig_submit_transaction(
+        &mut self,
+        sender: &Identity,
+        args: SubmitTransactionArgs,
+    ) -> Result<SubmitTransactionReturn, ManyError> {
+        self.submit(sender, args, None)
+    }
+
+    fn multisig_info(&self, _sender: &Identity, args: InfoArgs) -> Result<InfoReturn, ManyError> {
+        Ok(self.get(&args.token)?.info(self.now()))
+    }
+
+    fn multisig_list(&self, sender: &Identity, args: ListArgs) -> Result<ListReturn, ManyError> {
+        let now = self.now();
+        let descending = args.order == Some(SortOrder::Descending);
+        let count = args
+            .count
+            .map_or(MULTISIG_LIST_MAX_COUNT, |c| c.min(MULTISIG_LIST_MAX_COUNT))
+            as usize;
+
+        let mut transactions = Vec::new();
+        for entry in self.storage.iter() {
+            let (token, tx) = entry?;
+            let info = tx.info(now);
+            if args.matches(sender, &tx.account, &info) {
+                transactions.push(ListItem {
+                    token,
+                    account: tx.account,
+                    info,
+                });
+            }
+        }
+        if descending {
+            transactions.reverse();
+        }
+        let nb_transactions = transactions.len() as u64;
+
+        // The cursor is the token of the last transaction of the previous page.
+        let mut page: Vec<ListItem> = transactions
+            .into_iter()
+            .filter(|item| {
+                args.cursor.as_ref().map_or(true, |c| {
+                    if descending {
+                        &item.token < c
+                    } else {
+                        &item.token > c
+                    }
+                })
+            })
+            .take(count + 1)
+            .collect();
+        let cursor = if page.len() > count {
+            page.truncate(count);
+            page.last().map(|item| item.token.clone())
+        } else {
+            None
+        };
+
+        Ok(ListReturn {
+            nb_transactions,
+            transactions: page,
+            cursor,
+        })
+    }
+
+    fn multisig_set_defaults(
+        &mut self,
+        sender: &Identity,
+        args: SetDefaultsArgs,
+    ) -> Result<SetDefaultsReturn, ManyError> {
+        let account = self.executor.account(&args.account)?;
+        account.needs_role_at(sender, [Role::Owner], self.now())?;
+
+        let mut feature = feature(&account)?;
+        if args.threshold.is_some() {
+            feature.arg.threshold = args.threshold;
+        }
+        if args.timeout_in_secs.is_some() {
+            feature.arg.timeout_in_secs = args.timeout_in_secs;
+        }
+        if args.execute_automatically.is_some() {
+            feature.arg.execute_automatically = args.execute_automatically;
+        }
+        if args.weights.is_some() {
+            feature.arg.weights = args.weights.clone();
+        }
+        if args.kind_thresholds.is_some() {
+            feature.arg.kind_thresholds = args.kind_thresholds.clone();
+        }
+        self.executor.set_defaults(&args.account, feature)?;
+
+        self.emit(EventInfo::AccountMultisigSetDefaults {
+            submitter: *sender,
+            account: args.account,
+            threshold: args.threshold,
+            timeout_in_secs: args.timeout_in_secs,
+            execute_automatically: args.execute_automatically,
+            weights: args.weights,
+            kind_thresholds: args.kind_thresholds,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn multisig_approve(
+        &mut self,
+        sender: &Identity,
+        args: ApproveArgs,
+    ) -> Result<ApproveReturn, ManyError> {
+        let mut tx = self.pending(&args.token)?;
+        match tx.approvers.get_mut(sender) {
+            Some(info) => info.approved = true,
+            None => return Err(errors::user_cannot_approve_transaction()),
+        }
+        self.storage.put(&args.token, &tx)?;
+        self.emit(EventInfo::AccountMultisigApprove {
+            account: tx.account,
+            token: args.token.clone(),
+            approver: *sender,
+        })?;
+
+        if tx.execute_automatically && tx.is_approved() {
+            self.execute_transaction(&args.token, tx, None)?;
+        }
+        Ok(EmptyReturn)
+    }
+
+    fn multisig_revoke(
+        &mut self,
+        sender: &Identity,
+        args: RevokeArgs,
+    ) -> Result<RevokeReturn, ManyError> {
+        let mut tx = self.pending(&args.token)?;
+        match tx.approvers.get_mut(sender) {
+            Some(info) => info.approved = false,
+            None => return Err(errors::user_cannot_approve_transaction()),
+        }
+        self.storage.put(&args.token, &tx)?;
+        self.emit(EventInfo::AccountMultisigRevoke {
+            account: tx.account,
+            token: args.token,
+            revoker: *sender,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn multisig_execute(
+        &mut self,
+        sender: &Identity,
+        args: ExecuteArgs,
+    ) -> Result<ResponseMessage, ManyError> {
+        let tx = self.pending(&args.token)?;
+        if *sender != tx.submitter && !tx.approvers.contains_key(sender) {
+            return Err(errors::user_cannot_approve_transaction());
+        }
+        if !tx.is_approved() {
+            return Err(errors::cannot_execute_transaction());
+        }
+        self.execute_transaction(&args.token, tx, Some(*sender))
+    }
+
+    fn multisig_withdraw(
+        &mut self,
+        sender: &Identity,
+        args: WithdrawArgs,
+    ) -> Result<WithdrawReturn, ManyError> {
+        let mut tx = self.pending(&args.token)?;
+        if *sender != tx.submitter {
+            self.executor
+                .account(&tx.account)?
+                .needs_role_at(sender, [Role::Owner], self.now())?;
+        }
+
+        tx.state = MultisigTransactionState::Withdrawn;
+        self.storage.put(&args.token, &tx)?;
+        self.timeouts.remove(&(tx.timeout, args.token.clone()));
+        self.emit(EventInfo::AccountMultisigWithdraw {
+            account: tx.account,
+            token: args.token,
+            withdrawer: *sender,
+        })?;
+        Ok(EmptyReturn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::account::features::spending_limits::{
+        route_send, SendRoute, SpendingHistory, SpendingLimitsAccountFeature,
+    };
+    use crate::server::module::account::features::FeatureSet;
+    use crate::server::module::account::{AccountMap, CreateArgs, DisableArgs};
+    use crate::types::events::EventKind;
+    use crate::types::identity::testing::identity;
+
+    #[derive(Default)]
+    struct TestExecutor {
+        accounts: BTreeMap<Identity, Account>,
+        executed: Vec<AccountMultisigTransaction>,
+        history: SpendingHistory,
+    }
+
+    impl MultisigExecutor for TestExecutor {
+        fn account(&self, id: &Identity) -> Result<Account, ManyError> {
+            self.accounts
+                .get(id)
+                .cloned()
+                .ok_or_else(|| ManyError::unknown("Unknown account.".to_string()))
+        }
+
+        fn set_defaults(
+            &mut self,
+            id: &Identity,
+            feature: MultisigAccountFeature,
+        ) -> Result<(), ManyError> {
+            let account = self.accounts.get_mut(id).unwrap();
+            account.features.insert(feature.as_feature());
+            Ok(())
+        }
+
+        fn execute(
+            &mut self,
+            _account: &Identity,
+            transaction: &AccountMultisigTransaction,
+        ) -> Result<Vec<u8>, ManyError> {
+            self.executed.push(transaction.clone());
+            Ok(vec![])
+        }
+
+        fn record_spending(
+            &mut self,
+            spender: &Identity,
+            send: &SendArgs,
+            time: Timestamp,
+        ) -> Result<(), ManyError> {
+            self.history
+                .record(spender, &send.symbol, send.amount.clone(), time);
+            Ok(())
+        }
+    }
+
+    type Engine = MultisigEngine<InMemoryMultisigStorage, TestExecutor, Vec<EventInfo>>;
+
+    fn account_id() -> Identity {
+        identity(100)
+    }
+
+    fn engine(feature: MultisigAccountFeature) -> Engine {
+        let account = Account::create(
+            &identity(1),
+            CreateArgs {
+                description: None,
+                roles: Some(BTreeMap::from([
+                    (identity(2), BTreeSet::from([Role::CanMultisigApprove])),
+                    (identity(3), BTreeSet::from([Role::CanMultisigSubmit])),
+                ])),
+                features: FeatureSet::from_iter([feature.as_feature()]),
+            },
+        );
+        let executor = TestExecutor {
+            accounts: BTreeMap::from([(account_id(), account)]),
+            ..Default::default()
+        };
+        let mut engine =
+            MultisigEngine::load(InMemoryMultisigStorage::default(), executor, Vec::new()).unwrap();
+        engine.set_time(Some(Timestamp::new(1000).unwrap()));
+        engine
+    }
+
+    fn submit(engine: &mut Engine, sender: u32) -> Result<ByteVec, ManyError> {
+        submit_transaction(
+            engine,
+            sender,
+            AccountMultisigTransaction::AccountDisable(DisableArgs {
+                account: account_id(),
+            }),
+        )
+    }
+
+    fn submit_transaction(
+        engine: &mut Engine,
+        sender: u32,
+        transaction: AccountMultisigTransaction,
+    ) -> Result<ByteVec, ManyError> {
+        engine
+            .multisig_submit_transaction(
+                &identity(sender),
+                SubmitTransactionArgs {
+                    account: account_id(),
+                    memo: None,
+                    transaction: Box::new(transaction),
+                    threshold: None,
+                    timeout_in_secs: None,
+                    execute_automatically: None,
+                    data: None,
+                },
+            )
+            .map(|r| r.token)
+    }
+
+    #[test]
+    fn approve_and_execute() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), Some(100), None));
+        assert!(submit(&mut engine, 4).is_err());
+
+        let token = submit(&mut engine, 3).unwrap();
+        let info = engine
+            .multisig_info(
+                &identity(0),
+                InfoArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(info.threshold, 2);
+        assert_eq!(info.timeout, Timestamp::new(1100).unwrap());
+        assert_eq!(
+            info.approvers.keys().copied().collect::<Vec<_>>(),
+            vec![identity(1), identity(2), identity(3)]
+        );
+
+        assert_eq!(
+            engine
+                .multisig_execute(
+                    &identity(3),
+                    ExecuteArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap_err(),
+            errors::cannot_execute_transaction()
+        );
+        assert_eq!(
+            engine
+                .multisig_approve(
+                    &identity(4),
+                    ApproveArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap_err(),
+            errors::user_cannot_approve_transaction()
+        );
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        engine
+            .multisig_execute(
+                &identity(2),
+                ExecuteArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(engine.executor().executed.len(), 1);
+        assert_eq!(
+            engine
+                .multisig_info(&identity(0), InfoArgs { token })
+                .unwrap()
+                .state,
+            MultisigTransactionState::ExecutedManually
+        );
+    }
+
+    #[test]
+    fn weights_and_kind_thresholds() {
+        let mut strict = engine(MultisigAccountFeature::create(Some(2), None, None));
+        let mut engine = engine(
+            MultisigAccountFeature::create(Some(1), None, None)
+                .with_weight(identity(2), 2)
+                .with_weight(identity(4), 5)
+                .with_kind_threshold(EventKind::AccountDisable, 3),
+        );
+        let token = submit(&mut engine, 1).unwrap();
+        let info = engine
+            .multisig_info(
+                &identity(0),
+                InfoArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(info.threshold, 3);
+        assert_eq!(info.weights, Some(BTreeMap::from([(identity(2), 2)])));
+        assert_eq!(
+            engine
+                .multisig_execute(
+                    &identity(1),
+                    ExecuteArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap_err(),
+            errors::cannot_execute_transaction()
+        );
+
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        engine
+            .multisig_execute(&identity(1), ExecuteArgs { token })
+            .unwrap();
+        assert_eq!(engine.executor().executed.len(), 1);
+
+        // The threshold of a kind, or of the account, is a minimum for the
+        // submitter, even when executing automatically.
+        let threshold_of = |engine: &mut Engine, threshold: u64| {
+            let token = engine
+                .multisig_submit_transaction(
+                    &identity(1),
+                    SubmitTransactionArgs {
+                        account: account_id(),
+                        memo: None,
+                        transaction: Box::new(AccountMultisigTransaction::AccountDisable(
+                            DisableArgs {
+                                account: account_id(),
+                            },
+                        )),
+                        threshold: Some(threshold),
+                        timeout_in_secs: None,
+                        execute_automatically: Some(true),
+                        data: None,
+                    },
+                )
+                .unwrap()
+                .token;
+            engine
+                .multisig_info(&identity(0), InfoArgs { token })
+                .unwrap()
+                .threshold
+        };
+        assert_eq!(threshold_of(&mut engine, 1), 3);
+        assert_eq!(threshold_of(&mut engine, 4), 4);
+        assert_eq!(engine.executor().executed.len(), 1);
+
+        assert_eq!(threshold_of(&mut strict, 1), 2);
+        assert_eq!(threshold_of(&mut strict, 3), 3);
+        assert!(strict.executor().executed.is_empty());
+    }
+
+    #[test]
+    fn list() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), Some(100), None));
+        let tokens = [1, 3, 1]
+            .into_iter()
+            .map(|sender| submit(&mut engine, sender).unwrap())
+            .collect::<Vec<_>>();
+        engine
+            .multisig_withdraw(
+                &identity(1),
+                WithdrawArgs {
+                    token: tokens[2].clone(),
+                },
+            )
+            .unwrap();
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: tokens[1].clone(),
+                },
+            )
+            .unwrap();
+
+        let list_tokens = |engine: &Engine, sender: u32, args: ListArgs| {
+            let list = engine.multisig_list(&identity(sender), args).unwrap();
+            (
+                list.transactions
+                    .into_iter()
+                    .map(|item| item.token)
+                    .collect::<Vec<_>>(),
+                list.cursor,
+            )
+        };
+
+        let (all, cursor) = list_tokens(&engine, 0, ListArgs::default());
+        assert_eq!(all, tokens);
+        assert_eq!(cursor, None);
+
+        let pending = ListArgs {
+            account: Some(account_id()),
+            state: Some(vec![MultisigTransactionState::Pending].into()),
+            ..Default::default()
+        };
+        assert_eq!(list_tokens(&engine, 0, pending.clone()).0, tokens[..2]);
+        assert_eq!(
+            list_tokens(
+                &engine,
+                0,
+                ListArgs {
+                    submitter: Some(identity(3)),
+                    ..pending
+                }
+            )
+            .0,
+            tokens[1..2]
+        );
+
+        let awaiting = ListArgs {
+            awaiting_approval: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(list_tokens(&engine, 2, awaiting.clone()).0, tokens[..1]);
+        assert_eq!(list_tokens(&engine, 1, awaiting.clone()).0, tokens[1..2]);
+        assert!(list_tokens(&engine, 4, awaiting).0.is_empty());
+
+        let (first, cursor) = list_tokens(
+            &engine,
+            0,
+            ListArgs {
+                count: Some(2),
+                order: Some(SortOrder::Descending),
+                ..Default::default()
+            },
+        );
+        assert_eq!(first, vec![tokens[2].clone(), tokens[1].clone()]);
+        let (second, cursor) = list_tokens(
+            &engine,
+            0,
+            ListArgs {
+                count: Some(2),
+                order: Some(SortOrder::Descending),
+                cursor,
+                ..Default::default()
+            },
+        );
+        assert_eq!(second, vec![tokens[0].clone()]);
+        assert_eq!(cursor, None);
+
+        engine.set_time(Some(Timestamp::new(1100).unwrap()));
+        let expired = ListArgs {
+            state: Some(vec![MultisigTransactionState::Expired].into()),
+            ..Default::default()
+        };
+        assert_eq!(list_tokens(&engine, 0, expired).0, tokens[..2]);
+    }
+
+    #[test]
+    fn call() {
+        let mut engine = engine(
+            MultisigAccountFeature::create(Some(1), None, Some(true))
+                .with_allowed_method("kvstore.put"),
+        );
+        let call = |method: &str| {
+            AccountMultisigTransaction::AccountMultisigCall(CallArgs {
+                method: method.to_string(),
+                argument: vec![].into(),
+            })
+        };
+
+        assert_eq!(
+            submit_transaction(&mut engine, 1, call("kvstore.delete")).unwrap_err(),
+            errors::method_not_allowed("kvstore.delete")
+        );
+        submit_transaction(&mut engine, 1, call("kvstore.put")).unwrap();
+        assert_eq!(engine.executor().executed, vec![call("kvstore.put")]);
+        assert!(engine.events().iter().any(|e| matches!(
+            e,
+            EventInfo::AccountMultisigCall { method, .. } if method == "kvstore.put"
+        )));
+    }
+
+    #[test]
+    fn call_needs_allowed_method() {
+        let call = AccountMultisigTransaction::AccountMultisigCall(CallArgs {
+            method: "kvstore.put".to_string(),
+            argument: vec![].into(),
+        });
+
+        // Methods are denied by default.
+        let mut engine = engine(MultisigAccountFeature::create(Some(1), None, None));
+        assert_eq!(
+            submit_transaction(&mut engine, 1, call.clone()).unwrap_err(),
+            errors::method_not_allowed("kvstore.put")
+        );
+
+        // Calls have the threshold of the kind of their method.
+        let feature = MultisigAccountFeature::create(Some(1), None, None)
+            .with_allowed_method("kvstore.put")
+            .with_kind_threshold(EventKind::KvStorePut, 2);
+        let mut engine = engine(feature);
+        let token = submit_transaction(&mut engine, 1, call).unwrap();
+        let info = engine
+            .multisig_info(
+                &identity(0),
+                InfoArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(info.threshold, 2);
+
+        // The method is no longer allowed when the transaction is executed.
+        engine
+            .executor_mut()
+            .set_defaults(
+                &account_id(),
+                MultisigAccountFeature::create(Some(1), None, None),
+            )
+            .unwrap();
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            engine
+                .multisig_execute(
+                    &identity(1),
+                    ExecuteArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap_err(),
+            errors::method_not_allowed("kvstore.put")
+        );
+        assert!(engine.executor().executed.is_empty());
+
+        // The transaction stays pending, and can be executed once allowed again.
+        let info = engine
+            .multisig_info(
+                &identity(0),
+                InfoArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(info.state, MultisigTransactionState::Pending);
+        engine
+            .executor_mut()
+            .set_defaults(
+                &account_id(),
+                MultisigAccountFeature::create(Some(1), None, None)
+                    .with_allowed_method("kvstore.put"),
+            )
+            .unwrap();
+        engine
+            .multisig_execute(&identity(1), ExecuteArgs { token })
+            .unwrap();
+        assert_eq!(engine.executor().executed.len(), 1);
+    }
+
+    #[test]
+    fn server_dispatcher() {
+        let server = ManyServer::simple(
+            "dispatcher",
+            crate::types::identity::cose::CoseKeyIdentity::anonymous(),
+            None,
+            None,
+        );
+        let dispatcher = ManyServerDispatcher::new(server);
+
+        let status = dispatcher
+            .call(
+                &identity(1),
+                &CallArgs {
+                    method: "status".to_string(),
+                    argument: vec![].into(),
+                },
+            )
+            .unwrap();
+        let status: crate::server::module::base::Status = minicbor::decode(&status).unwrap();
+        assert_eq!(status.name, "dispatcher");
+
+        assert_eq!(
+            dispatcher
+                .call(
+                    &identity(1),
+                    &CallArgs {
+                        method: "unknown.method".to_string(),
+                        argument: vec![].into(),
+                    },
+                )
+                .unwrap_err(),
+            ManyError::invalid_method_name("unknown.method")
+        );
+    }
+
+    #[test]
+    fn execute_automatically() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), None, Some(true)));
+        let token = submit(&mut engine, 1).unwrap();
+        engine
+            .multisig_revoke(
+                &identity(1),
+                RevokeArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        engine
+            .multisig_approve(
+                &identity(1),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert!(engine.executor().executed.is_empty());
+
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(engine.executor().executed.len(), 1);
+        assert!(matches!(
+            engine.events().last(),
+            Some(EventInfo::AccountMultisigExecute { executer: None, .. })
+        ));
+    }
+
+    #[test]
+    fn expire() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), Some(100), None));
+        let token = submit(&mut engine, 1).unwrap();
+
+        engine.set_time(Some(Timestamp::new(1100).unwrap()));
+        assert_eq!(
+            engine
+                .multisig_info(
+                    &identity(0),
+                    InfoArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap()
+                .state,
+            MultisigTransactionState::Expired
+        );
+        assert_eq!(engine.expire().unwrap(), vec![token.clone()]);
+        assert!(matches!(
+            engine.events().last(),
+            Some(EventInfo::AccountMultisigExpired { .. })
+        ));
+        assert_eq!(
+            engine
+                .multisig_approve(&identity(2), ApproveArgs { token })
+                .unwrap_err(),
+            errors::transaction_expired_or_withdrawn()
+        );
+    }
+
+    #[test]
+    fn expire_pending_only() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), Some(100), None));
+        let first = submit(&mut engine, 1).unwrap();
+        let withdrawn = submit(&mut engine, 1).unwrap();
+        engine
+            .multisig_withdraw(&identity(1), WithdrawArgs { token: withdrawn })
+            .unwrap();
+        engine.set_time(Some(Timestamp::new(1050).unwrap()));
+        let second = submit(&mut engine, 1).unwrap();
+
+        // The pending transactions are found again when loading.
+        let mut engine: Engine = MultisigEngine::load(
+            engine.storage().clone(),
+            TestExecutor::default(),
+            Vec::new(),
+        )
+        .unwrap();
+        engine.set_time(Some(Timestamp::new(1100).unwrap()));
+        assert_eq!(engine.expire().unwrap(), vec![first]);
+        assert_eq!(engine.expire().unwrap(), Vec::<ByteVec>::new());
+        engine.set_time(Some(Timestamp::new(1150).unwrap()));
+        assert_eq!(engine.expire().unwrap(), vec![second]);
+    }
+
+    #[test]
+    fn set_defaults() {
+        let mut engine = engine(MultisigAccountFeature::create(Some(2), None, None));
+        assert!(engine
+            .multisig_set_defaults(
+                &identity(2),
+                SetDefaultsArgs {
+                    account: account_id(),
+                    threshold: Some(1),
+                    timeout_in_secs: None,
+                    execute_automatically: None,
+                    weights: None,
+                    kind_thresholds: None,
+                }
+            )
+            .is_err());
+        engine
+            .multisig_set_defaults(
+                &identity(1),
+                SetDefaultsArgs {
+                    account: account_id(),
+                    threshold: Some(1),
+                    timeout_in_secs: None,
+                    execute_automatically: None,
+                    weights: None,
+                    kind_thresholds: None,
+                },
+            )
+            .unwrap();
+
+        let token = submit(&mut engine, 1).unwrap();
+        let info = engine
+            .multisig_info(&identity(0), InfoArgs { token })
+            .unwrap();
+        assert_eq!(info.threshold, 1);
+        assert_eq!(
+            info.timeout,
+            Timestamp::new(1000 + MULTISIG_DEFAULT_TIMEOUT_IN_SECS).unwrap()
+        );
+    }
+
+    #[test]
+    fn routed_send() {
+        let symbol = identity(200);
+        let mut accounts = AccountMap::new(Identity::public_key_raw([0; 28]));
+        let (address, _) = accounts
+            .insert(Account::create(
+                &identity(1),
+                CreateArgs {
+                    description: None,
+                    roles: Some(BTreeMap::from([
+                        (identity(2), BTreeSet::from([Role::CanMultisigApprove])),
+                        (identity(4), BTreeSet::from([Role::CanLedgerTransact])),
+                    ])),
+                    features: FeatureSet::from_iter([
+                        MultisigAccountFeature::create(Some(1), None, Some(false)).as_feature(),
+                        SpendingLimitsAccountFeature::default()
+                            .with_limit(
+                                Role::CanLedgerTransact,
+                                symbol,
+                                TokenAmount::from(100u64),
+                                3600,
+                            )
+                            .as_feature(),
+                    ]),
+                },
+            ))
+            .unwrap();
+        let executor = TestExecutor {
+            accounts: BTreeMap::from([(address, accounts.get(&address).unwrap().clone())]),
+            ..Default::default()
+        };
+        let mut engine: Engine =
+            MultisigEngine::load(InMemoryMultisigStorage::default(), executor, Vec::new()).unwrap();
+        let now = Timestamp::new(1000).unwrap();
+        engine.set_time(Some(now));
+
+        let send = SendArgs {
+            from: Some(address),
+            to: identity(5),
+            symbol,
+            amount: TokenAmount::from(150u64),
+        };
+        let args = match route_send(
+            &accounts,
+            &engine.executor().history,
+            &identity(4),
+            &send,
+            now,
+        ) {
+            Ok(SendRoute::Multisig(args)) => args,
+            _ => panic!("Send should be routed to multisig"),
+        };
+
+        // The sender can send from the account, but not submit transactions.
+        assert_eq!(
+            engine
+                .multisig_submit_transaction(&identity(4), args.clone())
+                .unwrap_err(),
+            crate::server::module::account::errors::user_needs_role(Role::Owner)
+        );
+        assert!(engine
+            .submit_routed_send(&identity(5), args.clone())
+            .is_err());
+        let token = engine.submit_routed_send(&identity(4), args).unwrap().token;
+
+        // The sender does not approve its own send over the limits.
+        let info = engine
+            .multisig_info(
+                &identity(0),
+                InfoArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(info.submitter, identity(4));
+        assert!(!info.approvers.contains_key(&identity(4)));
+        assert_eq!(
+            engine
+                .multisig_execute(
+                    &identity(4),
+                    ExecuteArgs {
+                        token: token.clone()
+                    }
+                )
+                .unwrap_err(),
+            errors::cannot_execute_transaction()
+        );
+
+        engine
+            .multisig_approve(
+                &identity(2),
+                ApproveArgs {
+                    token: token.clone(),
+                },
+            )
+            .unwrap();
+        engine
+            .multisig_execute(&identity(4), ExecuteArgs { token })
+            .unwrap();
+        assert_eq!(
+            engine.executor().executed,
+            vec![AccountMultisigTransaction::Send(send.clone())]
+        );
+
+        // The executed send counts against the limits of the sender.
+        assert_eq!(
+            engine.executor().history.spent(&identity(4), &symbol, now),
+            TokenAmount::from(150u64)
+        );
+        assert_eq!(
+            route_send(
+                &accounts,
+                &engine.executor().history,
+                &identity(4),
+                &SendArgs {
+                    amount: TokenAmount::from(1u64),
+                    ..send
+                },
+                now,
+            )
+            .map(|route| route == SendRoute::Execute),
+            Ok(false)
+        );
+    }
+}
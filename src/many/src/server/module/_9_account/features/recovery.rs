@@ -0,0 +1,494 @@
+use crate::cbor::CborAny;
+use crate::server::module::account::features::{Feature, FeatureId, TryCreateFeature};
+use crate::server::module::account::{Account, Role};
+use crate::server::module::EmptyReturn;
+use crate::types::Timestamp;
+use crate::{Identity, ManyError};
+use many_macros::many_module;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+#[cfg(test)]
+use mockall::{automock, predicate::*};
+
+pub mod errors {
+    use crate::define_attribute_many_error;
+    define_attribute_many_error!(
+        attribute 9 => {
+            300: pub fn recovery_cannot_be_found() => "The recovery cannot be found.",
+            301: pub fn user_is_not_guardian() => "The user is not a guardian of this account.",
+            302: pub fn recovery_not_approved(approvals, threshold) => "The recovery has {approvals} approvals out of {threshold}.",
+            303: pub fn recovery_delay_not_passed(time) => "The recovery cannot be finalized before {time}.",
+        }
+    );
+}
+
+/// Social recovery of an account. Guardians can replace the owners of the
+/// account once enough of them approve and the delay has passed, leaving the
+/// owners time to cancel an illegitimate recovery.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecoveryAccountFeature {
+    pub guardians: BTreeSet<Identity>,
+
+    /// Number of guardians that must approve a recovery.
+    pub threshold: u64,
+
+    /// Time between the initiation of a recovery and its finalization.
+    pub delay_in_secs: u64,
+}
+
+impl RecoveryAccountFeature {
+    pub fn create(guardians: BTreeSet<Identity>, threshold: u64, delay_in_secs: u64) -> Self {
+        Self {
+            guardians,
+            threshold,
+            delay_in_secs,
+        }
+    }
+
+    pub fn is_guardian(&self, id: &Identity) -> bool {
+        self.guardians.contains(id)
+    }
+}
+
+impl TryCreateFeature for RecoveryAccountFeature {
+    const ID: FeatureId = 3;
+
+    fn try_create(f: &Feature) -> Result<Self, ManyError> {
+        let argument = f.arguments();
+        if argument.len() != 1 {
+            return Err(ManyError::invalid_attribute_arguments());
+        }
+
+        match argument.get(0) {
+            Some(CborAny::Map(m)) => {
+                let guardians = match m.get(&CborAny::Int(0)) {
+                    Some(CborAny::Array(a)) => a
+                        .iter()
+                        .map(|g| match g {
+                            CborAny::Bytes(b) => Identity::from_bytes(b)
+                                .map_err(|_| ManyError::invalid_attribute_arguments()),
+                            _ => Err(ManyError::invalid_attribute_arguments()),
+                        })
+                        .collect::<Result<BTreeSet<_>, _>>()?,
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+                let threshold = match m.get(&CborAny::Int(1)) {
+                    Some(CborAny::Int(x)) => u64::try_from(*x)
+                        .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+                let delay_in_secs = match m.get(&CborAny::Int(2)) {
+                    Some(CborAny::Int(x)) => u64::try_from(*x)
+                        .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+
+                if guardians.is_empty() || threshold == 0 || threshold > guardians.len() as u64 {
+                    return Err(ManyError::invalid_attribute_arguments());
+                }
+
+                Ok(Self {
+                    guardians,
+                    threshold,
+                    delay_in_secs,
+                })
+            }
+            _ => Err(ManyError::invalid_attribute_arguments()),
+        }
+    }
+}
+
+impl super::FeatureInfo for RecoveryAccountFeature {
+    fn as_feature(&self) -> Feature {
+        let map = BTreeMap::from([
+            (
+                CborAny::Int(0),
+                CborAny::Array(
+                    self.guardians
+                        .iter()
+                        .map(|g| CborAny::Bytes(g.to_vec()))
+                        .collect(),
+                ),
+            ),
+            (CborAny::Int(1), super::positive_int(self.threshold)),
+            (CborAny::Int(2), super::positive_int(self.delay_in_secs)),
+        ]);
+
+        Feature::with_id(Self::ID).with_argument(CborAny::Map(map))
+    }
+
+    fn roles() -> BTreeSet<Role> {
+        BTreeSet::new()
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RecoveryInitiateArgs {
+    #[n(0)]
+    pub account: Identity,
+
+    /// The identity that will own the account after the recovery.
+    #[n(1)]
+    pub new_owner: Identity,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RecoveryInitiateReturn {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RecoveryApproveArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type RecoveryApproveReturn = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RecoveryCancelArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type RecoveryCancelReturn = EmptyReturn;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct RecoveryFinalizeArgs {
+    #[n(0)]
+    pub token: ByteVec,
+}
+
+pub type RecoveryFinalizeReturn = EmptyReturn;
+
+/// A recovery in progress. This is useful as utility for managing recoveries in
+/// your backend. The guardian initiating the recovery approves it.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Recovery {
+    #[n(0)]
+    pub account: Identity,
+
+    #[n(1)]
+    pub new_owner: Identity,
+
+    #[n(2)]
+    pub initiator: Identity,
+
+    #[n(3)]
+    pub approvers: BTreeSet<Identity>,
+
+    /// The recovery can be finalized from this time.
+    #[n(4)]
+    pub finalizable_at: Timestamp,
+}
+
+impl Recovery {
+    pub fn initiate(
+        feature: &RecoveryAccountFeature,
+        sender: &Identity,
+        args: RecoveryInitiateArgs,
+        now: Timestamp,
+    ) -> Result<Self, ManyError> {
+        if !feature.is_guardian(sender) {
+            return Err(errors::user_is_not_guardian());
+        }
+
+        let finalizable_at = now
+            .0
+            .checked_add(Duration::from_secs(feature.delay_in_secs))
+            .ok_or_else(|| ManyError::unknown("Recovery delay is too long.".to_string()))?;
+        Ok(Self {
+            account: args.account,
+            new_owner: args.new_owner,
+            initiator: *sender,
+            approvers: BTreeSet::from([*sender]),
+            finalizable_at: Timestamp(finalizable_at),
+        })
+    }
+
+    /// Record the approval of a guardian. Returns false if it already approved.
+    pub fn approve(
+        &mut self,
+        feature: &RecoveryAccountFeature,
+        sender: &Identity,
+    ) -> Result<bool, ManyError> {
+        if !feature.is_guardian(sender) {
+            return Err(errors::user_is_not_guardian());
+        }
+        Ok(self.approvers.insert(*sender))
+    }
+
+    /// The number of approvals from identities that are still guardians.
+    pub fn approvals(&self, feature: &RecoveryAccountFeature) -> u64 {
+        self.approvers
+            .iter()
+            .filter(|a| feature.is_guardian(a))
+            .count() as u64
+    }
+
+    /// Verify that the sender can cancel this recovery, i.e. is an owner at
+    /// the current block time.
+    pub fn check_cancel(
+        &self,
+        account: &Account,
+        sender: &Identity,
+        now: Timestamp,
+    ) -> Result<(), ManyError> {
+        account.needs_role_at(sender, [Role::Owner], now)
+    }
+
+    /// Verify that the recovery can be finalized and replace the owners of the
+    /// account with the new owner. The account identity keeps its owner role.
+    /// Returns the identities that lost the owner role.
+    pub fn finalize(
+        &self,
+        feature: &RecoveryAccountFeature,
+        account: &mut Account,
+        now: Timestamp,
+    ) -> Result<BTreeSet<Identity>, ManyError> {
+        let approvals = self.approvals(feature);
+        if approvals < feature.threshold {
+            return Err(errors::recovery_not_approved(approvals, feature.threshold));
+        }
+        if now < self.finalizable_at {
+            return Err(errors::recovery_delay_not_passed(
+                self.finalizable_at
+                    .0
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+            ));
+        }
+
+        let owners: BTreeSet<Identity> = account
+            .roles
+            .iter()
+            .filter(|(id, roles)| **id != self.account && roles.contains(&Role::Owner))
+            .map(|(id, _)| *id)
+            .collect();
+        for owner in &owners {
+            account.remove_role(owner, Role::Owner);
+        }
+        account.add_role(&self.new_owner, Role::Owner);
+
+        Ok(owners)
+    }
+}
+
+#[many_module(name = AccountRecoveryModule, namespace = account, many_crate = crate)]
+#[cfg_attr(test, automock)]
+pub trait AccountRecoveryModuleBackend: Send {
+    /// Start the recovery of an account. The sender must be a guardian.
+    fn recovery_initiate(
+        &mut self,
+        sender: &Identity,
+        args: RecoveryInitiateArgs,
+    ) -> Result<RecoveryInitiateReturn, ManyError>;
+
+    /// Approve a recovery. The sender must be a guardian.
+    fn recovery_approve(
+        &mut self,
+        sender: &Identity,
+        args: RecoveryApproveArgs,
+    ) -> Result<RecoveryApproveReturn, ManyError>;
+
+    /// Cancel a recovery. The sender must be an owner of the account.
+    fn recovery_cancel(
+        &mut self,
+        sender: &Identity,
+        args: RecoveryCancelArgs,
+    ) -> Result<RecoveryCancelReturn, ManyError>;
+
+    /// Replace the owners of the account once the recovery has been approved by
+    /// enough guardians and its delay has passed.
+    fn recovery_finalize(
+        &mut self,
+        sender: &Identity,
+        args: RecoveryFinalizeArgs,
+    ) -> Result<RecoveryFinalizeReturn, ManyError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::account::features::{FeatureInfo, FeatureSet};
+    use crate::server::module::account::CreateArgs;
+    use crate::server::module::testutils::call_module_cbor;
+    use crate::types::identity::testing::identity;
+    use mockall::predicate;
+    use std::sync::{Arc, Mutex};
+
+    fn feature() -> RecoveryAccountFeature {
+        RecoveryAccountFeature::create(
+            BTreeSet::from([identity(10), identity(11), identity(12)]),
+            2,
+            3600,
+        )
+    }
+
+    #[test]
+    fn feature_roundtrip() {
+        assert_eq!(
+            RecoveryAccountFeature::try_create(&feature().as_feature()).unwrap(),
+            feature()
+        );
+        assert!(RecoveryAccountFeature::try_create(
+            &RecoveryAccountFeature::create(BTreeSet::from([identity(10)]), 2, 0).as_feature()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn recovery() {
+        let feature = feature();
+        let mut account = Account::create(
+            &identity(1),
+            CreateArgs {
+                description: None,
+                roles: None,
+                features: FeatureSet::from_iter([feature.as_feature()]),
+            },
+        );
+        let args = RecoveryInitiateArgs {
+            account: identity(100),
+            new_owner: identity(2),
+        };
+
+        assert_eq!(
+            Recovery::initiate(&feature, &identity(1), args.clone(), Timestamp::new(0).unwrap()),
+            Err(errors::user_is_not_guardian())
+        );
+        let mut recovery =
+            Recovery::initiate(&feature, &identity(10), args, Timestamp::new(1000).unwrap())
+                .unwrap();
+
+        let now = Timestamp::new(1000).unwrap();
+        assert!(recovery.check_cancel(&account, &identity(1), now).is_ok());
+        assert!(recovery.check_cancel(&account, &identity(10), now).is_err());
+
+        assert_eq!(
+            recovery.finalize(&feature, &mut account, Timestamp::new(5000).unwrap()),
+            Err(errors::recovery_not_approved(1, 2))
+        );
+        assert_eq!(recovery.approve(&feature, &identity(11)), Ok(true));
+        assert_eq!(
+            recovery.finalize(&feature, &mut account, Timestamp::new(2000).unwrap()),
+            Err(errors::recovery_delay_not_passed(4600))
+        );
+
+        assert_eq!(
+            recovery.finalize(&feature, &mut account, Timestamp::new(4600).unwrap()),
+            Ok(BTreeSet::from([identity(1)]))
+        );
+        assert!(!account.has_role(&identity(1), Role::Owner));
+        assert!(account.has_role(&identity(2), Role::Owner));
+    }
+
+    fn token() -> ByteVec {
+        vec![1u8, 2].into()
+    }
+
+    fn module(
+        mock: MockAccountRecoveryModuleBackend,
+    ) -> AccountRecoveryModule<MockAccountRecoveryModuleBackend> {
+        super::AccountRecoveryModule::new(Arc::new(Mutex::new(mock)))
+    }
+
+    #[test]
+    fn recovery_initiate() {
+        let data = RecoveryInitiateArgs {
+            account: identity(100),
+            new_owner: identity(2),
+        };
+        let mut mock = MockAccountRecoveryModuleBackend::new();
+        mock.expect_recovery_initiate()
+            .with(predicate::eq(identity(10)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_, _| Ok(RecoveryInitiateReturn { token: token() }));
+
+        let result: RecoveryInitiateReturn = minicbor::decode(
+            &call_module_cbor(
+                10,
+                &module(mock),
+                "account.recoveryInitiate",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result.token, token());
+    }
+
+    #[test]
+    fn recovery_approve() {
+        let data = RecoveryApproveArgs { token: token() };
+        let mut mock = MockAccountRecoveryModuleBackend::new();
+        mock.expect_recovery_approve()
+            .with(predicate::eq(identity(11)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_, _| Ok(EmptyReturn));
+
+        let result: RecoveryApproveReturn = minicbor::decode(
+            &call_module_cbor(
+                11,
+                &module(mock),
+                "account.recoveryApprove",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result, EmptyReturn);
+    }
+
+    #[test]
+    fn recovery_cancel() {
+        let data = RecoveryCancelArgs { token: token() };
+        let mut mock = MockAccountRecoveryModuleBackend::new();
+        mock.expect_recovery_cancel()
+            .with(predicate::eq(identity(1)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_, _| Err(errors::recovery_cannot_be_found()));
+
+        assert_eq!(
+            call_module_cbor(
+                1,
+                &module(mock),
+                "account.recoveryCancel",
+                minicbor::to_vec(data).unwrap(),
+            ),
+            Err(errors::recovery_cannot_be_found())
+        );
+    }
+
+    #[test]
+    fn recovery_finalize() {
+        let data = RecoveryFinalizeArgs { token: token() };
+        let mut mock = MockAccountRecoveryModuleBackend::new();
+        mock.expect_recovery_finalize()
+            .with(predicate::eq(identity(2)), predicate::eq(data.clone()))
+            .times(1)
+            .returning(|_, _| Ok(EmptyReturn));
+
+        let result: RecoveryFinalizeReturn = minicbor::decode(
+            &call_module_cbor(
+                2,
+                &module(mock),
+                "account.recoveryFinalize",
+                minicbor::to_vec(data).unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(result, EmptyReturn);
+    }
+}
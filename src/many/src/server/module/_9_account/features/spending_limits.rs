@@ -0,0 +1,474 @@
+use crate::cbor::CborAny;
+use crate::server::module::account::features::multisig::{
+    MultisigAccountFeature, SubmitTransactionArgs,
+};
+use crate::server::module::account::features::{Feature, FeatureId, TryCreateFeature};
+use crate::server::module::account::{Account, AccountMap, Role};
+use crate::server::module::ledger::SendArgs;
+use crate::types::ledger::{Symbol, TokenAmount};
+use crate::types::Timestamp;
+use crate::{Identity, ManyError};
+use minicbor::{Decode, Encode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, UNIX_EPOCH};
+
+pub mod errors {
+    use crate::define_attribute_many_error;
+    define_attribute_many_error!(
+        attribute 9 => {
+            200: pub fn spending_limit_exceeded(symbol) => "Spending limit exceeded for symbol {symbol}.",
+        }
+    );
+}
+
+/// Roles that allow sending tokens from an account, and so can be limited.
+const SENDING_ROLES: [Role; 2] = [Role::Owner, Role::CanLedgerTransact];
+
+/// The maximum amount of a symbol that can be sent over a rolling window.
+#[derive(Clone, Debug, Encode, Decode, PartialEq, Eq)]
+#[cbor(map)]
+pub struct SpendingLimit {
+    #[n(0)]
+    pub amount: TokenAmount,
+
+    #[n(1)]
+    pub window_in_secs: u64,
+}
+
+/// Per-role, per-symbol spending limits. The argument of the feature is a map of
+/// role names to maps of symbols to `{ 0 => amount, 1 => window_in_secs }`. The
+/// amount is either an integer or big-endian bytes.
+///
+/// An identity is only limited for a symbol if every role it holds that allows
+/// sending has a limit for that symbol. It can then send as much as the most
+/// permissive of those limits allows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpendingLimitsAccountFeature {
+    pub limits: BTreeMap<Role, BTreeMap<Symbol, SpendingLimit>>,
+}
+
+impl SpendingLimitsAccountFeature {
+    pub fn with_limit(
+        mut self,
+        role: Role,
+        symbol: Symbol,
+        amount: TokenAmount,
+        window_in_secs: u64,
+    ) -> Self {
+        self.limits.entry(role).or_default().insert(
+            symbol,
+            SpendingLimit {
+                amount,
+                window_in_secs,
+            },
+        );
+        self
+    }
+
+    /// The limits applying to an identity sending a symbol, or None if it is not
+    /// limited.
+    pub fn limits_for(
+        &self,
+        account: &Account,
+        id: &Identity,
+        symbol: &Symbol,
+        now: Timestamp,
+    ) -> Option<Vec<&SpendingLimit>> {
+        let mut limits = Vec::new();
+        for role in SENDING_ROLES {
+            if !account.has_role_at(id, role.clone(), now) {
+                continue;
+            }
+            match self.limits.get(&role).and_then(|l| l.get(symbol)) {
+                Some(limit) => limits.push(limit),
+                None => return None,
+            }
+        }
+
+        if limits.is_empty() {
+            None
+        } else {
+            Some(limits)
+        }
+    }
+
+    /// Whether sending an amount stays within the limits of the sender.
+    pub fn allows(
+        &self,
+        account: &Account,
+        history: &SpendingHistory,
+        sender: &Identity,
+        symbol: &Symbol,
+        amount: &TokenAmount,
+        now: Timestamp,
+    ) -> bool {
+        self.limits_for(account, sender, symbol, now)
+            .map_or(true, |limits| {
+                limits.into_iter().any(|limit| {
+                    let since = window_start(now, limit.window_in_secs);
+                    history.spent(sender, symbol, since) + amount.clone() <= limit.amount
+                })
+            })
+    }
+
+    /// The longest window of all limits. Spendings older than this can be pruned.
+    pub fn max_window_in_secs(&self) -> u64 {
+        self.limits
+            .values()
+            .flat_map(|l| l.values())
+            .map(|l| l.window_in_secs)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl TryCreateFeature for SpendingLimitsAccountFeature {
+    const ID: FeatureId = 2;
+
+    fn try_create(f: &Feature) -> Result<Self, ManyError> {
+        let argument = f.arguments();
+        if argument.len() != 1 {
+            return Err(ManyError::invalid_attribute_arguments());
+        }
+
+        let roles = match argument.get(0) {
+            Some(CborAny::Map(m)) => m,
+            _ => return Err(ManyError::invalid_attribute_arguments()),
+        };
+
+        let mut limits = BTreeMap::new();
+        for (role, symbols) in roles {
+            let role: Role = match role {
+                CborAny::String(r) => r
+                    .parse()
+                    .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                _ => return Err(ManyError::invalid_attribute_arguments()),
+            };
+            let symbols = match symbols {
+                CborAny::Map(m) => m,
+                _ => return Err(ManyError::invalid_attribute_arguments()),
+            };
+
+            let mut role_limits = BTreeMap::new();
+            for (symbol, limit) in symbols {
+                let symbol = match symbol {
+                    CborAny::Bytes(b) => Identity::from_bytes(b)
+                        .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+                let limit = match limit {
+                    CborAny::Map(m) => m,
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+                let amount = match limit.get(&CborAny::Int(0)) {
+                    Some(CborAny::Int(x)) => u64::try_from(*x)
+                        .map(TokenAmount::from)
+                        .map_err(|_| ManyError::invalid_attribute_arguments())?,
+                    Some(CborAny::Bytes(b)) => TokenAmount::from(b.clone()),
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+                let window_in_secs = match limit.get(&CborAny::Int(1)) {
+                    Some(CborAny::Int(x)) => {
+                        u64::try_from(*x).map_err(|_| ManyError::invalid_attribute_arguments())?
+                    }
+                    _ => return Err(ManyError::invalid_attribute_arguments()),
+                };
+
+                role_limits.insert(
+                    symbol,
+                    SpendingLimit {
+                        amount,
+                        window_in_secs,
+                    },
+                );
+            }
+            limits.insert(role, role_limits);
+        }
+
+        Ok(Self { limits })
+    }
+}
+
+impl super::FeatureInfo for SpendingLimitsAccountFeature {
+    fn as_feature(&self) -> Feature {
+        let mut roles = BTreeMap::<CborAny, CborAny>::new();
+        for (role, symbols) in &self.limits {
+            let mut map = BTreeMap::<CborAny, CborAny>::new();
+            for (symbol, limit) in symbols {
+                map.insert(
+                    CborAny::Bytes(symbol.to_vec()),
+                    CborAny::Map(BTreeMap::from([
+                        (CborAny::Int(0), CborAny::Bytes(limit.amount.to_vec())),
+                        (CborAny::Int(1), super::positive_int(limit.window_in_secs)),
+                    ])),
+                );
+            }
+            roles.insert(CborAny::String(role.to_string()), CborAny::Map(map));
+        }
+
+        Feature::with_id(Self::ID).with_argument(CborAny::Map(roles))
+    }
+
+    fn roles() -> BTreeSet<Role> {
+        BTreeSet::from([Role::CanLedgerTransact])
+    }
+}
+
+/// A single send counted against the limits.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Spending {
+    #[n(0)]
+    pub spender: Identity,
+
+    #[n(1)]
+    pub symbol: Symbol,
+
+    #[n(2)]
+    pub amount: TokenAmount,
+
+    #[n(3)]
+    pub time: Timestamp,
+}
+
+/// The sends of an account over the limits windows. This is useful as utility
+/// for keeping track of the spendings of an account in your backend.
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct SpendingHistory {
+    #[n(0)]
+    pub spendings: Vec<Spending>,
+}
+
+impl SpendingHistory {
+    pub fn record(
+        &mut self,
+        spender: &Identity,
+        symbol: &Symbol,
+        amount: TokenAmount,
+        now: Timestamp,
+    ) {
+        self.spendings.push(Spending {
+            spender: *spender,
+            symbol: *symbol,
+            amount,
+            time: now,
+        });
+    }
+
+    /// The total sent by an identity for a symbol since a time (inclusive).
+    pub fn spent(&self, spender: &Identity, symbol: &Symbol, since: Timestamp) -> TokenAmount {
+        self.spendings
+            .iter()
+            .filter(|s| &s.spender == spender && &s.symbol == symbol && s.time >= since)
+            .fold(TokenAmount::zero(), |acc, s| acc + s.amount.clone())
+    }
+
+    /// Remove the spendings older than a time.
+    pub fn prune(&mut self, before: Timestamp) {
+        self.spendings.retain(|s| s.time >= before);
+    }
+}
+
+/// How a send should be processed by a backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SendRoute {
+    /// The send is within limits (or the account is not limited) and can be
+    /// executed. Backends should record it in the [SpendingHistory].
+    Execute,
+
+    /// The send exceeds the limits of the sender and must go through multisig
+    /// approval. Backends submit it as the sender with
+    /// [MultisigEngine::submit_routed_send](super::multisig::MultisigEngine::submit_routed_send),
+    /// which records it once executed.
+    Multisig(SubmitTransactionArgs),
+}
+
+/// Decide how to process a send, according to the spending limits of the
+/// account it is sent from. Only sends whose `from` names an account are
+/// limited; a plain send from the sender's own identity is never routed. A send
+/// over the limits is routed to multisig approval if the account has the
+/// multisig feature, and refused otherwise.
+pub fn route_send(
+    accounts: &AccountMap,
+    history: &SpendingHistory,
+    sender: &Identity,
+    args: &SendArgs,
+    now: Timestamp,
+) -> Result<SendRoute, ManyError> {
+    let (from, account) = match args
+        .from
+        .and_then(|from| accounts.get(&from).map(|account| (from, account)))
+    {
+        Some(x) => x,
+        None => return Ok(SendRoute::Execute),
+    };
+    let feature = match account.feature::<SpendingLimitsAccountFeature>() {
+        Some(feature) => feature,
+        None => return Ok(SendRoute::Execute),
+    };
+
+    if feature.allows(account, history, sender, &args.symbol, &args.amount, now) {
+        return Ok(SendRoute::Execute);
+    }
+
+    if account.feature::<MultisigAccountFeature>().is_none() {
+        return Err(errors::spending_limit_exceeded(args.symbol));
+    }
+
+    Ok(SendRoute::Multisig(SubmitTransactionArgs {
+        memo: Some("Spending limit exceeded.".to_string()),
+        ..SubmitTransactionArgs::send(from, args.to, args.symbol, args.amount.clone())
+    }))
+}
+
+fn window_start(now: Timestamp, window_in_secs: u64) -> Timestamp {
+    Timestamp(
+        now.0
+            .checked_sub(Duration::from_secs(window_in_secs))
+            .unwrap_or(UNIX_EPOCH),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::module::account::features::{FeatureInfo, FeatureSet};
+    use crate::server::module::account::CreateArgs;
+    use crate::types::identity::testing::identity;
+
+    /// An account map with a single account, and its address.
+    fn accounts(features: FeatureSet) -> (AccountMap, Identity) {
+        let mut accounts = AccountMap::new(Identity::public_key_raw([0; 28]));
+        let (address, _) = accounts
+            .insert(Account::create(
+                &identity(0),
+                CreateArgs {
+                    description: None,
+                    roles: Some(BTreeMap::from([(
+                        identity(1),
+                        BTreeSet::from([Role::CanLedgerTransact]),
+                    )])),
+                    features,
+                },
+            ))
+            .unwrap();
+        (accounts, address)
+    }
+
+    fn send(from: Option<Identity>, amount: u64) -> SendArgs {
+        SendArgs {
+            from,
+            to: identity(2),
+            amount: TokenAmount::from(amount),
+            symbol: identity(100),
+        }
+    }
+
+    fn feature() -> SpendingLimitsAccountFeature {
+        SpendingLimitsAccountFeature::default().with_limit(
+            Role::CanLedgerTransact,
+            identity(100),
+            TokenAmount::from(100u64),
+            3600,
+        )
+    }
+
+    #[test]
+    fn feature_roundtrip() {
+        let feature =
+            feature().with_limit(Role::Owner, identity(101), TokenAmount::from(u128::MAX), 60);
+        assert_eq!(
+            SpendingLimitsAccountFeature::try_create(&feature.as_feature()).unwrap(),
+            feature
+        );
+        assert!(SpendingLimitsAccountFeature::try_create(
+            &Feature::with_id(2).with_argument(CborAny::Int(1))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let (accounts, address) = accounts(FeatureSet::from_iter([feature().as_feature()]));
+        let from = Some(address);
+        let mut history = SpendingHistory::default();
+        let now = Timestamp::new(10_000).unwrap();
+
+        // The owner has no limit.
+        assert_eq!(
+            route_send(&accounts, &history, &identity(0), &send(from, 1000), now),
+            Ok(SendRoute::Execute)
+        );
+        assert_eq!(
+            route_send(&accounts, &history, &identity(1), &send(from, 60), now),
+            Ok(SendRoute::Execute)
+        );
+
+        history.record(&identity(1), &identity(100), TokenAmount::from(60u64), now);
+        assert_eq!(
+            route_send(&accounts, &history, &identity(1), &send(from, 60), now),
+            Err(errors::spending_limit_exceeded(identity(100)))
+        );
+
+        // Spendings out of the window are not counted.
+        let later = Timestamp::new(10_000 + 3601).unwrap();
+        assert_eq!(
+            route_send(&accounts, &history, &identity(1), &send(from, 60), later),
+            Ok(SendRoute::Execute)
+        );
+        history.prune(window_start(later, feature().max_window_in_secs()));
+        assert!(history.spendings.is_empty());
+    }
+
+    #[test]
+    fn plain_sends_are_not_limited() {
+        let (accounts, _) = accounts(FeatureSet::from_iter([feature().as_feature()]));
+        let now = Timestamp::new(10_000).unwrap();
+
+        for from in [None, Some(identity(1))] {
+            assert_eq!(
+                route_send(
+                    &accounts,
+                    &SpendingHistory::default(),
+                    &identity(1),
+                    &send(from, 1000),
+                    now
+                ),
+                Ok(SendRoute::Execute)
+            );
+        }
+    }
+
+    #[test]
+    fn routed_to_multisig() {
+        let (accounts, address) = accounts(FeatureSet::from_iter([
+            feature().as_feature(),
+            MultisigAccountFeature::default().as_feature(),
+        ]));
+        let history = SpendingHistory::default();
+
+        match route_send(
+            &accounts,
+            &history,
+            &identity(1),
+            &send(Some(address), 101),
+            Timestamp::now(),
+        ) {
+            Ok(SendRoute::Multisig(args)) => {
+                assert_eq!(args.account, address);
+                assert_eq!(
+                    args.transaction,
+                    SubmitTransactionArgs::send(
+                        address,
+                        identity(2),
+                        identity(100),
+                        TokenAmount::from(101u64)
+                    )
+                    .transaction
+                );
+            }
+            _ => panic!("Send should be routed to multisig"),
+        }
+    }
+}
@@ -0,0 +1,685 @@
+use super::features::ledger::AccountLedger;
+use super::features::multisig::MultisigAccountFeature;
+use super::features::recovery::RecoveryAccountFeature;
+use super::features::spending_limits::SpendingLimitsAccountFeature;
+use super::features::{Feature, FeatureId, FeatureInfo, FeatureSet, TryCreateFeature};
+use super::*;
+use crate::server::module::events::EventSink;
+use crate::types::events::EventInfo;
+
+/// Persistence of the accounts of an [AccountStore].
+pub trait AccountStorage: Send {
+    /// Persist an account, created or modified.
+    fn put(&mut self, id: &Identity, account: &Account) -> Result<(), ManyError>;
+
+    /// Remove a deleted account.
+    fn remove(&mut self, id: &Identity) -> Result<(), ManyError>;
+
+    /// All the accounts stored, used to load the store.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(Identity, Account), ManyError>> + '_>;
+
+    /// The subresource ID of the next account, persisted so the IDs of deleted
+    /// accounts are not reused.
+    fn next_subresource_id(&self) -> Result<u32, ManyError>;
+
+    fn set_next_subresource_id(&mut self, next: u32) -> Result<(), ManyError>;
+}
+
+/// An [AccountStorage] that keeps everything in memory.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryAccountStorage {
+    accounts: BTreeMap<Identity, Account>,
+    next_subresource_id: u32,
+}
+
+impl AccountStorage for InMemoryAccountStorage {
+    fn put(&mut self, id: &Identity, account: &Account) -> Result<(), ManyError> {
+        self.accounts.insert(*id, account.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &Identity) -> Result<(), ManyError> {
+        self.accounts.remove(id);
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(Identity, Account), ManyError>> + '_> {
+        Box::new(self.accounts.iter().map(|(id, a)| Ok((*id, a.clone()))))
+    }
+
+    fn next_subresource_id(&self) -> Result<u32, ManyError> {
+        Ok(self.next_subresource_id)
+    }
+
+    fn set_next_subresource_id(&mut self, next: u32) -> Result<(), ManyError> {
+        self.next_subresource_id = next;
+        Ok(())
+    }
+}
+
+type BalancesFn = Box<dyn Fn(&Identity) -> Result<BTreeMap<Symbol, TokenAmount>, ManyError> + Send>;
+
+/// A reusable account backend built on [AccountMap]. Accounts are subresources
+/// of the server identity, and every change is persisted to an [AccountStorage]
+/// and emitted to an [EventSink]. It implements [AccountModuleBackend] directly.
+///
+/// Only owners can modify an account, and disabled accounts can only be enabled
+/// or deleted. The account identity is an owner of itself, so that multisig
+/// transactions executed as the account are allowed.
+pub struct AccountStore<S: AccountStorage, E: EventSink> {
+    storage: S,
+    events: E,
+    accounts: AccountMap,
+    balances: BalancesFn,
+    features: BTreeMap<FeatureId, FeatureRolesFn>,
+    time: Option<Timestamp>,
+}
+
+impl<S: AccountStorage, E: EventSink> AccountStore<S, E> {
+    /// Create a store from a storage, loading the accounts already in it. `id`
+    /// is the identity of the server.
+    pub fn load(id: Identity, storage: S, events: E) -> Result<Self, ManyError> {
+        let mut accounts =
+            AccountMap::new(id).with_next_subresource_id(storage.next_subresource_id()?);
+        for entry in storage.iter() {
+            let (id, account) = entry?;
+            accounts.insert_at(&id, account)?;
+        }
+
+        Ok(Self {
+            storage,
+            events,
+            accounts,
+            balances: Box::new(|_| Ok(BTreeMap::new())),
+            features: BTreeMap::new(),
+            time: None,
+        }
+        .with_feature::<AccountLedger>()
+        .with_feature::<MultisigAccountFeature>()
+        .with_feature::<SpendingLimitsAccountFeature>()
+        .with_feature::<RecoveryAccountFeature>())
+    }
+
+    /// Accept an application-defined feature on accounts. Roles can only be
+    /// given if they are declared by a feature of the account, including the
+    /// custom roles of [FeatureInfo::roles].
+    pub fn with_feature<F: TryCreateFeature + FeatureInfo>(mut self) -> Self {
+        self.features.insert(F::ID, roles_of::<F>);
+        self
+    }
+
+    /// Set how to get the ledger balances of an account, which must be zero
+    /// before it is deleted. By default accounts are considered empty.
+    pub fn with_balances<F>(mut self, balances: F) -> Self
+    where
+        F: Fn(&Identity) -> Result<BTreeMap<Symbol, TokenAmount>, ManyError> + Send + 'static,
+    {
+        self.balances = Box::new(balances);
+        self
+    }
+
+    /// Set the time used for role expirations and events, e.g. the time of the
+    /// current block. The system time is used if None.
+    pub fn set_time(&mut self, time: Option<Timestamp>) {
+        self.time = time;
+    }
+
+    pub fn accounts(&self) -> &AccountMap {
+        &self.accounts
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn events(&self) -> &E {
+        &self.events
+    }
+
+    fn now(&self) -> Timestamp {
+        self.time.unwrap_or_else(Timestamp::now)
+    }
+
+    fn emit(&mut self, content: EventInfo) -> Result<(), ManyError> {
+        let now = self.now();
+        self.events.emit(now, content)
+    }
+
+    fn get(&self, id: &Identity) -> Result<&Account, ManyError> {
+        self.accounts
+            .get(id)
+            .ok_or_else(|| errors::unknown_account(id))
+    }
+
+    fn get_mut(&mut self, id: &Identity) -> Result<&mut Account, ManyError> {
+        self.accounts
+            .get_mut(id)
+            .ok_or_else(|| errors::unknown_account(id))
+    }
+
+    /// Get an account that can be modified by the sender.
+    fn owned(&self, sender: &Identity, id: &Identity) -> Result<&Account, ManyError> {
+        let account = self.get(id)?;
+        if account.is_disabled() {
+            return Err(errors::account_disabled(id));
+        }
+        account.needs_role_at(sender, [Role::Owner], self.now())?;
+        Ok(account)
+    }
+
+    /// The roles an account with these features can give, validating the
+    /// features.
+    fn feature_roles(&self, features: &FeatureSet) -> Result<BTreeSet<Role>, ManyError> {
+        let mut roles = BTreeSet::from([Role::Owner]);
+        for feature in features.iter() {
+            let roles_of = self
+                .features
+                .get(&feature.id())
+                .ok_or_else(|| ManyError::attribute_not_found(feature.id()))?;
+            roles.extend(roles_of(feature)?);
+        }
+        Ok(roles)
+    }
+
+    fn save(&mut self, id: &Identity) -> Result<(), ManyError> {
+        let account = self
+            .accounts
+            .get(id)
+            .ok_or_else(|| errors::unknown_account(id))?;
+        self.storage.put(id, account)
+    }
+}
+
+/// Validate a feature and return the roles it declares.
+type FeatureRolesFn = fn(&Feature) -> Result<BTreeSet<Role>, ManyError>;
+
+fn roles_of<F: TryCreateFeature + FeatureInfo>(
+    feature: &Feature,
+) -> Result<BTreeSet<Role>, ManyError> {
+    F::try_create(feature)?;
+    Ok(F::roles())
+}
+
+fn check_roles<'a>(
+    allowed: &BTreeSet<Role>,
+    roles: impl IntoIterator<Item = &'a Role>,
+) -> Result<(), ManyError> {
+    for role in roles {
+        if !allowed.contains(role) {
+            return Err(errors::unknown_role(role));
+        }
+    }
+    Ok(())
+}
+
+impl<S: AccountStorage, E: EventSink> AccountModuleBackend for AccountStore<S, E> {
+    fn create(&mut self, sender: &Identity, args: CreateArgs) -> Result<CreateReturn, ManyError> {
+        if sender.is_anonymous() {
+            return Err(ManyError::sender_cannot_be_anonymous());
+        }
+        let allowed = self.feature_roles(&args.features)?;
+        check_roles(
+            &allowed,
+            args.roles.iter().flat_map(|r| r.values().flatten()),
+        )?;
+
+        let (id, _) = self.accounts.insert(Account::create(sender, args))?;
+        let account = self.get_mut(&id)?;
+        account.add_role(&id, Role::Owner);
+        let event = EventInfo::AccountCreate {
+            account: id,
+            description: account.description.clone(),
+            roles: account.roles.clone(),
+            features: account.features.clone(),
+        };
+
+        self.storage
+            .set_next_subresource_id(self.accounts.next_subresource_id())?;
+        self.save(&id)?;
+        self.emit(event)?;
+        Ok(CreateReturn { id })
+    }
+
+    fn set_description(
+        &mut self,
+        sender: &Identity,
+        args: SetDescriptionArgs,
+    ) -> Result<SetDescriptionReturn, ManyError> {
+        self.owned(sender, &args.account)?;
+        self.get_mut(&args.account)?
+            .set_description(Some(&args.description));
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountSetDescription {
+            account: args.account,
+            description: args.description,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn list_roles(
+        &self,
+        _sender: &Identity,
+        args: ListRolesArgs,
+    ) -> Result<ListRolesReturn, ManyError> {
+        let account = self.get(&args.account)?;
+        Ok(ListRolesReturn {
+            roles: self.feature_roles(&account.features)?,
+        })
+    }
+
+    fn get_roles(
+        &self,
+        _sender: &Identity,
+        args: GetRolesArgs,
+    ) -> Result<GetRolesReturn, ManyError> {
+        let account = self.get(&args.account)?;
+        let now = self.now();
+        let roles = args
+            .identities
+            .iter()
+            .map(|id| {
+                let roles = account
+                    .get_roles(id)
+                    .into_iter()
+                    .filter(|r| account.has_role_at(id, r.clone(), now))
+                    .collect();
+                (*id, roles)
+            })
+            .collect();
+        Ok(GetRolesReturn { roles })
+    }
+
+    fn add_roles(
+        &mut self,
+        sender: &Identity,
+        args: AddRolesArgs,
+    ) -> Result<AddRolesReturn, ManyError> {
+        let account = self.owned(sender, &args.account)?;
+        check_roles(
+            &self.feature_roles(&account.features)?,
+            args.roles.values().flatten(),
+        )?;
+
+        let account = self.get_mut(&args.account)?;
+        for (id, roles) in &args.roles {
+            for role in roles {
+                match args.expiration {
+                    Some(expiration) => account.add_role_until(id, role.clone(), expiration),
+                    None => account.add_role(id, role.clone()),
+                };
+            }
+        }
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountAddRoles {
+            account: args.account,
+            roles: args.roles,
+            expiration: args.expiration,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn remove_roles(
+        &mut self,
+        sender: &Identity,
+        args: RemoveRolesArgs,
+    ) -> Result<RemoveRolesReturn, ManyError> {
+        self.owned(sender, &args.account)?;
+        if args
+            .roles
+            .get(&args.account)
+            .map_or(false, |r| r.contains(&Role::Owner))
+        {
+            return Err(errors::account_must_own_itself());
+        }
+
+        let account = self.get_mut(&args.account)?;
+        for (id, roles) in &args.roles {
+            for role in roles {
+                account.remove_role(id, role.clone());
+            }
+        }
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountRemoveRoles {
+            account: args.account,
+            roles: args.roles,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn info(&self, _sender: &Identity, args: InfoArgs) -> Result<InfoReturn, ManyError> {
+        let account = self.get(&args.account)?;
+        Ok(InfoReturn {
+            description: account.description.clone(),
+            roles: account.roles.clone(),
+            features: account.features.clone(),
+            disabled: account.disabled.clone(),
+            role_expirations: account.role_expirations.clone(),
+        })
+    }
+
+    fn disable(
+        &mut self,
+        sender: &Identity,
+        args: DisableArgs,
+    ) -> Result<DisableReturn, ManyError> {
+        self.owned(sender, &args.account)?;
+        self.accounts.disable(&args.account, None)?;
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountDisable {
+            account: args.account,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn enable(&mut self, sender: &Identity, args: EnableArgs) -> Result<EnableReturn, ManyError> {
+        self.get(&args.account)?
+            .needs_role_at(sender, [Role::Owner], self.now())?;
+        self.accounts.enable(&args.account)?;
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountEnable {
+            account: args.account,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn delete(&mut self, sender: &Identity, args: DeleteArgs) -> Result<DeleteReturn, ManyError> {
+        self.get(&args.account)?
+            .needs_role_at(sender, [Role::Owner], self.now())?;
+        let balances = (self.balances)(&args.account)?;
+        self.accounts.delete(&args.account, balances.iter())?;
+        self.storage.remove(&args.account)?;
+        self.emit(EventInfo::AccountDelete {
+            account: args.account,
+        })?;
+        Ok(EmptyReturn)
+    }
+
+    fn add_features(
+        &mut self,
+        sender: &Identity,
+        args: AddFeaturesArgs,
+    ) -> Result<AddFeaturesReturn, ManyError> {
+        let account = self.owned(sender, &args.account)?;
+        let features: FeatureSet = account
+            .features
+            .iter()
+            .chain(args.features.iter())
+            .cloned()
+            .collect();
+        check_roles(
+            &self.feature_roles(&features)?,
+            args.roles.iter().flat_map(|r| r.values().flatten()),
+        )?;
+
+        let account = self.get_mut(&args.account)?;
+        for feature in args.features.iter() {
+            account.features.insert(feature.clone());
+        }
+        for (id, roles) in args.roles.iter().flatten() {
+            for role in roles {
+                account.add_role(id, role.clone());
+            }
+        }
+        self.save(&args.account)?;
+        self.emit(EventInfo::AccountAddFeatures {
+            account: args.account,
+            roles: args.roles.unwrap_or_default(),
+            features: args.features,
+        })?;
+        Ok(EmptyReturn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::identity::testing::identity;
+
+    type Store = AccountStore<InMemoryAccountStorage, Vec<EventInfo>>;
+
+    /// An application feature declaring a custom role.
+    struct Voting;
+
+    impl TryCreateFeature for Voting {
+        const ID: FeatureId = 1000;
+
+        fn try_create(_: &Feature) -> Result<Self, ManyError> {
+            Ok(Self)
+        }
+    }
+
+    impl FeatureInfo for Voting {
+        fn as_feature(&self) -> Feature {
+            Feature::with_id(Self::ID)
+        }
+
+        fn roles() -> BTreeSet<Role> {
+            BTreeSet::from([Role::custom("app:canVote").unwrap()])
+        }
+    }
+
+    fn store() -> Store {
+        AccountStore::load(
+            Identity::public_key_raw([0; 28]),
+            InMemoryAccountStorage::default(),
+            Vec::new(),
+        )
+        .unwrap()
+        .with_feature::<Voting>()
+    }
+
+    fn create_account(store: &mut Store) -> Identity {
+        store
+            .create(
+                &identity(1),
+                CreateArgs {
+                    description: Some("test".to_string()),
+                    roles: Some(BTreeMap::from([(
+                        identity(2),
+                        BTreeSet::from([Role::CanLedgerTransact]),
+                    )])),
+                    features: FeatureSet::from_iter([AccountLedger.as_feature()]),
+                },
+            )
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn create() {
+        let mut store = store();
+        assert_eq!(
+            store
+                .create(
+                    &Identity::anonymous(),
+                    CreateArgs {
+                        description: None,
+                        roles: None,
+                        features: FeatureSet::default(),
+                    },
+                )
+                .unwrap_err(),
+            ManyError::sender_cannot_be_anonymous()
+        );
+        // CanLedgerTransact needs the ledger feature.
+        assert_eq!(
+            store
+                .create(
+                    &identity(1),
+                    CreateArgs {
+                        description: None,
+                        roles: Some(BTreeMap::from([(
+                            identity(2),
+                            BTreeSet::from([Role::CanLedgerTransact]),
+                        )])),
+                        features: FeatureSet::default(),
+                    },
+                )
+                .unwrap_err(),
+            errors::unknown_role(Role::CanLedgerTransact)
+        );
+
+        let id = create_account(&mut store);
+        assert_eq!(id.subresource_id(), Some(0));
+        let account = store.accounts().get(&id).unwrap();
+        assert!(account.has_role(&identity(1), Role::Owner));
+        assert!(account.has_role(&id, Role::Owner));
+        assert!(matches!(
+            store.events().as_slice(),
+            [EventInfo::AccountCreate { account, .. }] if account == &id
+        ));
+    }
+
+    #[test]
+    fn owner_rules() {
+        let mut store = store();
+        let id = create_account(&mut store);
+
+        let description = |description: &str| SetDescriptionArgs {
+            account: id,
+            description: description.to_string(),
+        };
+        assert!(store
+            .set_description(&identity(2), description("other"))
+            .is_err());
+        store
+            .set_description(&identity(1), description("other"))
+            .unwrap();
+
+        assert_eq!(
+            store.remove_roles(
+                &identity(1),
+                RemoveRolesArgs {
+                    account: id,
+                    roles: BTreeMap::from([(id, BTreeSet::from([Role::Owner]))]),
+                },
+            ),
+            Err(errors::account_must_own_itself())
+        );
+
+        store
+            .disable(&identity(1), DisableArgs { account: id })
+            .unwrap();
+        assert_eq!(
+            store.set_description(&identity(1), description("disabled")),
+            Err(errors::account_disabled(id))
+        );
+        store
+            .enable(&identity(1), EnableArgs { account: id })
+            .unwrap();
+        assert_eq!(
+            store
+                .info(&identity(3), InfoArgs { account: id })
+                .unwrap()
+                .description,
+            Some("other".to_string())
+        );
+    }
+
+    #[test]
+    fn roles() {
+        let mut store = store();
+        let id = create_account(&mut store);
+        store.set_time(Some(Timestamp::new(1000).unwrap()));
+
+        let custom = Role::custom("app:canVote").unwrap();
+        let add_custom = |store: &mut Store| {
+            store.add_roles(
+                &identity(1),
+                AddRolesArgs {
+                    account: id,
+                    roles: BTreeMap::from([(identity(3), BTreeSet::from([custom.clone()]))]),
+                    expiration: Some(Timestamp::new(2000).unwrap()),
+                },
+            )
+        };
+        // The custom role is only declared by the Voting feature.
+        assert_eq!(
+            add_custom(&mut store),
+            Err(errors::unknown_role(custom.clone()))
+        );
+        assert_eq!(
+            store.add_features(
+                &identity(1),
+                AddFeaturesArgs {
+                    account: id,
+                    roles: None,
+                    features: FeatureSet::from_iter([Feature::with_id(1001)]),
+                },
+            ),
+            Err(ManyError::attribute_not_found(1001))
+        );
+        store
+            .add_features(
+                &identity(1),
+                AddFeaturesArgs {
+                    account: id,
+                    roles: None,
+                    features: FeatureSet::from_iter([Voting.as_feature()]),
+                },
+            )
+            .unwrap();
+        add_custom(&mut store).unwrap();
+        assert_eq!(
+            store
+                .list_roles(&identity(3), ListRolesArgs { account: id })
+                .unwrap()
+                .roles,
+            BTreeSet::from([Role::Owner, Role::CanLedgerTransact, custom.clone()])
+        );
+
+        let get_roles = |store: &Store| {
+            store
+                .get_roles(
+                    &identity(3),
+                    GetRolesArgs {
+                        account: id,
+                        identities: vec![identity(3)].into(),
+                    },
+                )
+                .unwrap()
+                .roles
+        };
+        assert_eq!(
+            get_roles(&store),
+            BTreeMap::from([(identity(3), BTreeSet::from([custom]))])
+        );
+
+        store.set_time(Some(Timestamp::new(2000).unwrap()));
+        assert_eq!(
+            get_roles(&store),
+            BTreeMap::from([(identity(3), BTreeSet::new())])
+        );
+    }
+
+    #[test]
+    fn delete_and_reload() {
+        let mut store = store()
+            .with_balances(|_| Ok(BTreeMap::from([(identity(100), TokenAmount::from(1u64))])));
+        let first = create_account(&mut store);
+        let second = create_account(&mut store);
+
+        store
+            .disable(&identity(1), DisableArgs { account: first })
+            .unwrap();
+        assert_eq!(
+            store.delete(&identity(1), DeleteArgs { account: first }),
+            Err(errors::account_has_balance(identity(100)))
+        );
+
+        let mut store = store.with_balances(|_| Ok(BTreeMap::new()));
+        store
+            .delete(&identity(1), DeleteArgs { account: first })
+            .unwrap();
+
+        let mut store = Store::load(
+            Identity::public_key_raw([0; 28]),
+            store.storage().clone(),
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(!store.accounts().contains(&first));
+        assert!(store.accounts().contains(&second));
+        assert_eq!(create_account(&mut store).subresource_id(), Some(2));
+    }
+}
@@ -13,6 +13,7 @@ pub mod either;
 pub mod events;
 pub mod identity;
 pub mod ledger;
+pub mod proof;
 
 pub use either::Either;
 
@@ -156,6 +157,21 @@ impl<C> Encode<C> for Timestamp {
     }
 }
 
+/// Timestamps are serialized as seconds since the UNIX epoch.
+impl serde::Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u64(
+            self.0
+                .duration_since(UNIX_EPOCH)
+                .map_err(serde::ser::Error::custom)?
+                .as_secs(),
+        )
+    }
+}
+
 impl<'b, C> Decode<'b, C> for Timestamp {
     fn decode(d: &mut Decoder<'b>, _: &mut C) -> Result<Self, decode::Error> {
         if d.tag()? != Tag::Timestamp {
@@ -1,4 +1,7 @@
-use crate::types::Timestamp;
+use crate::message::RequestMessage;
+use crate::types::{CborRange, Timestamp, VecOrSingle};
+use crate::Identity;
+use coset::{CborSerializable, CoseSign1};
 use minicbor::encode::{Error, Write};
 use minicbor::{decode, Decode, Decoder, Encode, Encoder};
 
@@ -87,6 +90,18 @@ pub struct Transaction {
     pub content: Option<Vec<u8>>,
 }
 
+impl Transaction {
+    /// The request of this transaction, if its content is a request message or
+    /// an envelope containing one. The envelope's signature is not verified.
+    pub fn request(&self) -> Option<RequestMessage> {
+        let content = self.content.as_ref()?;
+        match CoseSign1::from_slice(content) {
+            Ok(sign1) => RequestMessage::from_bytes(sign1.payload.as_ref()?).ok(),
+            Err(_) => RequestMessage::from_bytes(content).ok(),
+        }
+    }
+}
+
 #[derive(Clone, Decode, Encode)]
 #[cbor(map)]
 pub struct Block {
@@ -109,6 +124,76 @@ pub struct Block {
     pub txs: Vec<Transaction>,
 }
 
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct BlockFilter {
+    #[n(0)]
+    pub height_range: Option<CborRange<u64>>,
+
+    #[n(1)]
+    pub date_range: Option<CborRange<Timestamp>>,
+}
+
+impl BlockFilter {
+    /// Whether a block is selected by all the criteria of this filter.
+    pub fn matches(&self, block: &Block) -> bool {
+        self.height_range
+            .as_ref()
+            .map_or(true, |range| range.contains(&block.id.height))
+            && self
+                .date_range
+                .as_ref()
+                .map_or(true, |range| range.contains(&block.timestamp))
+    }
+}
+
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct TransactionFilter {
+    #[n(0)]
+    pub height_range: Option<CborRange<u64>>,
+
+    /// Select transactions sent by one of these identities.
+    #[n(1)]
+    pub sender: Option<VecOrSingle<Identity>>,
+
+    /// Select transactions calling one of these methods.
+    #[n(2)]
+    pub method: Option<VecOrSingle<String>>,
+}
+
+impl TransactionFilter {
+    /// Whether a transaction of a block at this height is selected by all the
+    /// criteria of this filter. Transactions whose content is not a request
+    /// never match a sender or method.
+    pub fn matches(&self, height: u64, txn: &Transaction) -> bool {
+        if !self
+            .height_range
+            .as_ref()
+            .map_or(true, |range| range.contains(&height))
+        {
+            return false;
+        }
+        if self.sender.is_none() && self.method.is_none() {
+            return true;
+        }
+
+        match txn.request() {
+            Some(request) => {
+                let from = request.from.unwrap_or_default();
+                self.sender
+                    .as_ref()
+                    .map_or(true, |ids| ids.iter().any(|id| *id == from))
+                    && self
+                        .method
+                        .as_ref()
+                        .map_or(true, |methods| methods.iter().any(|m| *m == request.method))
+            }
+            None => false,
+        }
+    }
+}
+
 // TODO: This doesn't look right according to the spec
 // single-transaction-query =
 //     ; A transaction hash.
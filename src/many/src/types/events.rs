@@ -6,6 +6,8 @@ use crate::Identity;
 use minicbor::bytes::ByteVec;
 use minicbor::{encode, Decode, Decoder, Encode, Encoder};
 use num_bigint::BigUint;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Debug, PartialOrd, PartialEq, Ord, Eq)]
@@ -126,6 +128,363 @@ pub struct EventFilter {
 
     #[n(4)]
     pub date_range: Option<CborRange<Timestamp>>,
+
+    /// Select events about key-value store keys starting with this prefix.
+    #[n(5)]
+    pub key_prefix: Option<ByteVec>,
+
+    /// Select multisig submissions of transactions of these kinds, at any depth.
+    #[n(6)]
+    pub transaction_kind: Option<VecOrSingle<EventKind>>,
+
+    /// Select events matching at least one of these criteria.
+    #[n(7)]
+    pub any_of: Option<EventCriteria>,
+
+    /// Exclude events matching any of these criteria.
+    #[n(8)]
+    pub none_of: Option<EventCriteria>,
+}
+
+impl EventFilter {
+    /// Whether an event is selected by all the criteria of this filter.
+    pub fn matches(&self, log: &EventLog) -> bool {
+        self.account
+            .as_ref()
+            .map_or(true, |ids| matches_account(ids, log))
+            && self
+                .kind
+                .as_ref()
+                .map_or(true, |kinds| matches_kind(kinds, log))
+            && self
+                .symbol
+                .as_ref()
+                .map_or(true, |symbols| matches_symbol(symbols, log))
+            && self
+                .id_range
+                .as_ref()
+                .map_or(true, |range| range.contains(&log.id))
+            && self
+                .date_range
+                .as_ref()
+                .map_or(true, |range| range.contains(&log.time))
+            && self.key_prefix.as_ref().map_or(true, |prefix| {
+                log.key().map_or(false, |key| key.starts_with(prefix))
+            })
+            && self
+                .transaction_kind
+                .as_ref()
+                .map_or(true, |kinds| matches_transaction_kind(kinds, log))
+            && self
+                .any_of
+                .as_ref()
+                .map_or(true, |criteria| criteria.any(log).unwrap_or(true))
+            && self
+                .none_of
+                .as_ref()
+                .map_or(true, |criteria| !criteria.any(log).unwrap_or(false))
+    }
+}
+
+/// Criteria on events that can be combined with "any-of" or negated in an
+/// [EventFilter]. Each criterion has the same meaning as in the filter.
+#[derive(Clone, Debug, Default, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct EventCriteria {
+    #[n(0)]
+    pub account: Option<VecOrSingle<Identity>>,
+
+    #[n(1)]
+    pub kind: Option<VecOrSingle<EventKind>>,
+
+    #[n(2)]
+    pub symbol: Option<VecOrSingle<Identity>>,
+
+    #[n(3)]
+    pub transaction_kind: Option<VecOrSingle<EventKind>>,
+}
+
+impl EventCriteria {
+    /// Whether any of the criteria set matches the event, or None if no
+    /// criterion is set.
+    pub fn any(&self, log: &EventLog) -> Option<bool> {
+        let results = [
+            self.account.as_ref().map(|ids| matches_account(ids, log)),
+            self.kind.as_ref().map(|kinds| matches_kind(kinds, log)),
+            self.symbol
+                .as_ref()
+                .map(|symbols| matches_symbol(symbols, log)),
+            self.transaction_kind
+                .as_ref()
+                .map(|kinds| matches_transaction_kind(kinds, log)),
+        ];
+        results
+            .iter()
+            .flatten()
+            .fold(None, |acc, m| Some(acc.unwrap_or(false) || *m))
+    }
+}
+
+fn matches_account(ids: &VecOrSingle<Identity>, log: &EventLog) -> bool {
+    ids.iter().any(|id| log.is_about(id))
+}
+
+fn matches_kind(kinds: &VecOrSingle<EventKind>, log: &EventLog) -> bool {
+    kinds.iter().any(|k| *k == log.kind())
+}
+
+fn matches_symbol(symbols: &VecOrSingle<Identity>, log: &EventLog) -> bool {
+    log.symbol()
+        .map_or(false, |s| symbols.iter().any(|symbol| symbol == s))
+}
+
+fn matches_transaction_kind(kinds: &VecOrSingle<EventKind>, log: &EventLog) -> bool {
+    match &log.content {
+        EventInfo::AccountMultisigSubmit { transaction, .. } => transaction.has_kind(kinds),
+        _ => false,
+    }
+}
+
+/// Information about the arguments of a method that can be submitted as a
+/// multisig transaction, so filters can look inside transactions.
+pub trait TransactionInfo {
+    fn symbol(&self) -> Option<&Symbol> {
+        None
+    }
+
+    /// All the identities the arguments refer to.
+    fn addresses(&self) -> BTreeSet<Identity> {
+        BTreeSet::new()
+    }
+
+    fn is_about(&self, id: &Identity) -> bool {
+        self.addresses().contains(id)
+    }
+
+    fn key(&self) -> Option<&ByteVec> {
+        None
+    }
+}
+
+impl TransactionInfo for module::ledger::SendArgs {
+    fn symbol(&self) -> Option<&Symbol> {
+        Some(&self.symbol)
+    }
+
+    fn addresses(&self) -> BTreeSet<Identity> {
+        self.from.into_iter().chain([self.to]).collect()
+    }
+}
+
+impl TransactionInfo for module::ledger::ScheduleSendArgs {
+    fn symbol(&self) -> Option<&Symbol> {
+        Some(&self.symbol)
+    }
+
+    fn addresses(&self) -> BTreeSet<Identity> {
+        self.from.into_iter().chain([self.to]).collect()
+    }
+}
+
+impl TransactionInfo for module::escrow::LockArgs {
+    fn symbol(&self) -> Option<&Symbol> {
+        Some(&self.symbol)
+    }
+
+    fn addresses(&self) -> BTreeSet<Identity> {
+        self.from
+            .into_iter()
+            .chain([self.to, self.arbiter])
+            .collect()
+    }
+}
+
+impl TransactionInfo for module::account::features::multisig::SubmitTransactionArgs {
+    fn symbol(&self) -> Option<&Symbol> {
+        self.transaction.symbol()
+    }
+
+    fn addresses(&self) -> BTreeSet<Identity> {
+        let mut addresses = self.transaction.addresses();
+        addresses.insert(self.account);
+        addresses
+    }
+
+    fn key(&self) -> Option<&ByteVec> {
+        self.transaction.key()
+    }
+}
+
+impl TransactionInfo for module::kvstore::PutArgs {
+    fn key(&self) -> Option<&ByteVec> {
+        Some(&self.key)
+    }
+}
+
+impl TransactionInfo for module::kvstore::DeleteArgs {
+    fn key(&self) -> Option<&ByteVec> {
+        Some(&self.key)
+    }
+}
+
+macro_rules! impl_account_transaction_info {
+    ( $( $t: ty ),* $(,)? ) => {
+        $(
+        impl TransactionInfo for $t {
+            fn addresses(&self) -> BTreeSet<Identity> {
+                BTreeSet::from([self.account])
+            }
+        }
+        )*
+    };
+}
+
+impl_account_transaction_info!(
+    module::account::SetDescriptionArgs,
+    module::account::AddRolesArgs,
+    module::account::RemoveRolesArgs,
+    module::account::DisableArgs,
+    module::account::EnableArgs,
+    module::account::DeleteArgs,
+    module::account::AddFeaturesArgs,
+    module::account::features::multisig::SetDefaultsArgs,
+);
+
+// These arguments only refer to other objects by token.
+impl TransactionInfo for module::ledger::CancelScheduledSendArgs {}
+impl TransactionInfo for module::account::CreateArgs {}
+impl TransactionInfo for module::account::features::multisig::ApproveArgs {}
+impl TransactionInfo for module::account::features::multisig::RevokeArgs {}
+impl TransactionInfo for module::account::features::multisig::ExecuteArgs {}
+impl TransactionInfo for module::account::features::multisig::WithdrawArgs {}
+impl TransactionInfo for module::escrow::ReleaseArgs {}
+impl TransactionInfo for module::escrow::RefundArgs {}
+
+// Generic calls are opaque.
+impl TransactionInfo for module::account::features::multisig::CallArgs {}
+
+/// Serialization of event fields with serde, in a human-readable form. Fields
+/// without a natural representation are serialized as the hexadecimal of their
+/// CBOR encoding.
+trait EventField {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+
+    /// The value of a field absent from an encoded event, or None if the field
+    /// is required. Optional fields can be added to an event after it was
+    /// defined, and are absent from events encoded before.
+    fn missing() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// Wrapper to serialize an [EventField] with serde.
+struct Field<'a, T: ?Sized>(&'a T);
+
+impl<T: EventField + ?Sized> Serialize for Field<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_field(serializer)
+    }
+}
+
+macro_rules! impl_event_field_serde {
+    ( $( $t: ty ),* $(,)? ) => {
+        $(
+        impl EventField for $t {
+            fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                Serialize::serialize(self, serializer)
+            }
+        }
+        )*
+    };
+}
+
+macro_rules! impl_event_field_display {
+    ( $( $t: ty ),* $(,)? ) => {
+        $(
+        impl EventField for $t {
+            fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+        )*
+    };
+}
+
+macro_rules! impl_event_field_cbor {
+    ( $( $t: ty ),* $(,)? ) => {
+        $(
+        impl EventField for $t {
+            fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let bytes = minicbor::to_vec(self).map_err(serde::ser::Error::custom)?;
+                serializer.serialize_str(&hex::encode(bytes))
+            }
+        }
+        )*
+    };
+}
+
+impl_event_field_serde!(Identity, TokenAmount, Timestamp, String, u64, bool);
+impl_event_field_display!(EventKind, module::account::Role);
+impl_event_field_cbor!(
+    ResponseMessage,
+    module::ledger::Schedule,
+    module::account::features::FeatureSet,
+);
+
+impl EventField for ByteVec {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.as_slice()))
+    }
+}
+
+impl EventField for EventId {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self))
+    }
+}
+
+impl EventField for AccountMultisigTransaction {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = minicbor::to_vec(self).map_err(serde::ser::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("kind", &Field(&self.kind()))?;
+        map.serialize_entry("cbor", &hex::encode(bytes))?;
+        map.end()
+    }
+}
+
+impl<T: EventField> EventField for Option<T> {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(value) => value.serialize_field(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: EventField + ?Sized> EventField for Box<T> {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_ref().serialize_field(serializer)
+    }
+}
+
+impl<T: EventField> EventField for BTreeSet<T> {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(Field))
+    }
+}
+
+impl<K: EventField, V: EventField> EventField for BTreeMap<K, V> {
+    fn serialize_field<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter().map(|(k, v)| (Field(k), Field(v))))
+    }
 }
 
 macro_rules! define_event_kind {
@@ -230,6 +589,83 @@ macro_rules! define_event_info_symbol {
     };
 }
 
+macro_rules! define_event_info_key {
+    (@pick_key) => {};
+    (@pick_key $name: ident key $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
+        return Some(& $name)
+    };
+    (@pick_key $name_: ident $( $tag_: ident )*, $( $name: ident $( $tag: ident )*, )* ) => {
+        define_event_info_key!(@pick_key $( $name $( $tag )*, )* )
+    };
+
+    (@inner) => {};
+    (@inner $name: ident inner $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
+        if let Some(k) = $name .key() {
+            return Some(k);
+        }
+    };
+    (@inner $name_: ident $( $tag_: ident )*, $( $name: ident $( $tag: ident )*, )* ) => {
+        define_event_info_key!(@inner $( $name $( $tag )*, )* )
+    };
+
+    ( $( $name: ident { $( $fname: ident $( $tag: ident )* , )* } )* ) => {
+        pub fn key(&self) -> Option<&ByteVec> {
+            match self {
+                $( EventInfo :: $name {
+                    $( $fname, )*
+                } => {
+                    // Remove warnings.
+                    $( let _ = $fname; )*
+                    define_event_info_key!(@pick_key $( $fname $( $tag )*, )* );
+
+                    // Inner transactions might be about a key.
+                    define_event_info_key!(@inner $( $fname $( $tag )*, )*);
+                } )*
+            }
+
+            None
+        }
+    };
+}
+
+macro_rules! define_event_info_addresses {
+    (@add $set: ident) => {};
+    (@add $set: ident $name: ident id $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
+        $set.insert(*$name);
+        define_event_info_addresses!(@add $set $( $name_ $( $tag_ )*, )* )
+    };
+    (@add $set: ident $name: ident id_non_null $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
+        if let Some(id) = $name {
+            $set.insert(*id);
+        }
+        define_event_info_addresses!(@add $set $( $name_ $( $tag_ )*, )* )
+    };
+    (@add $set: ident $name: ident inner $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
+        $set.extend($name.addresses());
+        define_event_info_addresses!(@add $set $( $name_ $( $tag_ )*, )* )
+    };
+    (@add $set: ident $name_: ident $( $tag_: ident )*, $( $name: ident $( $tag: ident )*, )* ) => {
+        define_event_info_addresses!(@add $set $( $name $( $tag )*, )* )
+    };
+
+    ( $( $name: ident { $( $fname: ident $( $tag: ident )* , )* } )* ) => {
+        /// All the identities this event is about, including in inner transactions.
+        pub fn addresses(&self) -> BTreeSet<Identity> {
+            let mut addresses = BTreeSet::new();
+            match self {
+                $( EventInfo :: $name {
+                    $( $fname, )*
+                } => {
+                    // Remove warnings.
+                    $( let _ = $fname; )*
+                    define_event_info_addresses!(@add addresses $( $fname $( $tag )*, )* );
+                } )*
+            }
+            addresses
+        }
+    };
+}
+
 macro_rules! define_event_info_is_about {
     (@check_id $id: ident) => {};
     (@check_id $id: ident $name: ident id $(,)? $( $name_: ident $( $tag_: ident )*, )* ) => {
@@ -290,6 +726,8 @@ macro_rules! define_event_info {
 
         impl EventInfo {
             define_event_info_symbol!( $( $name { $( $fname $( $( $tag )* )?, )* } )* );
+            define_event_info_key!( $( $name { $( $fname $( $( $tag )* )?, )* } )* );
+            define_event_info_addresses!( $( $name { $( $fname $( $( $tag )* )?, )* } )* );
             define_event_info_is_about!( $( $name { $( $fname $( $( $tag )* )?, )* } )* );
         }
 
@@ -306,6 +744,19 @@ macro_rules! replace_expr {
 
 macro_rules! encode_event_info {
     ( $( $sname: ident { $( $idx: literal => $name: ident : $type: ty, )* }, )* ) => {
+        impl Serialize for EventInfo {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    $(  EventInfo :: $sname { $( $name, )* } => {
+                            let mut map = serializer.serialize_map(Some(1 $(+ replace_expr!($idx 1))* ))?;
+                            map.serialize_entry("kind", &Field(&EventKind :: $sname))?;
+                            $( map.serialize_entry(stringify!($name), &Field($name))?; )*
+                            map.end()
+                        }, )*
+                }
+            }
+        }
+
         impl<C> Encode<C> for EventInfo {
             fn encode<W: encode::Write>(
                 &self,
@@ -349,7 +800,11 @@ macro_rules! encode_event_info {
                             len -= 1;
                         }
 
-                        $( let $name: $type = $name.ok_or(minicbor::decode::Error::missing_value($idx))?; )*
+                        $( let $name: $type = match $name {
+                            Some(value) => value,
+                            None => <$type as EventField>::missing()
+                                .ok_or(minicbor::decode::Error::missing_value($idx))?,
+                        }; )*
 
                         Ok(EventInfo :: $sname {
                             $( $name, )*
@@ -371,14 +826,71 @@ macro_rules! define_multisig_event {
         }
 
         impl AccountMultisigTransaction {
+            pub fn kind(&self) -> EventKind {
+                match self {
+                    $(
+                    $( AccountMultisigTransaction :: $name(_) => {
+                        let _: $arg;
+                        EventKind :: $name
+                    }, )?
+                    )*
+                }
+            }
+
             pub fn symbol(&self) -> Option<&Identity> {
-                // TODO: implement this for recursively checking if inner infos
-                // has a symbol defined.
-                None
+                match self {
+                    $(
+                    $( AccountMultisigTransaction :: $name(arg) => {
+                        let _: $arg;
+                        TransactionInfo::symbol(arg)
+                    }, )?
+                    )*
+                }
+            }
+
+            pub fn is_about(&self, id: &Identity) -> bool {
+                match self {
+                    $(
+                    $( AccountMultisigTransaction :: $name(arg) => {
+                        let _: $arg;
+                        TransactionInfo::is_about(arg, id)
+                    }, )?
+                    )*
+                }
+            }
+
+            pub fn addresses(&self) -> BTreeSet<Identity> {
+                match self {
+                    $(
+                    $( AccountMultisigTransaction :: $name(arg) => {
+                        let _: $arg;
+                        TransactionInfo::addresses(arg)
+                    }, )?
+                    )*
+                }
+            }
+
+            pub fn key(&self) -> Option<&ByteVec> {
+                match self {
+                    $(
+                    $( AccountMultisigTransaction :: $name(arg) => {
+                        let _: $arg;
+                        TransactionInfo::key(arg)
+                    }, )?
+                    )*
+                }
             }
 
-            pub fn is_about(&self, _id: &Identity) -> bool {
-                false
+            /// Whether this transaction, or a transaction it submits, is of one
+            /// of the kinds.
+            pub fn has_kind(&self, kinds: &VecOrSingle<EventKind>) -> bool {
+                kinds.iter().any(|k| *k == self.kind())
+                    || match self {
+                        AccountMultisigTransaction::AccountMultisigSubmit(args) => {
+                            args.transaction.has_kind(kinds)
+                        }
+                        _ => false,
+                    }
             }
         }
 
@@ -452,6 +964,41 @@ define_event! {
         3     | symbol:                 Symbol                                  [ symbol ],
         4     | amount:                 TokenAmount,
     },
+    [6, 1]      ScheduleSend (module::ledger::ScheduleSendArgs) {
+        1     | token:                  ByteVec,
+        2     | sender:                 Identity                                [ id ],
+        3     | from:                   Identity                                [ id ],
+        4     | to:                     Identity                                [ id ],
+        5     | symbol:                 Symbol                                  [ symbol ],
+        6     | amount:                 TokenAmount,
+        7     | schedule:               module::ledger::Schedule,
+    },
+    [6, 2]      ScheduleCancel (module::ledger::CancelScheduledSendArgs) {
+        1     | token:                  ByteVec,
+        2     | canceller:              Identity                                [ id ],
+        3     | from:                   Identity                                [ id ],
+    },
+    [6, 3]      ScheduleExecute {
+        1     | token:                  ByteVec,
+        2     | from:                   Identity                                [ id ],
+        3     | to:                     Identity                                [ id ],
+        4     | symbol:                 Symbol                                  [ symbol ],
+        5     | amount:                 TokenAmount,
+    },
+    [7, 0]      KvStorePut (module::kvstore::PutArgs) {
+        1     | key:                    ByteVec                                 [ key ],
+        2     | owner:                  Identity                                [ id ],
+        3     | value_hash:             ByteVec,
+    },
+    [7, 1]      KvStoreDelete (module::kvstore::DeleteArgs) {
+        1     | key:                    ByteVec                                 [ key ],
+        2     | owner:                  Identity                                [ id ],
+    },
+    [7, 2]      KvStoreExpired {
+        1     | key:                    ByteVec                                 [ key ],
+        2     | owner:                  Identity                                [ id ],
+        3     | time:                   Timestamp,
+    },
     [9, 0]      AccountCreate (module::account::CreateArgs) {
         1     | account:                Identity                                [ id ],
         2     | description:            Option<String>,
@@ -465,6 +1012,7 @@ define_event! {
     [9, 2]      AccountAddRoles (module::account::AddRolesArgs) {
         1     | account:                Identity                                [ id ],
         2     | roles:                  BTreeMap<Identity, BTreeSet<module::account::Role>>,
+        3     | expiration:             Option<Timestamp>,
     },
     [9, 3]      AccountRemoveRoles (module::account::RemoveRolesArgs) {
         1     | account:                Identity                                [ id ],
@@ -478,6 +1026,12 @@ define_event! {
         2     | roles:                  BTreeMap<Identity, BTreeSet<module::account::Role>>,
         3     | features:               module::account::features::FeatureSet,
     },
+    [9, 6]      AccountEnable (module::account::EnableArgs) {
+        1     | account:                Identity                                [ id ],
+    },
+    [9, 7]      AccountDelete (module::account::DeleteArgs) {
+        1     | account:                Identity                                [ id ],
+    },
     [9, 1, 0]   AccountMultisigSubmit (module::account::features::multisig::SubmitTransactionArgs) {
         1     | submitter:              Identity                                [ id ],
         2     | account:                Identity                                [ id ],
@@ -516,16 +1070,112 @@ define_event! {
         3     | threshold:              Option<u64>,
         4     | timeout_in_secs:        Option<u64>,
         5     | execute_automatically:  Option<bool>,
+        6     | weights:                Option<BTreeMap<Identity, u64>>,
+        7     | kind_thresholds:        Option<BTreeMap<EventKind, u64>>,
     },
     [9, 1, 6]   AccountMultisigExpired {
         1     | account:                Identity                                [ id ],
         2     | token:                  ByteVec,
         3     | time:                   Timestamp,
     },
+    [9, 1, 7]   AccountMultisigCall (module::account::features::multisig::CallArgs) {
+        1     | account:                Identity                                [ id ],
+        2     | method:                 String,
+        3     | argument:               ByteVec,
+    },
+    [9, 3, 0]   AccountRecoveryInitiate {
+        1     | account:                Identity                                [ id ],
+        2     | token:                  ByteVec,
+        3     | guardian:               Identity                                [ id ],
+        4     | new_owner:              Identity                                [ id ],
+        5     | finalizable_at:         Timestamp,
+    },
+    [9, 3, 1]   AccountRecoveryApprove {
+        1     | account:                Identity                                [ id ],
+        2     | token:                  ByteVec,
+        3     | guardian:               Identity                                [ id ],
+    },
+    [9, 3, 2]   AccountRecoveryCancel {
+        1     | account:                Identity                                [ id ],
+        2     | token:                  ByteVec,
+        3     | canceller:              Identity                                [ id ],
+    },
+    [9, 3, 3]   AccountRecoveryFinalize {
+        1     | account:                Identity                                [ id ],
+        2     | token:                  ByteVec,
+        3     | new_owner:              Identity                                [ id ],
+        4     | previous_owners:        BTreeSet<Identity>,
+    },
+    [10, 0]     EscrowLock (module::escrow::LockArgs) {
+        1     | token:                  ByteVec,
+        2     | from:                   Identity                                [ id ],
+        3     | to:                     Identity                                [ id ],
+        4     | arbiter:                Identity                                [ id ],
+        5     | symbol:                 Symbol                                  [ symbol ],
+        6     | amount:                 TokenAmount,
+        7     | expiration:             Timestamp,
+        8     | memo:                   Option<String>,
+    },
+    [10, 1]     EscrowRelease (module::escrow::ReleaseArgs) {
+        1     | token:                  ByteVec,
+        2     | arbiter:                Identity                                [ id ],
+        3     | to:                     Identity                                [ id ],
+        4     | symbol:                 Symbol                                  [ symbol ],
+        5     | amount:                 TokenAmount,
+    },
+    [10, 2]     EscrowRefund (module::escrow::RefundArgs) {
+        1     | token:                  ByteVec,
+        2     | arbiter:                Identity                                [ id ],
+        3     | from:                   Identity                                [ id ],
+        4     | symbol:                 Symbol                                  [ symbol ],
+        5     | amount:                 TokenAmount,
+    },
+    [10, 3]     EscrowExpired {
+        1     | token:                  ByteVec,
+        2     | from:                   Identity                                [ id ],
+        3     | symbol:                 Symbol                                  [ symbol ],
+        4     | amount:                 TokenAmount,
+        5     | time:                   Timestamp,
+    },
+}
+
+impl EventKind {
+    /// The kind of the event emitted by a method, if any.
+    pub fn from_method(method: &str) -> Option<Self> {
+        Some(match method {
+            "ledger.send" => EventKind::Send,
+            "ledger.scheduleSend" => EventKind::ScheduleSend,
+            "ledger.cancelScheduledSend" => EventKind::ScheduleCancel,
+            "kvstore.put" => EventKind::KvStorePut,
+            "kvstore.delete" => EventKind::KvStoreDelete,
+            "account.create" => EventKind::AccountCreate,
+            "account.setDescription" => EventKind::AccountSetDescription,
+            "account.addRoles" => EventKind::AccountAddRoles,
+            "account.removeRoles" => EventKind::AccountRemoveRoles,
+            "account.disable" => EventKind::AccountDisable,
+            "account.addFeatures" => EventKind::AccountAddFeatures,
+            "account.enable" => EventKind::AccountEnable,
+            "account.delete" => EventKind::AccountDelete,
+            "account.multisigSubmitTransaction" => EventKind::AccountMultisigSubmit,
+            "account.multisigApprove" => EventKind::AccountMultisigApprove,
+            "account.multisigRevoke" => EventKind::AccountMultisigRevoke,
+            "account.multisigExecute" => EventKind::AccountMultisigExecute,
+            "account.multisigWithdraw" => EventKind::AccountMultisigWithdraw,
+            "account.multisigSetDefaults" => EventKind::AccountMultisigSetDefaults,
+            "account.recoveryInitiate" => EventKind::AccountRecoveryInitiate,
+            "account.recoveryApprove" => EventKind::AccountRecoveryApprove,
+            "account.recoveryCancel" => EventKind::AccountRecoveryCancel,
+            "account.recoveryFinalize" => EventKind::AccountRecoveryFinalize,
+            "escrow.lock" => EventKind::EscrowLock,
+            "escrow.release" => EventKind::EscrowRelease,
+            "escrow.refund" => EventKind::EscrowRefund,
+            _ => return None,
+        })
+    }
 }
 
 /// An Event that happened on the server and that is part of the log.
-#[derive(Debug, Encode, Decode)]
+#[derive(Clone, Debug, Encode, Decode)]
 #[cbor(map)]
 pub struct EventLog {
     #[n(0)]
@@ -538,6 +1188,16 @@ pub struct EventLog {
     pub content: EventInfo,
 }
 
+impl Serialize for EventLog {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("id", &Field(&self.id))?;
+        map.serialize_entry("time", &self.time)?;
+        map.serialize_entry("content", &self.content)?;
+        map.end()
+    }
+}
+
 impl EventLog {
     pub fn kind(&self) -> EventKind {
         EventKind::from(&self.content)
@@ -547,9 +1207,17 @@ impl EventLog {
         self.content.symbol()
     }
 
+    pub fn key(&self) -> Option<&ByteVec> {
+        self.content.key()
+    }
+
     pub fn is_about(&self, id: &Identity) -> bool {
         self.content.is_about(id)
     }
+
+    pub fn addresses(&self) -> BTreeSet<Identity> {
+        self.content.addresses()
+    }
 }
 
 #[cfg(test)]
@@ -670,6 +1338,262 @@ mod test {
         assert_eq!(event.symbol(), None);
     }
 
+    #[test]
+    fn event_log_serde() {
+        let i0 = Identity::public_key_raw([0; 28]);
+        let log = EventLog {
+            id: EventId::from(1u64),
+            time: Timestamp::new(1000).unwrap(),
+            content: EventInfo::Send {
+                from: i0,
+                to: i0,
+                symbol: i0,
+                amount: TokenAmount::from(123u64),
+            },
+        };
+
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(json["id"], "0000000000000001");
+        assert_eq!(json["time"], 1000);
+        assert_eq!(json["content"]["kind"], "send");
+        assert_eq!(json["content"]["from"], i0.to_string());
+        assert_eq!(json["content"]["amount"], "123");
+
+        let log = EventLog {
+            content: EventInfo::AccountMultisigSubmit {
+                submitter: i0,
+                account: i0,
+                memo: None,
+                transaction: Box::new(AccountMultisigTransaction::Send(module::ledger::SendArgs {
+                    from: None,
+                    to: i0,
+                    symbol: i0,
+                    amount: TokenAmount::from(1u64),
+                })),
+                token: Some(vec![1, 2].into()),
+                threshold: 2,
+                timeout: Timestamp::new(2000).unwrap(),
+                execute_automatically: false,
+                data: None,
+            },
+            ..log
+        };
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(json["content"]["kind"], "account-multisig-submit");
+        assert_eq!(json["content"]["memo"], serde_json::Value::Null);
+        assert_eq!(json["content"]["token"], "0102");
+        assert_eq!(json["content"]["transaction"]["kind"], "send");
+    }
+
+    #[test]
+    fn event_filter_key_prefix() {
+        let i0 = Identity::public_key_raw([0; 28]);
+        let log = EventLog {
+            id: EventId::from(1u64),
+            time: Timestamp::now(),
+            content: EventInfo::KvStorePut {
+                key: b"/users/1".to_vec().into(),
+                owner: i0,
+                value_hash: Vec::new().into(),
+            },
+        };
+        assert_eq!(log.key(), Some(&ByteVec::from(b"/users/1".to_vec())));
+
+        let filter = EventFilter {
+            key_prefix: Some(b"/users/".to_vec().into()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&log));
+        assert!(!EventFilter {
+            key_prefix: Some(b"/groups/".to_vec().into()),
+            ..Default::default()
+        }
+        .matches(&log));
+        assert!(EventFilter {
+            account: Some(vec![i0].into()),
+            kind: Some(vec![EventKind::KvStorePut, EventKind::KvStoreDelete].into()),
+            ..filter.clone()
+        }
+        .matches(&log));
+
+        // Events not about a key never match a key prefix.
+        let log = EventLog {
+            content: EventInfo::AccountDisable { account: i0 },
+            ..log
+        };
+        assert_eq!(log.key(), None);
+        assert!(!filter.matches(&log));
+    }
+
+    #[test]
+    fn event_filter_any_of_none_of() {
+        let i0 = Identity::public_key_raw([0; 28]);
+        let i1 = Identity::public_key_raw([1; 28]);
+        let log = EventLog {
+            id: EventId::from(1u64),
+            time: Timestamp::now(),
+            content: EventInfo::AccountDisable { account: i0 },
+        };
+
+        let any_of = EventFilter {
+            any_of: Some(EventCriteria {
+                account: Some(vec![i1].into()),
+                kind: Some(vec![EventKind::AccountDisable].into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(any_of.matches(&log));
+
+        let none_of = EventFilter {
+            none_of: Some(EventCriteria {
+                account: Some(vec![i0].into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(!none_of.matches(&log));
+
+        // Empty criteria select everything.
+        assert!(EventFilter {
+            any_of: Some(Default::default()),
+            none_of: Some(Default::default()),
+            ..Default::default()
+        }
+        .matches(&log));
+    }
+
+    #[test]
+    fn event_filter_nested_transaction() {
+        let i0 = Identity::public_key_raw([0; 28]);
+        let i1 = Identity::public_key_raw([1; 28]);
+        let i2 = Identity::public_key_raw([2; 28]);
+        let send = module::account::features::multisig::SubmitTransactionArgs::send(
+            i0,
+            i1,
+            i2,
+            TokenAmount::from(10u64),
+        );
+        let log = EventLog {
+            id: EventId::from(1u64),
+            time: Timestamp::now(),
+            content: EventInfo::AccountMultisigSubmit {
+                submitter: i0,
+                account: Identity::public_key_raw([3; 28]),
+                memo: None,
+                transaction: Box::new(AccountMultisigTransaction::AccountMultisigSubmit(send)),
+                token: None,
+                threshold: 1,
+                timeout: Timestamp::now(),
+                execute_automatically: false,
+                data: None,
+            },
+        };
+
+        assert_eq!(log.symbol(), Some(&i2));
+        assert!(EventFilter {
+            account: Some(vec![i1].into()),
+            transaction_kind: Some(vec![EventKind::Send].into()),
+            ..Default::default()
+        }
+        .matches(&log));
+        assert!(!EventFilter {
+            transaction_kind: Some(vec![EventKind::KvStorePut].into()),
+            ..Default::default()
+        }
+        .matches(&log));
+    }
+
+    #[test]
+    fn decode_without_optional_fields() {
+        let i0 = Identity::public_key_raw([0; 28]);
+        let roles = BTreeMap::from([(i0, BTreeSet::from([module::account::Role::Owner]))]);
+
+        // An AccountAddRoles event encoded before the expiration was added.
+        let mut e = minicbor::Encoder::new(Vec::new());
+        e.map(3)
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .encode(EventKind::AccountAddRoles)
+            .unwrap()
+            .u8(1)
+            .unwrap()
+            .encode(i0)
+            .unwrap()
+            .u8(2)
+            .unwrap()
+            .encode(&roles)
+            .unwrap();
+        let decoded: EventInfo = minicbor::decode(&e.into_writer()).unwrap();
+        assert!(matches!(
+            decoded,
+            EventInfo::AccountAddRoles { account, roles: r, expiration: None }
+                if account == i0 && r == roles
+        ));
+
+        // Required fields are still required.
+        let mut e = minicbor::Encoder::new(Vec::new());
+        e.map(2)
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .encode(EventKind::AccountAddRoles)
+            .unwrap()
+            .u8(2)
+            .unwrap()
+            .encode(&roles)
+            .unwrap();
+        assert!(minicbor::decode::<EventInfo>(&e.into_writer()).is_err());
+    }
+
+    #[test]
+    fn decode_set_defaults_without_weights() {
+        let i0 = Identity::public_key_raw([0; 28]);
+
+        // An AccountMultisigSetDefaults event encoded before the weights and
+        // kind thresholds were added.
+        let mut e = minicbor::Encoder::new(Vec::new());
+        e.map(6)
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .encode(EventKind::AccountMultisigSetDefaults)
+            .unwrap()
+            .u8(1)
+            .unwrap()
+            .encode(i0)
+            .unwrap()
+            .u8(2)
+            .unwrap()
+            .encode(i0)
+            .unwrap()
+            .u8(3)
+            .unwrap()
+            .encode(Some(2u64))
+            .unwrap()
+            .u8(4)
+            .unwrap()
+            .encode(Option::<u64>::None)
+            .unwrap()
+            .u8(5)
+            .unwrap()
+            .encode(Some(true))
+            .unwrap();
+        let decoded: EventInfo = minicbor::decode(&e.into_writer()).unwrap();
+        assert!(matches!(
+            decoded,
+            EventInfo::AccountMultisigSetDefaults {
+                threshold: Some(2),
+                timeout_in_secs: None,
+                execute_automatically: Some(true),
+                weights: None,
+                kind_thresholds: None,
+                ..
+            }
+        ));
+    }
+
     mod event_info {
         use super::super::*;
         use proptest::prelude::*;
@@ -744,9 +1668,57 @@ mod test {
                         threshold: Some(2),
                         timeout_in_secs: None,
                         execute_automatically: Some(false),
+                        weights: Some(BTreeMap::from([(Identity::public_key_raw([3; 28]), 2)])),
+                        kind_thresholds: Some(BTreeMap::from([(EventKind::AccountAddRoles, 3)])),
                     }))
                 );
             }
+
+            #[test]
+            fn submit_call(memo in "\\PC*", method in "[a-z]+\\.[a-zA-Z]+", argument: Vec<u8>) {
+                _assert_serde(
+                    _create_event_info(memo, vec![], AccountMultisigTransaction::AccountMultisigCall(module::account::features::multisig::CallArgs {
+                        method,
+                        argument: argument.into(),
+                    }))
+                );
+            }
+
+            #[test]
+            fn submit_schedule_send(memo in "\\PC*", amount: u64, start: u32, interval: u32) {
+                _assert_serde(
+                    _create_event_info(memo, vec![], AccountMultisigTransaction::ScheduleSend(module::ledger::ScheduleSendArgs {
+                        from: Some(Identity::public_key_raw([2; 28])),
+                        to: Identity::public_key_raw([3; 28]),
+                        symbol: Identity::public_key_raw([4; 28]),
+                        amount: amount.into(),
+                        schedule: module::ledger::Schedule::recurring(
+                            Timestamp::new(start.into()).unwrap(),
+                            interval.into(),
+                            None,
+                        ),
+                    }))
+                );
+            }
+
+            #[test]
+            fn kvstore_expired(key: Vec<u8>, time: u32) {
+                _assert_serde(EventInfo::KvStoreExpired {
+                    key: key.into(),
+                    owner: Identity::public_key_raw([2; 28]),
+                    time: Timestamp::new(time.into()).unwrap(),
+                });
+            }
+
+            #[test]
+            fn account_recovery_finalize(token: Vec<u8>, seed: u8) {
+                _assert_serde(EventInfo::AccountRecoveryFinalize {
+                    account: Identity::public_key_raw([seed; 28]).with_subresource_id_unchecked(1),
+                    token: token.into(),
+                    new_owner: Identity::public_key_raw([2; 28]),
+                    previous_owners: BTreeSet::from([Identity::public_key_raw([seed; 28])]),
+                });
+            }
         }
     }
 }
@@ -5,7 +5,7 @@ use minicbor::{encode, Decode, Decoder, Encode, Encoder};
 use num_bigint::{BigInt, BigUint};
 use num_traits::Num;
 use serde::de::Unexpected;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
 use std::fmt::{Display, Formatter};
 use std::ops::Shr;
 
@@ -173,6 +173,17 @@ impl Display for TokenAmount {
     }
 }
 
+/// Amounts are serialized as decimal strings, as they can be larger than what
+/// most formats (like JSON) support for numbers.
+impl Serialize for TokenAmount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 impl<C> Encode<C> for TokenAmount {
     fn encode<W: encode::Write>(
         &self,
@@ -292,7 +303,7 @@ impl<'de> Deserialize<'de> for TokenAmount {
 #[cfg(test)]
 mod test {
     use super::*;
-    use serde_test::{assert_de_tokens, Token};
+    use serde_test::{assert_de_tokens, assert_ser_tokens, Token};
 
     #[test]
     fn serde_token_amount() {
@@ -306,6 +317,7 @@ mod test {
         assert_de_tokens(&token, &[Token::I32(123)]);
         assert_de_tokens(&token, &[Token::I64(123)]);
         assert_de_tokens(&token, &[Token::String("123")]);
+        assert_ser_tokens(&token, &[Token::Str("123")]);
     }
 
     #[test]
@@ -323,5 +335,6 @@ mod test {
             &token,
             &[Token::String("1_208_925_819_614_629_174_706_175")],
         );
+        assert_ser_tokens(&token, &[Token::Str("1208925819614629174706175")]);
     }
 }
@@ -0,0 +1,311 @@
+use crate::server::module::blockchain::invalid_proof;
+use crate::ManyError;
+use minicbor::bytes::ByteVec;
+use minicbor::{Decode, Encode};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+fn hash_leaf(key: &[u8], value_hash: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update((key.len() as u64).to_be_bytes());
+    hasher.update(key);
+    hasher.update(value_hash);
+    hasher.finalize().to_vec()
+}
+
+fn hash_node(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+/// The largest power of two smaller than `n`, for `n > 1`.
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn subtree_root(leaves: &[Vec<u8>]) -> Vec<u8> {
+    match leaves.len() {
+        0 => Sha256::digest(b"").to_vec(),
+        1 => leaves[0].clone(),
+        n => {
+            let k = split(n);
+            hash_node(&subtree_root(&leaves[..k]), &subtree_root(&leaves[k..]))
+        }
+    }
+}
+
+fn audit_path(index: usize, leaves: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let n = leaves.len();
+    if n <= 1 {
+        return vec![];
+    }
+
+    let k = split(n);
+    if index < k {
+        let mut path = audit_path(index, &leaves[..k]);
+        path.push(subtree_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = audit_path(index - k, &leaves[k..]);
+        path.push(subtree_root(&leaves[..k]));
+        path
+    }
+}
+
+/// Recompute the root of a tree of `size` leaves from a leaf and its audit
+/// path, as in RFC 9162, section 2.1.3.2.
+fn root_from_path(index: u64, size: u64, leaf: Vec<u8>, path: &[ByteVec]) -> Option<Vec<u8>> {
+    if index >= size {
+        return None;
+    }
+
+    let (mut f, mut s) = (index, size - 1);
+    let mut root = leaf;
+    for p in path {
+        if s == 0 {
+            return None;
+        }
+        if f & 1 == 1 || f == s {
+            root = hash_node(p, &root);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            root = hash_node(&root, p);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+
+    if s == 0 {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+/// A Merkle tree of a key-value state, used to prove queries to light clients.
+/// Its leaves are the key-value pairs sorted by key, and its shape and audit
+/// paths are the ones of RFC 6962. Applications whose app hash is the root of
+/// this tree can answer queries with a [Proof].
+#[derive(Clone, Debug, Default)]
+pub struct MerkleTree {
+    /// The keys and value hashes, sorted by key.
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    leaves: Vec<Vec<u8>>,
+}
+
+impl MerkleTree {
+    pub fn new<K: AsRef<[u8]>, V: AsRef<[u8]>>(state: impl IntoIterator<Item = (K, V)>) -> Self {
+        let state: BTreeMap<Vec<u8>, Vec<u8>> = state
+            .into_iter()
+            .map(|(k, v)| (k.as_ref().to_vec(), Sha256::digest(v.as_ref()).to_vec()))
+            .collect();
+        let leaves = state.iter().map(|(k, h)| hash_leaf(k, h)).collect();
+
+        Self {
+            entries: state.into_iter().collect(),
+            leaves,
+        }
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        subtree_root(&self.leaves)
+    }
+
+    fn leaf(&self, index: usize) -> ProofLeaf {
+        let (key, value_hash) = &self.entries[index];
+        ProofLeaf {
+            index: index as u64,
+            key: key.clone().into(),
+            value_hash: value_hash.clone().into(),
+            path: audit_path(index, &self.leaves)
+                .into_iter()
+                .map(ByteVec::from)
+                .collect(),
+        }
+    }
+
+    /// Prove the value of a key, or that it has no value, in the state of the
+    /// block at a height, whose app hash is the root of this tree.
+    pub fn prove(&self, height: u64, key: &[u8]) -> Proof {
+        let leaves = match self
+            .entries
+            .binary_search_by(|(k, _)| k.as_slice().cmp(key))
+        {
+            Ok(index) => vec![self.leaf(index)],
+            Err(index) => {
+                let mut leaves = Vec::new();
+                if index > 0 {
+                    leaves.push(self.leaf(index - 1));
+                }
+                if index < self.entries.len() {
+                    leaves.push(self.leaf(index));
+                }
+                leaves
+            }
+        };
+
+        Proof {
+            key: key.to_vec().into(),
+            size: self.entries.len() as u64,
+            leaves,
+            height,
+            root: self.root().into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct ProofLeaf {
+    #[n(0)]
+    pub index: u64,
+
+    #[n(1)]
+    pub key: ByteVec,
+
+    /// The SHA-256 of the value of the key.
+    #[n(2)]
+    pub value_hash: ByteVec,
+
+    #[n(3)]
+    pub path: Vec<ByteVec>,
+}
+
+/// A proof that a key has a value, or no value, in a [MerkleTree]. An inclusion
+/// proof contains the leaf of the key. An exclusion proof contains the leaves
+/// around where the key would be.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+#[cbor(map)]
+pub struct Proof {
+    #[n(0)]
+    pub key: ByteVec,
+
+    /// The number of leaves of the tree.
+    #[n(1)]
+    pub size: u64,
+
+    #[n(2)]
+    pub leaves: Vec<ProofLeaf>,
+
+    /// The height of the block whose state is proven.
+    #[n(3)]
+    pub height: u64,
+
+    /// The root of the tree, i.e. the app hash of the block at `height`.
+    #[n(4)]
+    pub root: ByteVec,
+}
+
+impl Proof {
+    /// Verify that the key of this proof has this value, or no value if None, in
+    /// the state of a trusted root, e.g. the app hash of a committed block.
+    pub fn verify(&self, root: &[u8], value: Option<&[u8]>) -> Result<(), ManyError> {
+        if self.root.as_slice() != root {
+            return Err(invalid_proof());
+        }
+        for leaf in &self.leaves {
+            let hash = hash_leaf(&leaf.key, &leaf.value_hash);
+            if root_from_path(leaf.index, self.size, hash, &leaf.path).as_deref() != Some(root) {
+                return Err(invalid_proof());
+            }
+        }
+
+        let key = self.key.as_slice();
+        let value_hash = match self.leaves.as_slice() {
+            [] if self.size == 0 && subtree_root(&[]) == root => None,
+            [leaf] if leaf.key.as_slice() == key => Some(leaf.value_hash.as_slice()),
+            [leaf] if leaf.index == 0 && key < leaf.key.as_slice() => None,
+            [leaf] if leaf.index + 1 == self.size && leaf.key.as_slice() < key => None,
+            [left, right]
+                if left.index + 1 == right.index
+                    && left.key.as_slice() < key
+                    && key < right.key.as_slice() =>
+            {
+                None
+            }
+            _ => return Err(invalid_proof()),
+        };
+
+        match (value_hash, value) {
+            (None, None) => Ok(()),
+            (Some(hash), Some(value)) if Sha256::digest(value).as_slice() == hash => Ok(()),
+            _ => Err(invalid_proof()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn tree(size: u8) -> MerkleTree {
+        MerkleTree::new((0..size).map(|i| (vec![i * 2], vec![i; 4])))
+    }
+
+    #[test]
+    fn empty() {
+        let tree = tree(0);
+        let proof = tree.prove(1, b"key");
+        assert!(proof.leaves.is_empty());
+        assert_eq!(proof.verify(&tree.root(), None), Ok(()));
+        assert_eq!(
+            proof.verify(&tree.root(), Some(b"value")),
+            Err(invalid_proof())
+        );
+        assert_eq!(proof.verify(&[0; 32], None), Err(invalid_proof()));
+    }
+
+    #[test]
+    fn tampered() {
+        let tree = tree(5);
+        let root = tree.root();
+
+        let mut proof = tree.prove(1, &[4]);
+        assert_eq!(proof.verify(&root, Some(&[2; 4])), Ok(()));
+        assert_eq!(proof.verify(&root, Some(&[3; 4])), Err(invalid_proof()));
+        assert_eq!(proof.verify(&root, None), Err(invalid_proof()));
+        proof.leaves[0].index = 3;
+        assert_eq!(proof.verify(&root, Some(&[2; 4])), Err(invalid_proof()));
+
+        // Hiding a key between two leaves that are not adjacent.
+        let mut proof = tree.prove(1, &[3]);
+        let right = tree.prove(1, &[6]).leaves.remove(0);
+        proof.leaves[1] = right;
+        assert_eq!(proof.verify(&root, None), Err(invalid_proof()));
+
+        // A proof for another root.
+        let mut proof = tree.prove(1, &[4]);
+        proof.root = vec![0; 32].into();
+        assert_eq!(proof.verify(&root, Some(&[2; 4])), Err(invalid_proof()));
+
+        // A proof of a key is not a proof of another one.
+        let mut proof = tree.prove(1, &[4]);
+        proof.key = vec![5].into();
+        assert_eq!(proof.verify(&root, Some(&[2; 4])), Err(invalid_proof()));
+    }
+
+    proptest! {
+        #[test]
+        fn inclusion_and_exclusion(size in 0u8..40, key in 0u8..90) {
+            let tree = tree(size);
+            let proof = tree.prove(1, &[key]);
+            let decoded: Proof = minicbor::decode(&minicbor::to_vec(&proof).unwrap()).unwrap();
+            prop_assert_eq!(&decoded, &proof);
+
+            let value = if key % 2 == 0 && key / 2 < size {
+                Some(vec![key / 2; 4])
+            } else {
+                None
+            };
+            prop_assert_eq!(proof.verify(&tree.root(), value.as_deref()), Ok(()));
+        }
+    }
+}